@@ -0,0 +1,281 @@
+use crate::matrix::*;
+use crate::point;
+use crate::ray::Ray;
+use crate::tuple::*;
+
+// An axis aligned bounding box. The empty box has min at +infinity and max
+// at -infinity, so adding anything to it gives a box around that thing.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct BoundingBox {
+    pub min: Tuple,
+    pub max: Tuple,
+}
+
+impl Default for BoundingBox {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl BoundingBox {
+    pub fn new(min: Tuple, max: Tuple) -> Self {
+        Self { min, max }
+    }
+
+    pub fn empty() -> Self {
+        Self {
+            min: point!(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+            max: point!(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+        }
+    }
+
+    pub fn infinite() -> Self {
+        Self {
+            min: point!(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+            max: point!(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn is_finite(&self) -> bool {
+        [
+            self.min.x, self.min.y, self.min.z, self.max.x, self.max.y, self.max.z,
+        ]
+        .iter()
+        .all(|value| value.is_finite())
+    }
+
+    pub fn add_point(&mut self, point: Tuple) {
+        self.min = point!(
+            self.min.x.min(point.x),
+            self.min.y.min(point.y),
+            self.min.z.min(point.z)
+        );
+        self.max = point!(
+            self.max.x.max(point.x),
+            self.max.y.max(point.y),
+            self.max.z.max(point.z)
+        );
+    }
+
+    pub fn merge(&mut self, other: &BoundingBox) {
+        if other.is_empty() {
+            return;
+        }
+        self.add_point(other.min);
+        self.add_point(other.max);
+    }
+
+    pub fn contains_point(&self, point: Tuple) -> bool {
+        (self.min.x..=self.max.x).contains(&point.x)
+            && (self.min.y..=self.max.y).contains(&point.y)
+            && (self.min.z..=self.max.z).contains(&point.z)
+    }
+
+    pub fn contains_box(&self, other: &BoundingBox) -> bool {
+        self.contains_point(other.min) && self.contains_point(other.max)
+    }
+
+    pub fn centroid(&self) -> Tuple {
+        point!(
+            (self.min.x + self.max.x) / 2.0,
+            (self.min.y + self.max.y) / 2.0,
+            (self.min.z + self.max.z) / 2.0
+        )
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        if self.is_empty() {
+            return 0.0;
+        }
+        let dx = self.max.x - self.min.x;
+        let dy = self.max.y - self.min.y;
+        let dz = self.max.z - self.min.z;
+        2.0 * (dx * dy + dy * dz + dz * dx)
+    }
+
+    // The box around all eight corners once transformed. Infinite boxes stay
+    // infinite, as multiplying infinity by the zeros of the matrix is NaN.
+    pub fn transform(&self, transform: &Matrix4) -> BoundingBox {
+        if self.is_empty() {
+            return *self;
+        }
+        if !self.is_finite() {
+            return BoundingBox::infinite();
+        }
+
+        let mut result = BoundingBox::empty();
+        for x in [self.min.x, self.max.x] {
+            for y in [self.min.y, self.max.y] {
+                for z in [self.min.z, self.max.z] {
+                    result.add_point(transform * point!(x, y, z));
+                }
+            }
+        }
+        result
+    }
+
+    // Slab test, like Cube but with arbitrary bounds. Dividing by a zero
+    // direction gives infinities that work out, and NaN from 0 / 0 is
+    // ignored by f64::max and f64::min.
+    pub fn intersects(&self, ray: &Ray) -> bool {
+        if self.is_empty() {
+            return false;
+        }
+
+        let (xtmin, xtmax) = check_axis(ray.origin.x, ray.direction.x, self.min.x, self.max.x);
+        let (ytmin, ytmax) = check_axis(ray.origin.y, ray.direction.y, self.min.y, self.max.y);
+        let (ztmin, ztmax) = check_axis(ray.origin.z, ray.direction.z, self.min.z, self.max.z);
+
+        let tmin = xtmin.max(ytmin).max(ztmin);
+        let tmax = xtmax.min(ytmax).min(ztmax);
+
+        tmin <= tmax && tmax >= 0.0
+    }
+}
+
+fn check_axis(origin: f64, direction: f64, min: f64, max: f64) -> (f64, f64) {
+    let tmin = (min - origin) / direction;
+    let tmax = (max - origin) / direction;
+
+    if tmin > tmax {
+        (tmax, tmin)
+    } else {
+        (tmin, tmax)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vector;
+    use std::f64::consts::{FRAC_PI_4, SQRT_2};
+
+    #[test]
+    fn test_empty_box() {
+        let bounds = BoundingBox::empty();
+
+        assert!(bounds.is_empty());
+        assert_eq!(bounds.surface_area(), 0.0);
+    }
+
+    #[test]
+    fn test_adding_points() {
+        let mut bounds = BoundingBox::empty();
+        bounds.add_point(point!(-5.0, 2.0, 0.0));
+        bounds.add_point(point!(7.0, 0.0, -3.0));
+
+        assert_eq!(bounds.min, point!(-5.0, 0.0, -3.0));
+        assert_eq!(bounds.max, point!(7.0, 2.0, 0.0));
+    }
+
+    #[test]
+    fn test_merging_boxes() {
+        let mut a = BoundingBox::new(point!(-5.0, -2.0, 0.0), point!(7.0, 4.0, 4.0));
+        let b = BoundingBox::new(point!(8.0, -7.0, -2.0), point!(14.0, 2.0, 8.0));
+        a.merge(&b);
+        a.merge(&BoundingBox::empty());
+
+        assert_eq!(a.min, point!(-5.0, -7.0, -2.0));
+        assert_eq!(a.max, point!(14.0, 4.0, 8.0));
+    }
+
+    #[test]
+    fn test_contains_point() {
+        let bounds = BoundingBox::new(point!(5.0, -2.0, 0.0), point!(11.0, 4.0, 7.0));
+        let examples = [
+            (point!(5.0, -2.0, 0.0), true),
+            (point!(11.0, 4.0, 7.0), true),
+            (point!(8.0, 1.0, 3.0), true),
+            (point!(3.0, 0.0, 3.0), false),
+            (point!(8.0, -4.0, 3.0), false),
+            (point!(8.0, 1.0, -1.0), false),
+            (point!(13.0, 1.0, 3.0), false),
+            (point!(8.0, 5.0, 3.0), false),
+            (point!(8.0, 1.0, 8.0), false),
+        ];
+
+        for (point, result) in examples {
+            assert_eq!(bounds.contains_point(point), result);
+        }
+    }
+
+    #[test]
+    fn test_contains_box() {
+        let bounds = BoundingBox::new(point!(5.0, -2.0, 0.0), point!(11.0, 4.0, 7.0));
+        let examples = [
+            (point!(5.0, -2.0, 0.0), point!(11.0, 4.0, 7.0), true),
+            (point!(6.0, -1.0, 1.0), point!(10.0, 3.0, 6.0), true),
+            (point!(4.0, -3.0, -1.0), point!(10.0, 3.0, 6.0), false),
+            (point!(6.0, -1.0, 1.0), point!(12.0, 5.0, 8.0), false),
+        ];
+
+        for (min, max, result) in examples {
+            assert_eq!(bounds.contains_box(&BoundingBox::new(min, max)), result);
+        }
+    }
+
+    #[test]
+    fn test_transforming_box() {
+        let bounds = BoundingBox::new(point!(-1.0, -1.0, -1.0), point!(1.0, 1.0, 1.0));
+        let transform = Matrix4::identity()
+            .rotation_y(FRAC_PI_4)
+            .rotation_x(FRAC_PI_4);
+
+        let transformed = bounds.transform(&transform);
+
+        assert_eq!(transformed.min, point!(-SQRT_2, -1.70711, -1.70711));
+        assert_eq!(transformed.max, point!(SQRT_2, 1.70711, 1.70711));
+    }
+
+    #[test]
+    fn test_transforming_infinite_box() {
+        let bounds = BoundingBox::new(
+            point!(f64::NEG_INFINITY, 0.0, f64::NEG_INFINITY),
+            point!(f64::INFINITY, 0.0, f64::INFINITY),
+        );
+
+        let transformed = bounds.transform(&Matrix4::identity().translation(0.0, 1.0, 0.0));
+
+        // Tuple equality takes a difference, which is NaN for infinities
+        assert!(!transformed.is_finite());
+        assert!(transformed.contains_point(point!(1e9, -1e9, 1e9)));
+    }
+
+    #[test]
+    fn test_intersecting_ray_with_box() {
+        let bounds = BoundingBox::new(point!(5.0, -2.0, 0.0), point!(11.0, 4.0, 7.0));
+        let examples = [
+            (point!(15.0, 1.0, 2.0), vector!(-1.0, 0.0, 0.0), true),
+            (point!(-5.0, -1.0, 4.0), vector!(1.0, 0.0, 0.0), true),
+            (point!(7.0, 6.0, 5.0), vector!(0.0, -1.0, 0.0), true),
+            (point!(9.0, -5.0, 6.0), vector!(0.0, 1.0, 0.0), true),
+            (point!(8.0, 2.0, 12.0), vector!(0.0, 0.0, -1.0), true),
+            (point!(6.0, 0.0, -5.0), vector!(0.0, 0.0, 1.0), true),
+            (point!(8.0, 1.0, 3.5), vector!(0.0, 0.0, 1.0), true),
+            (point!(9.0, -1.0, -8.0), vector!(2.0, 4.0, 6.0), false),
+            (point!(8.0, 3.0, -4.0), vector!(6.0, 2.0, 4.0), false),
+            (point!(9.0, -1.0, -2.0), vector!(4.0, 6.0, 2.0), false),
+            (point!(4.0, 0.0, 9.0), vector!(0.0, 0.0, -1.0), false),
+            (point!(8.0, 6.0, -1.0), vector!(0.0, -1.0, 0.0), false),
+            (point!(12.0, 5.0, 4.0), vector!(-1.0, 0.0, 0.0), false),
+            // pointing away from the box
+            (point!(15.0, 1.0, 2.0), vector!(1.0, 0.0, 0.0), false),
+        ];
+
+        for (origin, direction, result) in examples {
+            let ray = Ray::new(origin, direction.norm());
+            assert_eq!(bounds.intersects(&ray), result);
+        }
+    }
+
+    #[test]
+    fn test_intersecting_ray_with_infinite_box() {
+        let ray = Ray::new(point!(0.0, 5.0, 0.0), vector!(0.0, 0.0, 1.0));
+
+        assert!(BoundingBox::infinite().intersects(&ray));
+    }
+}
@@ -0,0 +1,342 @@
+use crate::bounds::BoundingBox;
+use crate::ray::*;
+use crate::shape::Shape;
+use crate::tuple::Tuple;
+
+// leaves with this many objects or less are never split
+const MIN_LEAF_SIZE: usize = 4;
+// leaves with more objects than this are always split, even when the
+// surface area heuristic says it is not worth it
+const MAX_LEAF_SIZE: usize = 32;
+// cost of testing a ray against a node, relative to testing an object
+const TRAVERSAL_COST: f64 = 0.125;
+
+#[derive(Debug)]
+enum Node {
+    // objects are indices[first..first + count]
+    Leaf {
+        bounds: BoundingBox,
+        first: usize,
+        count: usize,
+    },
+    Interior {
+        bounds: BoundingBox,
+        left: usize,
+        right: usize,
+    },
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Item {
+    index: usize,
+    bounds: BoundingBox,
+    centroid: Tuple,
+}
+
+// A bounding volume hierarchy over a list of shapes, split with the surface
+// area heuristic. It only keeps indices, so the shapes stay wherever they
+// are, and has to be rebuilt when they change. Shapes with infinite bounds,
+// like planes, are kept aside and tested against every ray.
+#[derive(Debug, Default)]
+pub struct Bvh {
+    nodes: Vec<Node>,
+    indices: Vec<usize>,
+    unbounded: Vec<usize>,
+    len: usize,
+}
+
+impl Bvh {
+    pub fn build(objects: &[Box<dyn Shape>]) -> Bvh {
+        let bounds: Vec<BoundingBox> = objects
+            .iter()
+            .map(|object| object.parent_space_bounds())
+            .collect();
+        Bvh::from_bounds(&bounds)
+    }
+
+    pub fn from_bounds(bounds: &[BoundingBox]) -> Bvh {
+        let mut bvh = Bvh {
+            len: bounds.len(),
+            ..Default::default()
+        };
+
+        let mut items = vec![];
+        for (index, bounds) in bounds.iter().enumerate() {
+            if bounds.is_empty() {
+                continue;
+            }
+            if bounds.is_finite() {
+                items.push(Item {
+                    index,
+                    bounds: *bounds,
+                    centroid: bounds.centroid(),
+                });
+            } else {
+                bvh.unbounded.push(index);
+            }
+        }
+
+        if !items.is_empty() {
+            bvh.build_node(&mut items, 0);
+        }
+        bvh.indices = items.iter().map(|item| item.index).collect();
+        bvh
+    }
+
+    // number of objects the hierarchy was built for
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn bounds(&self) -> BoundingBox {
+        match self.nodes.first() {
+            Some(Node::Leaf { bounds, .. }) | Some(Node::Interior { bounds, .. }) => *bounds,
+            None => BoundingBox::empty(),
+        }
+    }
+
+    // Indices of the objects whose bounds are hit by the ray, in no
+    // particular order
+    pub fn candidates(&self, ray: &Ray) -> Vec<usize> {
+        let mut result = self.unbounded.clone();
+        if self.nodes.is_empty() {
+            return result;
+        }
+
+        let mut stack = vec![0];
+        while let Some(node) = stack.pop() {
+            match &self.nodes[node] {
+                Node::Leaf {
+                    bounds,
+                    first,
+                    count,
+                } => {
+                    if bounds.intersects(ray) {
+                        result.extend_from_slice(&self.indices[*first..*first + *count]);
+                    }
+                }
+                Node::Interior {
+                    bounds,
+                    left,
+                    right,
+                } => {
+                    if bounds.intersects(ray) {
+                        stack.push(*right);
+                        stack.push(*left);
+                    }
+                }
+            }
+        }
+
+        result
+    }
+
+    // Intersections with every object the ray may hit, unsorted. The objects
+    // have to be the ones the hierarchy was built from.
+    pub fn intersect<'a>(&self, objects: &'a [Box<dyn Shape>], ray: &Ray) -> Vec<Intersection<'a>> {
+        let mut intersections = vec![];
+        for index in self.candidates(ray) {
+            let mut isects = ray.intersect(objects[index].as_ref());
+            intersections.append(&mut isects);
+        }
+        intersections
+    }
+
+    // Builds the subtree for items, which start at first in the final list
+    // of indices, and returns the index of its root node
+    fn build_node(&mut self, items: &mut [Item], first: usize) -> usize {
+        let mut bounds = BoundingBox::empty();
+        for item in items.iter() {
+            bounds.merge(&item.bounds);
+        }
+
+        let node = self.nodes.len();
+        self.nodes.push(Node::Leaf {
+            bounds,
+            first,
+            count: items.len(),
+        });
+
+        if items.len() <= MIN_LEAF_SIZE {
+            return node;
+        }
+
+        let split = match best_split(items, &bounds) {
+            Some(split) => split,
+            None => return node,
+        };
+
+        let (left_items, right_items) = items.split_at_mut(split);
+        let left = self.build_node(left_items, first);
+        let right = self.build_node(right_items, first + split);
+        self.nodes[node] = Node::Interior {
+            bounds,
+            left,
+            right,
+        };
+
+        node
+    }
+}
+
+fn axis_value(point: &Tuple, axis: usize) -> f64 {
+    match axis {
+        0 => point.x,
+        1 => point.y,
+        _ => point.z,
+    }
+}
+
+fn sort_by_axis(items: &mut [Item], axis: usize) {
+    items.sort_by(|a, b| {
+        axis_value(&a.centroid, axis)
+            .partial_cmp(&axis_value(&b.centroid, axis))
+            .unwrap()
+    });
+}
+
+// Tries every split along every axis, with the items sorted by centroid, and
+// returns where to split once the items are sorted on the best axis. None
+// when keeping everything in a leaf is cheaper.
+fn best_split(items: &mut [Item], bounds: &BoundingBox) -> Option<usize> {
+    let count = items.len();
+    let area = bounds.surface_area();
+    let mut best: Option<(f64, usize, usize)> = None;
+
+    for axis in 0..3 {
+        sort_by_axis(items, axis);
+
+        // right_areas[i] is the area around items[i..]
+        let mut right_areas = vec![0.0; count];
+        let mut right = BoundingBox::empty();
+        for i in (1..count).rev() {
+            right.merge(&items[i].bounds);
+            right_areas[i] = right.surface_area();
+        }
+
+        let mut left = BoundingBox::empty();
+        for i in 1..count {
+            left.merge(&items[i - 1].bounds);
+            let cost = if area > 0.0 {
+                TRAVERSAL_COST
+                    + (left.surface_area() * i as f64 + right_areas[i] * (count - i) as f64) / area
+            } else {
+                // everything is flat, fall back to splitting in the middle
+                (count / 2).abs_diff(i) as f64
+            };
+
+            if best.map_or(true, |(best_cost, _, _)| cost < best_cost) {
+                best = Some((cost, axis, i));
+            }
+        }
+    }
+
+    let (cost, axis, split) = best?;
+    if area > 0.0 && cost >= count as f64 && count <= MAX_LEAF_SIZE {
+        return None;
+    }
+
+    sort_by_axis(items, axis);
+    Some(split)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matrix::Matrix4;
+    use crate::plane::Plane;
+    use crate::point;
+    use crate::sphere::Sphere;
+    use crate::vector;
+
+    // a row of unit spheres along x, three units apart
+    fn spheres(count: usize) -> Vec<Box<dyn Shape>> {
+        (0..count)
+            .map(|i| {
+                let mut sphere = Sphere::init();
+                sphere.set_transform(Matrix4::identity().translation(i as f64 * 3.0, 0.0, 0.0));
+                Box::new(sphere) as Box<dyn Shape>
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_empty_hierarchy() {
+        let bvh = Bvh::build(&[]);
+        let ray = Ray::new(point!(0.0, 0.0, -5.0), vector!(0.0, 0.0, 1.0));
+
+        assert!(bvh.is_empty());
+        assert!(bvh.candidates(&ray).is_empty());
+    }
+
+    #[test]
+    fn test_bounds_of_hierarchy() {
+        let bvh = Bvh::build(&spheres(10));
+
+        assert_eq!(
+            bvh.bounds(),
+            BoundingBox::new(point!(-1.0, -1.0, -1.0), point!(28.0, 1.0, 1.0))
+        );
+    }
+
+    #[test]
+    fn test_large_hierarchies_are_split() {
+        let bvh = Bvh::build(&spheres(100));
+
+        assert!(bvh.nodes.len() > 1);
+        for node in &bvh.nodes {
+            if let Node::Leaf { count, .. } = node {
+                assert!(*count <= MAX_LEAF_SIZE);
+            }
+        }
+    }
+
+    #[test]
+    fn test_candidates_are_only_objects_along_the_ray() {
+        let objects = spheres(100);
+        let bvh = Bvh::build(&objects);
+        let ray = Ray::new(point!(30.0, 0.0, -5.0), vector!(0.0, 0.0, 1.0));
+
+        let candidates = bvh.candidates(&ray);
+        let intersections = bvh.intersect(&objects, &ray);
+
+        assert!(candidates.contains(&10));
+        assert!(candidates.len() <= MAX_LEAF_SIZE);
+        assert_eq!(intersections.len(), 2);
+        assert_eq!(intersections[0].object, objects[10].as_ref());
+    }
+
+    #[test]
+    fn test_same_intersections_as_testing_every_object() {
+        let objects = spheres(50);
+        let bvh = Bvh::build(&objects);
+        let ray = Ray::new(point!(-5.0, 0.5, 0.0), vector!(1.0, 0.0, 0.0));
+
+        let mut expected: Vec<f64> = objects
+            .iter()
+            .flat_map(|object| ray.intersect(object.as_ref()))
+            .map(|i| i.t)
+            .collect();
+        let mut found: Vec<f64> = bvh.intersect(&objects, &ray).iter().map(|i| i.t).collect();
+        expected.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        found.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn test_unbounded_objects_are_always_candidates() {
+        let mut objects = spheres(10);
+        objects.push(Box::new(Plane::init()));
+        let bvh = Bvh::build(&objects);
+        let ray = Ray::new(point!(0.0, 5.0, 0.0), vector!(0.0, -1.0, 0.0).norm());
+        let missing = Ray::new(point!(100.0, 5.0, 0.0), vector!(0.0, 1.0, 0.0));
+
+        assert!(bvh.candidates(&ray).contains(&10));
+        assert_eq!(bvh.candidates(&missing), vec![10]);
+    }
+}
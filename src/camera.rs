@@ -1,14 +1,104 @@
 extern crate approx;
-use crate::{canvas::Canvas, matrix::Matrix, point, ray::Ray, tuple::Tuple, world::World};
+use crate::{
+    canvas::Canvas,
+    color::Color,
+    matrix::{Matrix4, Transform},
+    point,
+    ray::Ray,
+    sampling::{Filter, SamplePattern},
+    tuple::Tuple,
+    world::World,
+};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// side of the square tiles the image is split into for rendering
+pub const TILE_SIZE: usize = 16;
+
+// A rectangle of pixels rendered as one unit of work. Tiles on the right and
+// bottom edges are smaller when the image size is not a multiple of TILE_SIZE.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tile {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+// Passed to the progress callback every time a tile is done
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Progress {
+    pub tiles_done: usize,
+    pub tiles_total: usize,
+    pub elapsed: Duration,
+}
+
+impl Progress {
+    pub fn fraction(&self) -> f64 {
+        if self.tiles_total == 0 {
+            1.0
+        } else {
+            self.tiles_done as f64 / self.tiles_total as f64
+        }
+    }
+
+    // estimated time left, assuming the remaining tiles take as long as the
+    // ones done so far. None until the first tile is done.
+    pub fn eta(&self) -> Option<Duration> {
+        if self.tiles_done == 0 {
+            return None;
+        }
+        let remaining = (self.tiles_total - self.tiles_done) as f64;
+        Some(self.elapsed.mul_f64(remaining / self.tiles_done as f64))
+    }
+}
+
+// Stops a render from another thread, or from the progress callback.
+// Clones share the same flag.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+// What render_with_progress returns. When cancelled, the tiles that were not
+// rendered are left black in the canvas.
+pub struct Rendered {
+    pub canvas: Canvas,
+    pub cancelled: bool,
+    pub elapsed: Duration,
+}
 
 pub struct Camera {
     pub hsize: usize,
     pub vsize: usize,
     pub field_of_view: f64,
-    pub transform: Matrix,
+    transform: Transform,
     pixel_size: f64,
     half_width: f64,
     half_height: f64,
+    // how many threads render calls use, all available cores by default
+    pub threads: usize,
+    // rays per pixel, where they go and how they are combined. One sample
+    // through the pixel center is what the camera always did.
+    pub samples: usize,
+    pub sample_pattern: SamplePattern,
+    pub filter: Filter,
 }
 
 impl Camera {
@@ -29,20 +119,37 @@ impl Camera {
             hsize,
             vsize,
             field_of_view,
-            transform: Matrix::identity(4),
+            transform: Transform::default(),
             pixel_size: half_width * 2.0 / hsize as f64,
             half_width,
             half_height,
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            samples: 1,
+            sample_pattern: SamplePattern::Regular,
+            filter: Filter::Box,
         }
     }
 
+    pub fn transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    pub fn set_transform(&mut self, transform: Matrix4) {
+        self.transform = Transform::new(transform);
+    }
+
     pub fn pixel_size(&self) -> f64 {
-        return self.pixel_size;
+        self.pixel_size
     }
 
     pub fn ray_for_pixel(&self, x: usize, y: usize) -> Ray {
-        let x_offset = (x as f64 + 0.5) * self.pixel_size;
-        let y_offset = (y as f64 + 0.5) * self.pixel_size;
+        self.ray_for_sample(x as f64 + 0.5, y as f64 + 0.5)
+    }
+
+    // the ray through any point of the image, in pixels from its top left corner
+    pub fn ray_for_sample(&self, x: f64, y: f64) -> Ray {
+        let x_offset = x * self.pixel_size;
+        let y_offset = y * self.pixel_size;
 
         let world_x = self.half_width - x_offset;
         let world_y = self.half_height - y_offset;
@@ -53,20 +160,137 @@ impl Camera {
 
         Ray::new(origin, direction)
     }
+
+    // the tiles covering the image, row by row
+    pub fn tiles(&self) -> Vec<Tile> {
+        let mut tiles = vec![];
+        for y in (0..self.vsize).step_by(TILE_SIZE) {
+            for x in (0..self.hsize).step_by(TILE_SIZE) {
+                tiles.push(Tile {
+                    x,
+                    y,
+                    width: TILE_SIZE.min(self.hsize - x),
+                    height: TILE_SIZE.min(self.vsize - y),
+                });
+            }
+        }
+        tiles
+    }
+
+    // the colors of every pixel of the tile, row by row
+    pub fn render_tile(&self, world: &World, tile: &Tile) -> Vec<Color> {
+        let mut colors = Vec::with_capacity(tile.width * tile.height);
+        for y in tile.y..tile.y + tile.height {
+            for x in tile.x..tile.x + tile.width {
+                colors.push(self.render_pixel(world, x, y));
+            }
+        }
+        colors
+    }
+
+    // Spreads the samples over the whole filter footprint around the pixel
+    // center and takes their weighted average. A negative or zero total weight,
+    // which Mitchell can give with few samples, falls back to the plain average.
+    fn render_pixel(&self, world: &World, x: usize, y: usize) -> Color {
+        let samples = self.sample_pattern.samples(self.samples.max(1), x, y);
+        let width = 2.0 * self.filter.radius();
+        let mut weighted = Color::new(0.0, 0.0, 0.0);
+        let mut unweighted = Color::new(0.0, 0.0, 0.0);
+        let mut total_weight = 0.0;
+
+        for &(u, v) in samples.iter() {
+            let (dx, dy) = ((u - 0.5) * width, (v - 0.5) * width);
+            let ray = self.ray_for_sample(x as f64 + 0.5 + dx, y as f64 + 0.5 + dy);
+            let color = world.color_at(&ray);
+            let weight = self.filter.weight(dx, dy);
+
+            weighted = weighted + color * weight;
+            unweighted = unweighted + color;
+            total_weight += weight;
+        }
+
+        if total_weight > 0.0 {
+            weighted * (1.0 / total_weight)
+        } else {
+            unweighted * (1.0 / samples.len() as f64)
+        }
+    }
+}
+
+fn write_tile(canvas: &mut Canvas, tile: &Tile, colors: &[Color]) {
+    for (index, color) in colors.iter().enumerate() {
+        let x = tile.x + index % tile.width;
+        let y = tile.y + index / tile.width;
+        canvas.write_pixel(x, y, *color);
+    }
 }
 
 pub fn render(camera: &Camera, world: &World) -> Canvas {
+    render_with_progress(camera, world, |_| {}, &CancellationToken::new()).canvas
+}
+
+// Renders the world on camera.threads threads, each one taking the next tile
+// that is not rendered yet. Every pixel only depends on its own ray, so the
+// result is the same whatever the number of threads.
+//
+// Finished tiles are sent back to the calling thread, which writes them to
+// the canvas and calls progress. Once cancel is triggered no new tile is
+// started, and the canvas is returned with the tiles done so far.
+pub fn render_with_progress<F>(
+    camera: &Camera,
+    world: &World,
+    mut progress: F,
+    cancel: &CancellationToken,
+) -> Rendered
+where
+    F: FnMut(Progress),
+{
+    let start = Instant::now();
     let mut canvas = Canvas::init(camera.hsize, camera.vsize);
+    let tiles = camera.tiles();
+    let next_tile = AtomicUsize::new(0);
+    let threads = camera.threads.clamp(1, tiles.len().max(1));
+    let mut tiles_done = 0;
 
-    for y in 0..camera.vsize {
-        for x in 0..camera.hsize {
-            let ray = camera.ray_for_pixel(x, y);
-            let color = world.color_at(&ray);
-            canvas.write_pixel(x, y, color);
+    thread::scope(|scope| {
+        let (sender, receiver) = mpsc::sync_channel::<(Tile, Vec<Color>)>(threads);
+
+        for _ in 0..threads {
+            let sender = sender.clone();
+            let tiles = &tiles;
+            let next_tile = &next_tile;
+            scope.spawn(move || {
+                while !cancel.is_cancelled() {
+                    let tile = match tiles.get(next_tile.fetch_add(1, Ordering::Relaxed)) {
+                        Some(tile) => tile,
+                        None => break,
+                    };
+                    let colors = camera.render_tile(world, tile);
+                    if sender.send((*tile, colors)).is_err() {
+                        break;
+                    }
+                }
+            });
         }
-    }
+        // the loop below ends once every worker has dropped its sender
+        drop(sender);
+
+        for (tile, colors) in receiver {
+            write_tile(&mut canvas, &tile, &colors);
+            tiles_done += 1;
+            progress(Progress {
+                tiles_done,
+                tiles_total: tiles.len(),
+                elapsed: start.elapsed(),
+            });
+        }
+    });
 
-    canvas
+    Rendered {
+        canvas,
+        cancelled: tiles_done < tiles.len(),
+        elapsed: start.elapsed(),
+    }
 }
 
 #[cfg(test)]
@@ -74,7 +298,10 @@ mod tests {
     use super::*;
 
     use crate::color::Color;
+    use crate::cube::Cube;
+    use crate::light::Light;
     use crate::matrix;
+    use crate::shape::Shape;
     use crate::vector;
     use approx::relative_eq;
     use core::f64::consts::PI;
@@ -91,7 +318,7 @@ mod tests {
         assert_eq!(camera.hsize, 160);
         assert_eq!(camera.vsize, 120);
         assert_eq!(camera.field_of_view, PI / 2.0);
-        assert_eq!(camera.transform, Matrix::identity(4));
+        assert_eq!(camera.transform(), &Matrix4::identity());
     }
 
     #[test]
@@ -124,18 +351,93 @@ mod tests {
     #[test]
     fn test_constructing_a_ray_when_the_camera_is_transformed() {
         let mut camera = Camera::new(201, 101, FRAC_PI_2);
-        camera.transform = Matrix::identity(4)
-            .translation(0.0, -2.0, 5.0)
-            .rotation_y(FRAC_PI_4);
+        camera.set_transform(
+            Matrix4::identity()
+                .translation(0.0, -2.0, 5.0)
+                .rotation_y(FRAC_PI_4),
+        );
         let ray = camera.ray_for_pixel(100, 50);
 
         assert_eq!(ray.origin, point!(0.0, 2.0, -5.0));
         assert_eq!(
             ray.direction,
-            vector!(2.0_f64.sqrt() / 2.0, 0.0, -2.0_f64.sqrt() / 2.0)
+            vector!(2.0_f64.sqrt() / 2.0, 0.0, -(2.0_f64.sqrt()) / 2.0)
+        );
+    }
+
+    #[test]
+    fn test_ray_for_sample_at_the_pixel_center() {
+        let camera = Camera::new(201, 101, PI / 2.0);
+
+        let sample = camera.ray_for_sample(0.5, 0.5);
+        let pixel = camera.ray_for_pixel(0, 0);
+        assert_eq!(sample.origin, pixel.origin);
+        assert_eq!(sample.direction, pixel.direction);
+        assert_eq!(
+            camera.ray_for_sample(100.5, 50.5).direction,
+            vector!(0.0, 0.0, -1.0)
+        );
+    }
+
+    // a lit wall that covers the right half of a one pixel image, seen
+    // through the pixel center only just
+    fn half_covered_pixel() -> (Camera, World) {
+        let mut world = World::new();
+        world.lights.push(Light {
+            position: point!(0.0, 0.0, 0.0),
+            intensity: Color::new(1.0, 1.0, 1.0),
+        });
+        let mut wall = Cube::init();
+        wall.material.ambient = 1.0;
+        wall.material.diffuse = 0.0;
+        wall.material.specular = 0.0;
+        wall.set_transform(
+            Matrix4::identity()
+                .scaling(50.0, 100.0, 1.0)
+                .translation(50.1, 0.0, -5.0),
+        );
+        world.add_object(Box::new(wall));
+
+        (Camera::new(1, 1, FRAC_PI_2), world)
+    }
+
+    #[test]
+    fn test_supersampling_smooths_edges() {
+        let (mut camera, world) = half_covered_pixel();
+
+        assert_eq!(
+            render(&camera, &world).pixel_at(0, 0),
+            &Color::new(0.0, 0.0, 0.0)
+        );
+
+        camera.samples = 16;
+        assert_eq!(
+            render(&camera, &world).pixel_at(0, 0),
+            &Color::new(0.5, 0.5, 0.5)
         );
     }
 
+    #[test]
+    fn test_jittered_samples_with_wide_filters() {
+        let (mut camera, world) = half_covered_pixel();
+        camera.samples = 64;
+        camera.sample_pattern = SamplePattern::Jittered;
+
+        for &filter in [
+            Filter::Box,
+            Filter::Tent,
+            Filter::Gaussian,
+            Filter::Mitchell,
+        ]
+        .iter()
+        {
+            camera.filter = filter;
+            let red = render(&camera, &world).pixel_at(0, 0).red;
+            // the filters are symmetric, so the edge stays close to half covered
+            assert!(red > 0.35 && red < 0.65, "{:?} gave {}", filter, red);
+        }
+    }
+
     #[test]
     fn test_rendering_a_world_trough_a_camera() {
         let world = World::default();
@@ -143,10 +445,154 @@ mod tests {
         let from = point!(0.0, 0.0, -5.0);
         let to = point!(0.0, 0.0, 0.0);
         let up = vector!(0.0, 1.0, 0.0);
-        camera.transform = crate::matrix::view_transform(from, to, up);
+        camera.set_transform(crate::matrix::view_transform(from, to, up));
 
         let image = render(&camera, &world);
 
         assert_eq!(image.pixel_at(5, 5), &Color::new(0.38066, 0.47583, 0.2855));
     }
+
+    #[test]
+    fn test_tiles_cover_the_canvas() {
+        let camera = Camera::new(40, 20, FRAC_PI_2);
+        let tiles = camera.tiles();
+
+        assert_eq!(tiles.len(), 6);
+        assert_eq!(
+            tiles[0],
+            Tile {
+                x: 0,
+                y: 0,
+                width: 16,
+                height: 16
+            }
+        );
+        assert_eq!(
+            tiles[5],
+            Tile {
+                x: 32,
+                y: 16,
+                width: 8,
+                height: 4
+            }
+        );
+        let area: usize = tiles.iter().map(|tile| tile.width * tile.height).sum();
+        assert_eq!(area, 40 * 20);
+    }
+
+    #[test]
+    fn test_rendering_with_threads_matches_single_thread() {
+        let world = World::default();
+        let mut camera = Camera::new(37, 23, FRAC_PI_2);
+        camera.set_transform(crate::matrix::view_transform(
+            point!(0.0, 0.0, -5.0),
+            point!(0.0, 0.0, 0.0),
+            vector!(0.0, 1.0, 0.0),
+        ));
+
+        camera.threads = 1;
+        let single = render(&camera, &world);
+        camera.threads = 4;
+        let threaded = render(&camera, &world);
+
+        for y in 0..camera.vsize {
+            for x in 0..camera.hsize {
+                let a = single.pixel_at(x, y);
+                let b = threaded.pixel_at(x, y);
+                // exactly the same, not just within EPSILON
+                assert_eq!(
+                    (a.red.to_bits(), a.green.to_bits(), a.blue.to_bits()),
+                    (b.red.to_bits(), b.green.to_bits(), b.blue.to_bits())
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_progress_is_reported_for_every_tile() {
+        let world = World::default();
+        let mut camera = Camera::new(40, 20, FRAC_PI_2);
+        camera.threads = 2;
+        let mut reports = vec![];
+
+        let rendered = render_with_progress(
+            &camera,
+            &world,
+            |p| reports.push(p),
+            &CancellationToken::new(),
+        );
+
+        assert!(!rendered.cancelled);
+        assert_eq!(reports.len(), 6);
+        for (index, report) in reports.iter().enumerate() {
+            assert_eq!(report.tiles_done, index + 1);
+            assert_eq!(report.tiles_total, 6);
+        }
+        assert_eq!(reports[5].fraction(), 1.0);
+        assert_eq!(reports[5].eta(), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn test_estimating_time_left() {
+        let progress = Progress {
+            tiles_done: 1,
+            tiles_total: 4,
+            elapsed: Duration::from_secs(2),
+        };
+
+        assert_eq!(progress.fraction(), 0.25);
+        assert_eq!(progress.eta(), Some(Duration::from_secs(6)));
+        assert_eq!(
+            Progress {
+                tiles_done: 0,
+                ..progress
+            }
+            .eta(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_cancelling_before_rendering() {
+        let world = World::default();
+        let camera = Camera::new(40, 20, FRAC_PI_2);
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+
+        let rendered = render_with_progress(&camera, &world, |_| {}, &cancel);
+
+        assert!(rendered.cancelled);
+        assert_eq!(rendered.canvas.width, 40);
+        assert_eq!(rendered.canvas.height, 20);
+    }
+
+    #[test]
+    fn test_cancelling_returns_partial_canvas() {
+        let world = World::default();
+        let mut camera = Camera::new(64, 64, FRAC_PI_2);
+        camera.set_transform(crate::matrix::view_transform(
+            point!(0.0, 0.0, -5.0),
+            point!(0.0, 0.0, 0.0),
+            vector!(0.0, 1.0, 0.0),
+        ));
+        camera.threads = 1;
+        let cancel = CancellationToken::new();
+        let mut tiles_done = 0;
+
+        let rendered = render_with_progress(
+            &camera,
+            &world,
+            |p| {
+                tiles_done = p.tiles_done;
+                cancel.cancel();
+            },
+            &cancel,
+        );
+
+        assert!(rendered.cancelled);
+        assert!(tiles_done < camera.tiles().len());
+        // the sphere in the middle is in a tile that was never started
+        assert!(render(&camera, &world).pixel_at(32, 32).red > 0.0);
+        assert_eq!(rendered.canvas.pixel_at(32, 32), &Color::new(0.0, 0.0, 0.0));
+    }
 }
@@ -0,0 +1,255 @@
+use crate::bounds::BoundingBox;
+use crate::cylinder::check_cap;
+use crate::material::*;
+use crate::matrix::*;
+use crate::point;
+use crate::ray::*;
+use crate::shape::Shape;
+use crate::tuple::*;
+use crate::tuple::{EPSILON, PARALLEL_EPSILON};
+use crate::vector;
+
+// A double napped cone around the y axis with its tip at the origin, so the
+// radius at any height y is |y|. Truncation and caps work like Cylinder.
+#[derive(Debug, PartialEq)]
+pub struct Cone {
+    transform: Transform,
+    pub material: Material,
+    parent_transform: Transform,
+    pub minimum: f64,
+    pub maximum: f64,
+    pub closed: bool,
+}
+
+impl Cone {
+    pub fn init() -> Cone {
+        Cone {
+            transform: Transform::default(),
+            material: Material::default(),
+            parent_transform: Transform::default(),
+            minimum: f64::NEG_INFINITY,
+            maximum: f64::INFINITY,
+            closed: false,
+        }
+    }
+
+    fn intersect_caps<'a>(&'a self, ray: &Ray, intersections: &mut Vec<Intersection<'a>>) {
+        if !self.closed || ray.direction.y.abs() < PARALLEL_EPSILON {
+            return;
+        }
+
+        for y in [self.minimum, self.maximum] {
+            let t = (y - ray.origin.y) / ray.direction.y;
+            if check_cap(ray, t, y.abs()) {
+                intersections.push(Intersection::new(t, self));
+            }
+        }
+    }
+
+    fn push_if_within_bounds<'a>(
+        &'a self,
+        ray: &Ray,
+        t: f64,
+        intersections: &mut Vec<Intersection<'a>>,
+    ) {
+        let y = ray.origin.y + t * ray.direction.y;
+        if self.minimum < y && y < self.maximum {
+            intersections.push(Intersection::new(t, self));
+        }
+    }
+}
+
+impl Shape for Cone {
+    fn transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    fn set_transform(&mut self, transform: Matrix4) {
+        self.transform = Transform::new(transform);
+    }
+
+    fn parent_transform(&self) -> &Transform {
+        &self.parent_transform
+    }
+
+    fn set_parent_transform(&mut self, transform: Matrix4) {
+        self.parent_transform = Transform::new(transform);
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn material_mut(&mut self) -> &mut Material {
+        &mut self.material
+    }
+
+    fn local_intersect(&self, ray: &Ray) -> Vec<Intersection> {
+        let mut intersections = vec![];
+        let (origin, direction) = (ray.origin, ray.direction);
+
+        let a = direction.x * direction.x - direction.y * direction.y + direction.z * direction.z;
+        let b = 2.0 * origin.x * direction.x - 2.0 * origin.y * direction.y
+            + 2.0 * origin.z * direction.z;
+        let c = origin.x * origin.x - origin.y * origin.y + origin.z * origin.z;
+
+        if a.abs() < PARALLEL_EPSILON {
+            // the ray is parallel to one of the halves, so it crosses
+            // the other half once (or misses both when b is also zero)
+            if b.abs() >= PARALLEL_EPSILON {
+                self.push_if_within_bounds(ray, -c / (2.0 * b), &mut intersections);
+            }
+        } else {
+            let discriminant = b * b - 4.0 * a * c;
+            if discriminant < 0.0 {
+                return vec![];
+            }
+
+            let t0 = (-b - discriminant.sqrt()) / (2.0 * a);
+            let t1 = (-b + discriminant.sqrt()) / (2.0 * a);
+
+            self.push_if_within_bounds(ray, t0.min(t1), &mut intersections);
+            self.push_if_within_bounds(ray, t0.max(t1), &mut intersections);
+        }
+
+        self.intersect_caps(ray, &mut intersections);
+        intersections
+    }
+
+    fn bounds(&self) -> BoundingBox {
+        // the radius of a cone is as large as its furthest end
+        let limit = self.minimum.abs().max(self.maximum.abs());
+        BoundingBox::new(
+            point!(-limit, self.minimum, -limit),
+            point!(limit, self.maximum, limit),
+        )
+    }
+
+    fn local_normal_at(&self, point: Tuple) -> Tuple {
+        let distance = point.x * point.x + point.z * point.z;
+
+        if distance < self.maximum * self.maximum && point.y >= self.maximum - EPSILON {
+            vector!(0.0, 1.0, 0.0)
+        } else if distance < self.minimum * self.minimum && point.y <= self.minimum + EPSILON {
+            vector!(0.0, -1.0, 0.0)
+        } else {
+            let mut y = distance.sqrt();
+            if point.y > 0.0 {
+                y = -y;
+            }
+            vector!(point.x, y, point.z)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::point;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_ray_strikes_cone() {
+        let cone = Cone::init();
+        let examples = [
+            (point!(0.0, 0.0, -5.0), vector!(0.0, 0.0, 1.0), 5.0, 5.0),
+            (
+                point!(0.0, 0.0, -5.0),
+                vector!(1.0, 1.0, 1.0),
+                8.66025,
+                8.66025,
+            ),
+            (
+                point!(1.0, 1.0, -5.0),
+                vector!(-0.5, -1.0, 1.0),
+                4.55006,
+                49.44994,
+            ),
+        ];
+
+        for (origin, direction, t0, t1) in examples {
+            let ray = Ray::new(origin, direction.norm());
+            let intersections = cone.local_intersect(&ray);
+
+            assert_eq!(intersections.len(), 2);
+            assert_relative_eq!(intersections[0].t, t0, epsilon = EPSILON);
+            assert_relative_eq!(intersections[1].t, t1, epsilon = EPSILON);
+        }
+    }
+
+    #[test]
+    fn test_ray_parallel_to_one_half() {
+        let cone = Cone::init();
+        let ray = Ray::new(point!(0.0, 0.0, -1.0), vector!(0.0, 1.0, 1.0).norm());
+
+        let intersections = cone.local_intersect(&ray);
+
+        assert_eq!(intersections.len(), 1);
+        assert_relative_eq!(intersections[0].t, 0.35355, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn test_ray_strikes_scaled_cone() {
+        for &scale in [10.0, 100.0, 10000.0].iter() {
+            let mut cone = Cone::init();
+            cone.set_transform(Matrix4::identity().scaling(scale, 1.0, scale));
+            let ray = Ray::new(point!(0.0, 0.5, -5.0 * scale), vector!(0.0, 0.0, 1.0));
+
+            let intersections = ray.intersect(&cone);
+
+            // the radius at y = 0.5 is 0.5, scaled up like the rest
+            assert_eq!(intersections.len(), 2);
+            assert_relative_eq!(intersections[0].t, 4.5 * scale, epsilon = EPSILON);
+            assert_relative_eq!(intersections[1].t, 5.5 * scale, epsilon = EPSILON);
+        }
+    }
+
+    #[test]
+    fn test_intersecting_caps_of_closed_cone() {
+        let mut cone = Cone::init();
+        cone.minimum = -0.5;
+        cone.maximum = 0.5;
+        cone.closed = true;
+        let examples = [
+            (point!(0.0, 0.0, -5.0), vector!(0.0, 1.0, 0.0), 0),
+            (point!(0.0, 0.0, -0.25), vector!(0.0, 1.0, 1.0), 2),
+            (point!(0.0, 0.0, -0.25), vector!(0.0, 1.0, 0.0), 4),
+        ];
+
+        for (origin, direction, count) in examples {
+            let ray = Ray::new(origin, direction.norm());
+            assert_eq!(cone.local_intersect(&ray).len(), count);
+        }
+    }
+
+    #[test]
+    fn test_cone_normal() {
+        let cone = Cone::init();
+        let examples = [
+            (point!(0.0, 0.0, 0.0), vector!(0.0, 0.0, 0.0)),
+            (point!(1.0, 1.0, 1.0), vector!(1.0, -(2.0_f64.sqrt()), 1.0)),
+            (point!(-1.0, -1.0, 0.0), vector!(-1.0, 1.0, 0.0)),
+        ];
+
+        for (point, normal) in examples {
+            assert_eq!(cone.local_normal_at(point), normal);
+        }
+    }
+
+    #[test]
+    fn test_normal_on_cone_caps() {
+        let mut cone = Cone::init();
+        cone.minimum = -1.0;
+        cone.maximum = 2.0;
+        cone.closed = true;
+
+        assert_eq!(
+            cone.local_normal_at(point!(0.5, 2.0, 0.0)),
+            vector!(0.0, 1.0, 0.0)
+        );
+        assert_eq!(
+            cone.local_normal_at(point!(0.5, -1.0, 0.0)),
+            vector!(0.0, -1.0, 0.0)
+        );
+    }
+}
@@ -0,0 +1,310 @@
+use crate::bounds::BoundingBox;
+use crate::material::*;
+use crate::matrix::*;
+use crate::ray::*;
+use crate::shape::Shape;
+use crate::tuple::*;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Operation {
+    Union,
+    Intersection,
+    Difference,
+}
+
+// Whether a hit on one of the children is part of the combined surface.
+// left_hit tells which child was hit, inside_left and inside_right whether
+// the ray is currently inside each child.
+pub fn intersection_allowed(
+    operation: Operation,
+    left_hit: bool,
+    inside_left: bool,
+    inside_right: bool,
+) -> bool {
+    match operation {
+        Operation::Union => (left_hit && !inside_right) || (!left_hit && !inside_left),
+        Operation::Intersection => (left_hit && inside_right) || (!left_hit && inside_left),
+        Operation::Difference => (left_hit && !inside_right) || (!left_hit && inside_left),
+    }
+}
+
+// Constructive solid geometry: two shapes combined with a set operation.
+// Like Group, children are transformed by the transform of the Csg.
+#[derive(Debug)]
+pub struct Csg {
+    pub operation: Operation,
+    // private, as every change has to be pushed down to the children
+    transform: Transform,
+    pub material: Material,
+    parent_transform: Transform,
+    left: Box<dyn Shape>,
+    right: Box<dyn Shape>,
+    // around both children, in the space of the csg
+    bounds: BoundingBox,
+}
+
+impl Csg {
+    pub fn new(operation: Operation, left: Box<dyn Shape>, right: Box<dyn Shape>) -> Csg {
+        let mut bounds = left.parent_space_bounds();
+        bounds.merge(&right.parent_space_bounds());
+
+        let mut csg = Csg {
+            operation,
+            transform: Transform::default(),
+            material: Material::default(),
+            parent_transform: Transform::default(),
+            left,
+            right,
+            bounds,
+        };
+        csg.update_children();
+        csg
+    }
+
+    pub fn left(&self) -> &dyn Shape {
+        self.left.as_ref()
+    }
+
+    pub fn right(&self) -> &dyn Shape {
+        self.right.as_ref()
+    }
+
+    fn update_children(&mut self) {
+        let world_transform = self.parent_transform.matrix() * self.transform.matrix();
+        self.left.set_parent_transform(world_transform);
+        self.right.set_parent_transform(world_transform);
+    }
+
+    // Walks the sorted intersections of both children, keeping track of
+    // whether the ray is inside each of them, and keeps the ones that are on
+    // the surface of the combined shape
+    fn filter_intersections<'a>(
+        &self,
+        intersections: Vec<(Intersection<'a>, bool)>,
+    ) -> Vec<Intersection<'a>> {
+        let mut inside_left = false;
+        let mut inside_right = false;
+        let mut result = vec![];
+
+        for (intersection, left_hit) in intersections {
+            if intersection_allowed(self.operation, left_hit, inside_left, inside_right) {
+                result.push(intersection);
+            }
+
+            if left_hit {
+                inside_left = !inside_left;
+            } else {
+                inside_right = !inside_right;
+            }
+        }
+
+        result
+    }
+}
+
+impl Shape for Csg {
+    fn transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    fn set_transform(&mut self, transform: Matrix4) {
+        self.transform = Transform::new(transform);
+        self.update_children();
+    }
+
+    fn parent_transform(&self) -> &Transform {
+        &self.parent_transform
+    }
+
+    fn set_parent_transform(&mut self, transform: Matrix4) {
+        self.parent_transform = Transform::new(transform);
+        self.update_children();
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn material_mut(&mut self) -> &mut Material {
+        &mut self.material
+    }
+
+    fn local_intersect(&self, ray: &Ray) -> Vec<Intersection> {
+        if !self.bounds.intersects(ray) {
+            return vec![];
+        }
+
+        // remember which child every intersection came from
+        let left = ray.intersect(self.left()).into_iter().map(|i| (i, true));
+        let right = ray.intersect(self.right()).into_iter().map(|i| (i, false));
+
+        let mut intersections: Vec<(Intersection, bool)> = left.chain(right).collect();
+        intersections.sort_by(|a, b| a.0.t.partial_cmp(&b.0.t).unwrap());
+
+        self.filter_intersections(intersections)
+    }
+
+    fn bounds(&self) -> BoundingBox {
+        self.bounds
+    }
+
+    fn local_normal_at(&self, _object_point: Tuple) -> Tuple {
+        unreachable!("csg has no surface, intersections always point to a child")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cube::Cube;
+    use crate::cylinder::Cylinder;
+    use crate::point;
+    use crate::sphere::Sphere;
+    use crate::vector;
+
+    #[test]
+    fn test_csg_is_created_with_operation_and_two_shapes() {
+        let csg = Csg::new(
+            Operation::Union,
+            Box::new(Sphere::init()),
+            Box::new(Cube::init()),
+        );
+
+        assert_eq!(csg.operation, Operation::Union);
+        assert_eq!(csg.left().transform(), &Matrix4::identity());
+        assert_eq!(csg.right().transform(), &Matrix4::identity());
+    }
+
+    #[test]
+    fn test_evaluating_rules_for_csg_operations() {
+        use Operation::*;
+        let examples = [
+            (Union, true, true, true, false),
+            (Union, true, true, false, true),
+            (Union, true, false, true, false),
+            (Union, true, false, false, true),
+            (Union, false, true, true, false),
+            (Union, false, true, false, false),
+            (Union, false, false, true, true),
+            (Union, false, false, false, true),
+            (Intersection, true, true, true, true),
+            (Intersection, true, true, false, false),
+            (Intersection, true, false, true, true),
+            (Intersection, true, false, false, false),
+            (Intersection, false, true, true, true),
+            (Intersection, false, true, false, true),
+            (Intersection, false, false, true, false),
+            (Intersection, false, false, false, false),
+            (Difference, true, true, true, false),
+            (Difference, true, true, false, true),
+            (Difference, true, false, true, false),
+            (Difference, true, false, false, true),
+            (Difference, false, true, true, true),
+            (Difference, false, true, false, true),
+            (Difference, false, false, true, false),
+            (Difference, false, false, false, false),
+        ];
+
+        for (operation, left_hit, inside_left, inside_right, result) in examples {
+            assert_eq!(
+                intersection_allowed(operation, left_hit, inside_left, inside_right),
+                result
+            );
+        }
+    }
+
+    #[test]
+    fn test_filtering_list_of_intersections() {
+        // hits alternate between the children: left at 1 and 3,
+        // right at 2 and 4. The expected pair are indices into that list.
+        let examples = [
+            (Operation::Union, [0, 3]),
+            (Operation::Intersection, [1, 2]),
+            (Operation::Difference, [0, 1]),
+        ];
+
+        for (operation, [x0, x1]) in examples {
+            let csg = Csg::new(operation, Box::new(Sphere::init()), Box::new(Cube::init()));
+            let left = csg.left();
+            let right = csg.right();
+            let intersections = vec![
+                (Intersection::new(1.0, left), true),
+                (Intersection::new(2.0, right), false),
+                (Intersection::new(3.0, left), true),
+                (Intersection::new(4.0, right), false),
+            ];
+            let expected = [intersections[x0].0.clone(), intersections[x1].0.clone()];
+
+            let result = csg.filter_intersections(intersections);
+
+            assert_eq!(result, expected);
+        }
+    }
+
+    #[test]
+    fn test_csg_bounds_contain_children() {
+        let left = Sphere::init();
+        let mut right = Sphere::init();
+        right.set_transform(Matrix4::identity().translation(2.0, 3.0, 4.0));
+        let csg = Csg::new(Operation::Difference, Box::new(left), Box::new(right));
+
+        let bounds = csg.bounds();
+
+        assert_eq!(bounds.min, point!(-1.0, -1.0, -1.0));
+        assert_eq!(bounds.max, point!(3.0, 4.0, 5.0));
+    }
+
+    #[test]
+    fn test_ray_misses_csg() {
+        let csg = Csg::new(
+            Operation::Union,
+            Box::new(Sphere::init()),
+            Box::new(Cube::init()),
+        );
+        let ray = Ray::new(point!(0.0, 2.0, -5.0), vector!(0.0, 0.0, 1.0));
+
+        assert_eq!(csg.local_intersect(&ray).len(), 0);
+    }
+
+    #[test]
+    fn test_ray_hits_csg() {
+        let mut right = Sphere::init();
+        right.set_transform(Matrix4::identity().translation(0.0, 0.0, 0.5));
+        let csg = Csg::new(Operation::Union, Box::new(Sphere::init()), Box::new(right));
+        let ray = Ray::new(point!(0.0, 0.0, -5.0), vector!(0.0, 0.0, 1.0));
+
+        let intersections = csg.local_intersect(&ray);
+
+        assert_eq!(intersections.len(), 2);
+        assert_eq!(intersections[0].t, 4.0);
+        assert_eq!(intersections[0].object, csg.left());
+        assert_eq!(intersections[1].t, 6.5);
+        assert_eq!(intersections[1].object, csg.right());
+    }
+
+    #[test]
+    fn test_difference_cuts_a_hole() {
+        let mut hole = Cylinder::init();
+        hole.minimum = -2.0;
+        hole.maximum = 2.0;
+        hole.closed = true;
+        hole.set_transform(
+            Matrix4::identity()
+                .scaling(0.5, 1.0, 0.5)
+                .rotation_x(std::f64::consts::FRAC_PI_2),
+        );
+        let csg = Csg::new(
+            Operation::Difference,
+            Box::new(Cube::init()),
+            Box::new(hole),
+        );
+
+        // straight through the hole
+        let ray = Ray::new(point!(0.0, 0.0, -5.0), vector!(0.0, 0.0, 1.0));
+        assert_eq!(csg.local_intersect(&ray).len(), 0);
+
+        // next to the hole
+        let ray = Ray::new(point!(0.75, 0.0, -5.0), vector!(0.0, 0.0, 1.0));
+        assert_eq!(csg.local_intersect(&ray).len(), 2);
+    }
+}
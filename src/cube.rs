@@ -0,0 +1,215 @@
+use crate::bounds::BoundingBox;
+use crate::material::*;
+use crate::matrix::*;
+use crate::point;
+use crate::ray::*;
+use crate::shape::Shape;
+use crate::tuple::*;
+use crate::vector;
+
+// An axis aligned cube, spanning from -1 to 1 on every axis in object space
+#[derive(Debug, PartialEq)]
+pub struct Cube {
+    transform: Transform,
+    pub material: Material,
+    parent_transform: Transform,
+}
+
+impl Cube {
+    pub fn init() -> Cube {
+        Cube {
+            transform: Transform::default(),
+            material: Material::default(),
+            parent_transform: Transform::default(),
+        }
+    }
+}
+
+// Where the ray crosses the two planes of a slab at -1 and 1 on one axis.
+// A ray parallel to the slab divides by zero and gets infinities, which is
+// what the comparisons in local_intersect need; no epsilon here, as object
+// space directions get very short on cubes scaled up along that axis.
+fn check_axis(origin: f64, direction: f64) -> (f64, f64) {
+    let tmin = (-1.0 - origin) / direction;
+    let tmax = (1.0 - origin) / direction;
+
+    if tmin > tmax {
+        (tmax, tmin)
+    } else {
+        (tmin, tmax)
+    }
+}
+
+impl Shape for Cube {
+    fn transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    fn set_transform(&mut self, transform: Matrix4) {
+        self.transform = Transform::new(transform);
+    }
+
+    fn parent_transform(&self) -> &Transform {
+        &self.parent_transform
+    }
+
+    fn set_parent_transform(&mut self, transform: Matrix4) {
+        self.parent_transform = Transform::new(transform);
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn material_mut(&mut self) -> &mut Material {
+        &mut self.material
+    }
+
+    fn local_intersect(&self, ray: &Ray) -> Vec<Intersection> {
+        let (xtmin, xtmax) = check_axis(ray.origin.x, ray.direction.x);
+        let (ytmin, ytmax) = check_axis(ray.origin.y, ray.direction.y);
+        let (ztmin, ztmax) = check_axis(ray.origin.z, ray.direction.z);
+
+        // the ray is inside the cube between the last slab entered
+        // and the first slab exited
+        let tmin = xtmin.max(ytmin).max(ztmin);
+        let tmax = xtmax.min(ytmax).min(ztmax);
+
+        if tmin > tmax {
+            return vec![];
+        }
+
+        vec![Intersection::new(tmin, self), Intersection::new(tmax, self)]
+    }
+
+    fn bounds(&self) -> BoundingBox {
+        BoundingBox::new(point!(-1.0, -1.0, -1.0), point!(1.0, 1.0, 1.0))
+    }
+
+    fn local_normal_at(&self, point: Tuple) -> Tuple {
+        let maxc = point.x.abs().max(point.y.abs()).max(point.z.abs());
+
+        if maxc == point.x.abs() {
+            vector!(point.x, 0.0, 0.0)
+        } else if maxc == point.y.abs() {
+            vector!(0.0, point.y, 0.0)
+        } else {
+            vector!(0.0, 0.0, point.z)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::point;
+
+    #[test]
+    fn test_ray_intersects_cube() {
+        let cube = Cube::init();
+        let examples = [
+            // +x, -x, +y, -y, +z, -z, inside
+            (point!(5.0, 0.5, 0.0), vector!(-1.0, 0.0, 0.0), 4.0, 6.0),
+            (point!(-5.0, 0.5, 0.0), vector!(1.0, 0.0, 0.0), 4.0, 6.0),
+            (point!(0.5, 5.0, 0.0), vector!(0.0, -1.0, 0.0), 4.0, 6.0),
+            (point!(0.5, -5.0, 0.0), vector!(0.0, 1.0, 0.0), 4.0, 6.0),
+            (point!(0.5, 0.0, 5.0), vector!(0.0, 0.0, -1.0), 4.0, 6.0),
+            (point!(0.5, 0.0, -5.0), vector!(0.0, 0.0, 1.0), 4.0, 6.0),
+            (point!(0.0, 0.5, 0.0), vector!(0.0, 0.0, 1.0), -1.0, 1.0),
+        ];
+
+        for (origin, direction, t1, t2) in examples {
+            let ray = Ray::new(origin, direction);
+            let intersections = cube.local_intersect(&ray);
+
+            assert_eq!(intersections.len(), 2);
+            assert_eq!(intersections[0].t, t1);
+            assert_eq!(intersections[1].t, t2);
+        }
+    }
+
+    #[test]
+    fn test_ray_misses_cube() {
+        let cube = Cube::init();
+        let examples = [
+            (point!(-2.0, 0.0, 0.0), vector!(0.2673, 0.5345, 0.8018)),
+            (point!(0.0, -2.0, 0.0), vector!(0.8018, 0.2673, 0.5345)),
+            (point!(0.0, 0.0, -2.0), vector!(0.5345, 0.8018, 0.2673)),
+            (point!(2.0, 0.0, 2.0), vector!(0.0, 0.0, -1.0)),
+            (point!(0.0, 2.0, 2.0), vector!(0.0, -1.0, 0.0)),
+            (point!(2.0, 2.0, 0.0), vector!(-1.0, 0.0, 0.0)),
+        ];
+
+        for (origin, direction) in examples {
+            let ray = Ray::new(origin, direction);
+            assert_eq!(cube.local_intersect(&ray).len(), 0);
+        }
+    }
+
+    #[test]
+    fn test_ray_parallel_to_slabs() {
+        let cube = Cube::init();
+
+        let inside =
+            cube.local_intersect(&Ray::new(point!(0.5, 0.5, -5.0), vector!(0.0, 0.0, 1.0)));
+        let outside =
+            cube.local_intersect(&Ray::new(point!(0.5, 1.5, -5.0), vector!(0.0, 0.0, 1.0)));
+
+        assert_eq!(inside.len(), 2);
+        assert_eq!((inside[0].t, inside[1].t), (4.0, 6.0));
+        assert_eq!(outside.len(), 0);
+    }
+
+    #[test]
+    fn test_ray_strikes_tall_cube() {
+        let mut cube = Cube::init();
+        cube.set_transform(Matrix4::identity().scaling(1.0, 2000.0, 1.0));
+        let ray = Ray::new(point!(0.0, -3000.0, 0.0), vector!(0.0, 1.0, 0.0));
+
+        let intersections = ray.intersect(&cube);
+
+        assert_eq!(intersections.len(), 2);
+        assert_eq!(intersections[0].t, 1000.0);
+        assert_eq!(intersections[1].t, 5000.0);
+    }
+
+    #[test]
+    fn test_cube_normal() {
+        let cube = Cube::init();
+        let examples = [
+            (point!(1.0, 0.5, -0.8), vector!(1.0, 0.0, 0.0)),
+            (point!(-1.0, -0.2, 0.9), vector!(-1.0, 0.0, 0.0)),
+            (point!(-0.4, 1.0, -0.1), vector!(0.0, 1.0, 0.0)),
+            (point!(0.3, -1.0, -0.7), vector!(0.0, -1.0, 0.0)),
+            (point!(-0.6, 0.3, 1.0), vector!(0.0, 0.0, 1.0)),
+            (point!(0.4, 0.4, -1.0), vector!(0.0, 0.0, -1.0)),
+            (point!(1.0, 1.0, 1.0), vector!(1.0, 0.0, 0.0)),
+            (point!(-1.0, -1.0, -1.0), vector!(-1.0, 0.0, 0.0)),
+        ];
+
+        for (point, normal) in examples {
+            assert_eq!(cube.local_normal_at(point), normal);
+        }
+    }
+
+    #[test]
+    fn test_transformed_cube() {
+        let mut cube = Cube::init();
+        cube.set_transform(
+            Matrix4::identity()
+                .scaling(1.0, 1.0, 2.0)
+                .translation(0.0, 0.0, 5.0),
+        );
+
+        let ray = Ray::new(point!(0.0, 0.0, 0.0), vector!(0.0, 0.0, 1.0));
+        let intersections = ray.intersect(&cube);
+
+        assert_eq!(intersections.len(), 2);
+        assert_eq!(intersections[0].t, 3.0);
+        assert_eq!(intersections[1].t, 7.0);
+        assert_eq!(
+            cube.normal_at(ray.position(intersections[0].t)),
+            vector!(0.0, 0.0, -1.0)
+        );
+    }
+}
@@ -0,0 +1,294 @@
+use crate::bounds::BoundingBox;
+use crate::material::*;
+use crate::matrix::*;
+use crate::point;
+use crate::ray::*;
+use crate::shape::Shape;
+use crate::tuple::*;
+use crate::tuple::{EPSILON, PARALLEL_EPSILON};
+use crate::vector;
+
+// A cylinder of radius 1 around the y axis, optionally truncated between
+// minimum and maximum (both exclusive) and closed with caps at both ends
+#[derive(Debug, PartialEq)]
+pub struct Cylinder {
+    transform: Transform,
+    pub material: Material,
+    parent_transform: Transform,
+    pub minimum: f64,
+    pub maximum: f64,
+    pub closed: bool,
+}
+
+impl Cylinder {
+    pub fn init() -> Cylinder {
+        Cylinder {
+            transform: Transform::default(),
+            material: Material::default(),
+            parent_transform: Transform::default(),
+            minimum: f64::NEG_INFINITY,
+            maximum: f64::INFINITY,
+            closed: false,
+        }
+    }
+
+    fn intersect_caps<'a>(&'a self, ray: &Ray, intersections: &mut Vec<Intersection<'a>>) {
+        // caps only matter when the cylinder is closed and the ray
+        // is not parallel to them
+        if !self.closed || ray.direction.y.abs() < PARALLEL_EPSILON {
+            return;
+        }
+
+        for y in [self.minimum, self.maximum] {
+            let t = (y - ray.origin.y) / ray.direction.y;
+            if check_cap(ray, t, 1.0) {
+                intersections.push(Intersection::new(t, self));
+            }
+        }
+    }
+}
+
+// whether the intersection at t is within the radius of a cap
+pub(crate) fn check_cap(ray: &Ray, t: f64, radius: f64) -> bool {
+    let x = ray.origin.x + t * ray.direction.x;
+    let z = ray.origin.z + t * ray.direction.z;
+
+    (x * x + z * z) <= radius * radius
+}
+
+impl Shape for Cylinder {
+    fn transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    fn set_transform(&mut self, transform: Matrix4) {
+        self.transform = Transform::new(transform);
+    }
+
+    fn parent_transform(&self) -> &Transform {
+        &self.parent_transform
+    }
+
+    fn set_parent_transform(&mut self, transform: Matrix4) {
+        self.parent_transform = Transform::new(transform);
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn material_mut(&mut self) -> &mut Material {
+        &mut self.material
+    }
+
+    fn local_intersect(&self, ray: &Ray) -> Vec<Intersection> {
+        let mut intersections = vec![];
+        let a = ray.direction.x * ray.direction.x + ray.direction.z * ray.direction.z;
+
+        // a ray parallel to the y axis can only hit the caps
+        if a.abs() >= PARALLEL_EPSILON {
+            let b = 2.0 * ray.origin.x * ray.direction.x + 2.0 * ray.origin.z * ray.direction.z;
+            let c = ray.origin.x * ray.origin.x + ray.origin.z * ray.origin.z - 1.0;
+            let discriminant = b * b - 4.0 * a * c;
+
+            if discriminant < 0.0 {
+                return vec![];
+            }
+
+            let t0 = (-b - discriminant.sqrt()) / (2.0 * a);
+            let t1 = (-b + discriminant.sqrt()) / (2.0 * a);
+
+            for t in [t0.min(t1), t0.max(t1)] {
+                let y = ray.origin.y + t * ray.direction.y;
+                if self.minimum < y && y < self.maximum {
+                    intersections.push(Intersection::new(t, self));
+                }
+            }
+        }
+
+        self.intersect_caps(ray, &mut intersections);
+        intersections
+    }
+
+    fn bounds(&self) -> BoundingBox {
+        BoundingBox::new(
+            point!(-1.0, self.minimum, -1.0),
+            point!(1.0, self.maximum, 1.0),
+        )
+    }
+
+    fn local_normal_at(&self, point: Tuple) -> Tuple {
+        let distance = point.x * point.x + point.z * point.z;
+
+        if distance < 1.0 && point.y >= self.maximum - EPSILON {
+            vector!(0.0, 1.0, 0.0)
+        } else if distance < 1.0 && point.y <= self.minimum + EPSILON {
+            vector!(0.0, -1.0, 0.0)
+        } else {
+            vector!(point.x, 0.0, point.z)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::point;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_ray_misses_cylinder() {
+        let cylinder = Cylinder::init();
+        let examples = [
+            (point!(1.0, 0.0, 0.0), vector!(0.0, 1.0, 0.0)),
+            (point!(0.0, 0.0, 0.0), vector!(0.0, 1.0, 0.0)),
+            (point!(0.0, 0.0, -5.0), vector!(1.0, 1.0, 1.0)),
+        ];
+
+        for (origin, direction) in examples {
+            let ray = Ray::new(origin, direction.norm());
+            assert_eq!(cylinder.local_intersect(&ray).len(), 0);
+        }
+    }
+
+    #[test]
+    fn test_ray_strikes_cylinder() {
+        let cylinder = Cylinder::init();
+        let examples = [
+            (point!(1.0, 0.0, -5.0), vector!(0.0, 0.0, 1.0), 5.0, 5.0),
+            (point!(0.0, 0.0, -5.0), vector!(0.0, 0.0, 1.0), 4.0, 6.0),
+            (
+                point!(0.5, 0.0, -5.0),
+                vector!(0.1, 1.0, 1.0),
+                6.80798,
+                7.08872,
+            ),
+        ];
+
+        for (origin, direction, t0, t1) in examples {
+            let ray = Ray::new(origin, direction.norm());
+            let intersections = cylinder.local_intersect(&ray);
+
+            assert_eq!(intersections.len(), 2);
+            assert_relative_eq!(intersections[0].t, t0, epsilon = EPSILON);
+            assert_relative_eq!(intersections[1].t, t1, epsilon = EPSILON);
+        }
+    }
+
+    #[test]
+    fn test_ray_strikes_scaled_cylinder() {
+        for &radius in [10.0, 100.0, 10000.0].iter() {
+            let mut cylinder = Cylinder::init();
+            cylinder.set_transform(Matrix4::identity().scaling(radius, 1.0, radius));
+            let ray = Ray::new(point!(0.0, 0.0, -5.0 * radius), vector!(0.0, 0.0, 1.0));
+
+            let intersections = ray.intersect(&cylinder);
+
+            assert_eq!(intersections.len(), 2);
+            assert_relative_eq!(intersections[0].t, 4.0 * radius, epsilon = EPSILON);
+            assert_relative_eq!(intersections[1].t, 6.0 * radius, epsilon = EPSILON);
+        }
+    }
+
+    #[test]
+    fn test_caps_of_tall_closed_cylinder() {
+        let mut cylinder = Cylinder::init();
+        cylinder.minimum = -1.0;
+        cylinder.maximum = 1.0;
+        cylinder.closed = true;
+        cylinder.set_transform(Matrix4::identity().scaling(1.0, 2000.0, 1.0));
+        let ray = Ray::new(point!(0.0, -3000.0, 0.0), vector!(0.0, 1.0, 0.0));
+
+        let intersections = ray.intersect(&cylinder);
+
+        assert_eq!(intersections.len(), 2);
+        assert_relative_eq!(intersections[0].t, 1000.0, epsilon = EPSILON);
+        assert_relative_eq!(intersections[1].t, 5000.0, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn test_cylinder_normal() {
+        let cylinder = Cylinder::init();
+        let examples = [
+            (point!(1.0, 0.0, 0.0), vector!(1.0, 0.0, 0.0)),
+            (point!(0.0, 5.0, -1.0), vector!(0.0, 0.0, -1.0)),
+            (point!(0.0, -2.0, 1.0), vector!(0.0, 0.0, 1.0)),
+            (point!(-1.0, 1.0, 0.0), vector!(-1.0, 0.0, 0.0)),
+        ];
+
+        for (point, normal) in examples {
+            assert_eq!(cylinder.local_normal_at(point), normal);
+        }
+    }
+
+    #[test]
+    fn test_default_cylinder_is_infinite_and_open() {
+        let cylinder = Cylinder::init();
+
+        assert_eq!(cylinder.minimum, f64::NEG_INFINITY);
+        assert_eq!(cylinder.maximum, f64::INFINITY);
+        assert!(!cylinder.closed);
+    }
+
+    #[test]
+    fn test_intersecting_constrained_cylinder() {
+        let mut cylinder = Cylinder::init();
+        cylinder.minimum = 1.0;
+        cylinder.maximum = 2.0;
+        let examples = [
+            (point!(0.0, 1.5, 0.0), vector!(0.1, 1.0, 0.0), 0),
+            (point!(0.0, 3.0, -5.0), vector!(0.0, 0.0, 1.0), 0),
+            (point!(0.0, 0.0, -5.0), vector!(0.0, 0.0, 1.0), 0),
+            (point!(0.0, 2.0, -5.0), vector!(0.0, 0.0, 1.0), 0),
+            (point!(0.0, 1.0, -5.0), vector!(0.0, 0.0, 1.0), 0),
+            (point!(0.0, 1.5, -2.0), vector!(0.0, 0.0, 1.0), 2),
+        ];
+
+        for (origin, direction, count) in examples {
+            let ray = Ray::new(origin, direction.norm());
+            assert_eq!(cylinder.local_intersect(&ray).len(), count);
+        }
+    }
+
+    #[test]
+    fn test_intersecting_caps_of_closed_cylinder() {
+        let mut cylinder = Cylinder::init();
+        cylinder.minimum = 1.0;
+        cylinder.maximum = 2.0;
+        cylinder.closed = true;
+        let examples = [
+            (point!(0.0, 3.0, 0.0), vector!(0.0, -1.0, 0.0), 2),
+            (point!(0.0, 3.0, -2.0), vector!(0.0, -1.0, 2.0), 2),
+            // corner case
+            (point!(0.0, 4.0, -2.0), vector!(0.0, -1.0, 1.0), 2),
+            (point!(0.0, 0.0, -2.0), vector!(0.0, 1.0, 2.0), 2),
+            // corner case
+            (point!(0.0, -1.0, -2.0), vector!(0.0, 1.0, 1.0), 2),
+        ];
+
+        for (origin, direction, count) in examples {
+            let ray = Ray::new(origin, direction.norm());
+            assert_eq!(cylinder.local_intersect(&ray).len(), count);
+        }
+    }
+
+    #[test]
+    fn test_normal_on_cylinder_caps() {
+        let mut cylinder = Cylinder::init();
+        cylinder.minimum = 1.0;
+        cylinder.maximum = 2.0;
+        cylinder.closed = true;
+        let examples = [
+            (point!(0.0, 1.0, 0.0), vector!(0.0, -1.0, 0.0)),
+            (point!(0.5, 1.0, 0.0), vector!(0.0, -1.0, 0.0)),
+            (point!(0.0, 1.0, 0.5), vector!(0.0, -1.0, 0.0)),
+            (point!(0.0, 2.0, 0.0), vector!(0.0, 1.0, 0.0)),
+            (point!(0.5, 2.0, 0.0), vector!(0.0, 1.0, 0.0)),
+            (point!(0.0, 2.0, 0.5), vector!(0.0, 1.0, 0.0)),
+        ];
+
+        for (point, normal) in examples {
+            assert_eq!(cylinder.local_normal_at(point), normal);
+        }
+    }
+}
@@ -0,0 +1,972 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fmt;
+
+// zlib streams (RFC 1950) around raw deflate data (RFC 1951)
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Compression {
+    Stored,
+    Deflate,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct DeflateError {
+    pub message: String,
+}
+
+impl fmt::Display for DeflateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for DeflateError {}
+
+fn invalid<T>(message: impl Into<String>) -> Result<T, DeflateError> {
+    Err(DeflateError {
+        message: message.into(),
+    })
+}
+
+const WINDOW_SIZE: usize = 32768;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const HASH_BITS: u32 = 15;
+// how many earlier positions with the same hash are tried for a match
+const MAX_CHAIN: usize = 128;
+const MAX_STORED_BLOCK: usize = 65535;
+const BLOCK_TOKENS: usize = 1 << 15;
+
+const MAX_CODE_LENGTH: u8 = 15;
+const MAX_CODE_LENGTH_CODE_LENGTH: u8 = 7;
+const END_OF_BLOCK: usize = 256;
+const LITERAL_CODES: usize = 286;
+const DISTANCE_CODES: usize = 30;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+// the order in which code length code lengths are stored
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+pub fn adler32(data: &[u8]) -> u32 {
+    const MODULUS: u32 = 65521;
+    let (mut a, mut b) = (1, 0);
+
+    // 5552 bytes is the most that can be summed before b overflows
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= MODULUS;
+        b %= MODULUS;
+    }
+
+    b << 16 | a
+}
+
+pub fn zlib_compress(data: &[u8], compression: Compression) -> Vec<u8> {
+    // 32K window, deflate, no preset dictionary, check bits making the header a multiple of 31
+    let mut output = vec![0x78, 0x01];
+    output.extend(deflate(data, compression));
+    output.extend_from_slice(&adler32(data).to_be_bytes());
+    output
+}
+
+pub fn zlib_decompress(data: &[u8]) -> Result<Vec<u8>, DeflateError> {
+    if data.len() < 2 {
+        return invalid("zlib stream is too short");
+    }
+    let (cmf, flg) = (data[0], data[1]);
+    if cmf & 0x0f != 8 || cmf >> 4 > 7 {
+        return invalid("zlib stream does not use deflate");
+    }
+    if (cmf as u16 * 256 + flg as u16) % 31 != 0 {
+        return invalid("zlib header check failed");
+    }
+    if flg & 0x20 != 0 {
+        return invalid("zlib preset dictionaries are not supported");
+    }
+
+    let (output, consumed) = inflate_with_length(&data[2..])?;
+    let checksum = match data.get(2 + consumed..2 + consumed + 4) {
+        Some(bytes) => u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+        None => return invalid("zlib stream is missing its checksum"),
+    };
+    if checksum != adler32(&output) {
+        return invalid("zlib checksum mismatch");
+    }
+
+    Ok(output)
+}
+
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u64,
+    count: u32,
+}
+
+impl BitWriter {
+    fn new() -> BitWriter {
+        BitWriter {
+            bytes: vec![],
+            buffer: 0,
+            count: 0,
+        }
+    }
+
+    // least significant bit first, as deflate packs everything but Huffman codes
+    fn write_bits(&mut self, value: u32, bits: u32) {
+        self.buffer |= (value as u64) << self.count;
+        self.count += bits;
+        while self.count >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    fn align(&mut self) {
+        if self.count > 0 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer = 0;
+            self.count = 0;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        self.align();
+        self.bytes
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Token {
+    Literal(u8),
+    Match { length: u16, distance: u16 },
+}
+
+fn hash(data: &[u8], position: usize) -> usize {
+    let value = (data[position] as u32) << 16
+        | (data[position + 1] as u32) << 8
+        | data[position + 2] as u32;
+    (value.wrapping_mul(2_654_435_761) >> (32 - HASH_BITS)) as usize
+}
+
+// greedy LZ77 over hash chains
+fn find_matches(data: &[u8]) -> Vec<Token> {
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut previous = vec![usize::MAX; WINDOW_SIZE];
+    let insert = |position: usize, head: &mut Vec<usize>, previous: &mut Vec<usize>| {
+        if position + MIN_MATCH <= data.len() {
+            let hash = hash(data, position);
+            previous[position % WINDOW_SIZE] = head[hash];
+            head[hash] = position;
+        }
+    };
+    let mut tokens = vec![];
+
+    let mut position = 0;
+    while position < data.len() {
+        let mut best_length = 0;
+        let mut best_distance = 0;
+
+        if position + MIN_MATCH <= data.len() {
+            let max_length = MAX_MATCH.min(data.len() - position);
+            let mut candidate = head[hash(data, position)];
+            let mut chain = MAX_CHAIN;
+
+            while candidate != usize::MAX && position - candidate <= WINDOW_SIZE && chain > 0 {
+                let length = data[candidate..]
+                    .iter()
+                    .zip(&data[position..position + max_length])
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                if length > best_length {
+                    best_length = length;
+                    best_distance = position - candidate;
+                    if length == max_length {
+                        break;
+                    }
+                }
+
+                let next = previous[candidate % WINDOW_SIZE];
+                // the slot was reused by a newer position, so the chain ends here
+                if next >= candidate {
+                    break;
+                }
+                candidate = next;
+                chain -= 1;
+            }
+        }
+
+        if best_length >= MIN_MATCH {
+            tokens.push(Token::Match {
+                length: best_length as u16,
+                distance: best_distance as u16,
+            });
+            for offset in 0..best_length {
+                insert(position + offset, &mut head, &mut previous);
+            }
+            position += best_length;
+        } else {
+            tokens.push(Token::Literal(data[position]));
+            insert(position, &mut head, &mut previous);
+            position += 1;
+        }
+    }
+
+    tokens
+}
+
+// index of the last base that is not greater than value
+fn code_for(bases: &[u16], value: u16) -> usize {
+    bases.iter().rposition(|&base| base <= value).unwrap()
+}
+
+// Huffman code lengths, limited to limit bits; always at least two codes so the code is complete
+fn huffman_lengths(frequencies: &[u32], limit: u8) -> Vec<u8> {
+    let mut frequencies = frequencies.to_vec();
+    for index in 0..2 {
+        if frequencies.iter().filter(|&&f| f > 0).count() < 2 && frequencies[index] == 0 {
+            frequencies[index] = 1;
+        }
+    }
+
+    let mut heap = BinaryHeap::new();
+    let mut parents = vec![usize::MAX; frequencies.len()];
+    for (symbol, &frequency) in frequencies.iter().enumerate() {
+        if frequency > 0 {
+            heap.push(Reverse((frequency as u64, symbol)));
+        }
+    }
+    while heap.len() > 1 {
+        let Reverse((first_weight, first)) = heap.pop().unwrap();
+        let Reverse((second_weight, second)) = heap.pop().unwrap();
+        let node = parents.len();
+        parents.push(usize::MAX);
+        parents[first] = node;
+        parents[second] = node;
+        heap.push(Reverse((first_weight + second_weight, node)));
+    }
+
+    let mut lengths: Vec<u8> = (0..frequencies.len())
+        .map(|symbol| {
+            if frequencies[symbol] == 0 {
+                return 0;
+            }
+            let mut depth = 0;
+            let mut node = symbol;
+            while parents[node] != usize::MAX {
+                node = parents[node];
+                depth += 1;
+            }
+            depth.min(limit as usize) as u8
+        })
+        .collect();
+
+    // clamping may oversubscribe the code, so lengthen codes until it fits
+    let capacity = 1u64 << limit;
+    let mut kraft: u64 = lengths
+        .iter()
+        .filter(|&&length| length > 0)
+        .map(|&length| 1 << (limit - length))
+        .sum();
+    while kraft > capacity {
+        let symbol = (0..lengths.len())
+            .filter(|&symbol| lengths[symbol] > 0 && lengths[symbol] < limit)
+            .max_by_key(|&symbol| lengths[symbol])
+            .unwrap();
+        lengths[symbol] += 1;
+        kraft -= 1 << (limit - lengths[symbol]);
+    }
+    // then shorten the longest codes that fit back into the gap
+    while kraft < capacity {
+        let gap = capacity - kraft;
+        let symbol = (0..lengths.len())
+            .filter(|&symbol| lengths[symbol] > 1 && 1 << (limit - lengths[symbol]) <= gap)
+            .max_by_key(|&symbol| lengths[symbol])
+            .unwrap();
+        kraft += 1 << (limit - lengths[symbol]);
+        lengths[symbol] -= 1;
+    }
+
+    lengths
+}
+
+// canonical codes, bit-reversed so they can be written least significant bit first
+fn huffman_codes(lengths: &[u8]) -> Vec<u16> {
+    let mut counts = [0u16; 16];
+    for &length in lengths {
+        counts[length as usize] += 1;
+    }
+    counts[0] = 0;
+
+    let mut next = [0u16; 16];
+    let mut code = 0;
+    for bits in 1..16 {
+        code = (code + counts[bits - 1]) << 1;
+        next[bits] = code;
+    }
+
+    lengths
+        .iter()
+        .map(|&length| {
+            if length == 0 {
+                return 0;
+            }
+            let code = next[length as usize];
+            next[length as usize] += 1;
+            code.reverse_bits() >> (16 - length)
+        })
+        .collect()
+}
+
+fn fixed_lengths() -> (Vec<u8>, Vec<u8>) {
+    let mut literals = vec![8; 288];
+    literals[144..256].iter_mut().for_each(|length| *length = 9);
+    literals[256..280].iter_mut().for_each(|length| *length = 7);
+    (literals, vec![5; DISTANCE_CODES])
+}
+
+// code length symbols with their extra bits: 16 repeats the previous length, 17 and 18 repeat zeros
+fn run_length_encode(lengths: &[u8]) -> Vec<(u8, u8)> {
+    let mut symbols = vec![];
+    let mut index = 0;
+
+    while index < lengths.len() {
+        let length = lengths[index];
+        let run = lengths[index..]
+            .iter()
+            .take_while(|&&other| other == length)
+            .count();
+
+        if length == 0 && run >= 3 {
+            let run = run.min(138);
+            if run <= 10 {
+                symbols.push((17, run as u8 - 3));
+            } else {
+                symbols.push((18, run as u8 - 11));
+            }
+            index += run;
+        } else if length != 0 && run >= 4 {
+            symbols.push((length, 0));
+            let run = (run - 1).min(6);
+            symbols.push((16, run as u8 - 3));
+            index += run + 1;
+        } else {
+            symbols.push((length, 0));
+            index += 1;
+        }
+    }
+
+    symbols
+}
+
+struct Block<'a> {
+    tokens: &'a [Token],
+    bytes: &'a [u8],
+}
+
+impl<'a> Block<'a> {
+    fn frequencies(&self) -> (Vec<u32>, Vec<u32>) {
+        let mut literals = vec![0; LITERAL_CODES];
+        let mut distances = vec![0; DISTANCE_CODES];
+        for token in self.tokens {
+            match *token {
+                Token::Literal(byte) => literals[byte as usize] += 1,
+                Token::Match { length, distance } => {
+                    literals[257 + code_for(&LENGTH_BASE, length)] += 1;
+                    distances[code_for(&DISTANCE_BASE, distance)] += 1;
+                }
+            }
+        }
+        literals[END_OF_BLOCK] += 1;
+        (literals, distances)
+    }
+
+    // bits taken by the tokens themselves, extra bits included
+    fn cost(&self, literal_lengths: &[u8], distance_lengths: &[u8]) -> u64 {
+        let (literals, distances) = self.frequencies();
+        let literal_cost: u64 = literals
+            .iter()
+            .enumerate()
+            .map(|(symbol, &count)| {
+                let extra = if symbol > END_OF_BLOCK {
+                    LENGTH_EXTRA[symbol - 257]
+                } else {
+                    0
+                };
+                count as u64 * (literal_lengths[symbol] + extra) as u64
+            })
+            .sum();
+        let distance_cost: u64 = distances
+            .iter()
+            .enumerate()
+            .map(|(symbol, &count)| {
+                count as u64 * (distance_lengths[symbol] + DISTANCE_EXTRA[symbol]) as u64
+            })
+            .sum();
+        literal_cost + distance_cost
+    }
+
+    fn write_tokens(
+        &self,
+        writer: &mut BitWriter,
+        literal_lengths: &[u8],
+        distance_lengths: &[u8],
+    ) {
+        let literal_codes = huffman_codes(literal_lengths);
+        let distance_codes = huffman_codes(distance_lengths);
+        let write_symbol = |writer: &mut BitWriter, codes: &[u16], lengths: &[u8], symbol| {
+            writer.write_bits(codes[symbol] as u32, lengths[symbol] as u32)
+        };
+
+        for token in self.tokens {
+            match *token {
+                Token::Literal(byte) => {
+                    write_symbol(writer, &literal_codes, literal_lengths, byte as usize)
+                }
+                Token::Match { length, distance } => {
+                    let code = code_for(&LENGTH_BASE, length);
+                    write_symbol(writer, &literal_codes, literal_lengths, 257 + code);
+                    writer.write_bits(
+                        (length - LENGTH_BASE[code]) as u32,
+                        LENGTH_EXTRA[code] as u32,
+                    );
+
+                    let code = code_for(&DISTANCE_BASE, distance);
+                    write_symbol(writer, &distance_codes, distance_lengths, code);
+                    writer.write_bits(
+                        (distance - DISTANCE_BASE[code]) as u32,
+                        DISTANCE_EXTRA[code] as u32,
+                    );
+                }
+            }
+        }
+        write_symbol(writer, &literal_codes, literal_lengths, END_OF_BLOCK);
+    }
+
+    fn write(&self, writer: &mut BitWriter, last: bool) {
+        let (literals, distances) = self.frequencies();
+        let literal_lengths = huffman_lengths(&literals, MAX_CODE_LENGTH);
+        let distance_lengths = huffman_lengths(&distances, MAX_CODE_LENGTH);
+
+        let literal_count = 257.max(literal_lengths.iter().rposition(|&l| l > 0).unwrap() + 1);
+        let distance_count = 1.max(distance_lengths.iter().rposition(|&l| l > 0).unwrap() + 1);
+        let mut all_lengths = literal_lengths[..literal_count].to_vec();
+        all_lengths.extend_from_slice(&distance_lengths[..distance_count]);
+        let code_length_symbols = run_length_encode(&all_lengths);
+
+        let mut code_length_frequencies = vec![0; 19];
+        for &(symbol, _) in code_length_symbols.iter() {
+            code_length_frequencies[symbol as usize] += 1;
+        }
+        let code_length_lengths =
+            huffman_lengths(&code_length_frequencies, MAX_CODE_LENGTH_CODE_LENGTH);
+        let code_length_count = 4.max(
+            CODE_LENGTH_ORDER
+                .iter()
+                .rposition(|&symbol| code_length_lengths[symbol] > 0)
+                .unwrap()
+                + 1,
+        );
+
+        let extra_bits = |symbol: u8| match symbol {
+            16 => 2,
+            17 => 3,
+            18 => 7,
+            _ => 0,
+        };
+        let header_cost = 14
+            + 3 * code_length_count as u64
+            + code_length_symbols
+                .iter()
+                .map(|&(symbol, _)| {
+                    (code_length_lengths[symbol as usize] + extra_bits(symbol)) as u64
+                })
+                .sum::<u64>();
+        let dynamic_cost = header_cost + self.cost(&literal_lengths, &distance_lengths);
+
+        let (fixed_literal_lengths, fixed_distance_lengths) = fixed_lengths();
+        let fixed_cost = self.cost(&fixed_literal_lengths, &fixed_distance_lengths);
+
+        // stored blocks are byte aligned and need four bytes of lengths each
+        let stored_blocks = self.bytes.len().max(1).div_ceil(MAX_STORED_BLOCK) as u64;
+        let stored_cost = 8 * (self.bytes.len() as u64 + 5 * stored_blocks);
+
+        if stored_cost <= fixed_cost.min(dynamic_cost) {
+            write_stored(writer, self.bytes, last);
+        } else if fixed_cost <= dynamic_cost {
+            writer.write_bits(last as u32, 1);
+            writer.write_bits(1, 2);
+            self.write_tokens(writer, &fixed_literal_lengths, &fixed_distance_lengths);
+        } else {
+            writer.write_bits(last as u32, 1);
+            writer.write_bits(2, 2);
+            writer.write_bits(literal_count as u32 - 257, 5);
+            writer.write_bits(distance_count as u32 - 1, 5);
+            writer.write_bits(code_length_count as u32 - 4, 4);
+            for &symbol in CODE_LENGTH_ORDER[..code_length_count].iter() {
+                writer.write_bits(code_length_lengths[symbol] as u32, 3);
+            }
+
+            let code_length_codes = huffman_codes(&code_length_lengths);
+            for &(symbol, extra) in code_length_symbols.iter() {
+                let symbol = symbol as usize;
+                writer.write_bits(
+                    code_length_codes[symbol] as u32,
+                    code_length_lengths[symbol] as u32,
+                );
+                writer.write_bits(extra as u32, extra_bits(symbol as u8) as u32);
+            }
+
+            self.write_tokens(writer, &literal_lengths, &distance_lengths);
+        }
+    }
+}
+
+fn write_stored(writer: &mut BitWriter, bytes: &[u8], last: bool) {
+    let mut chunks: Vec<&[u8]> = bytes.chunks(MAX_STORED_BLOCK).collect();
+    if chunks.is_empty() {
+        chunks.push(&[]);
+    }
+
+    let count = chunks.len();
+    for (index, chunk) in chunks.into_iter().enumerate() {
+        writer.write_bits((last && index + 1 == count) as u32, 1);
+        writer.write_bits(0, 2);
+        writer.align();
+        let length = chunk.len() as u16;
+        writer.bytes.extend_from_slice(&length.to_le_bytes());
+        writer.bytes.extend_from_slice(&(!length).to_le_bytes());
+        writer.bytes.extend_from_slice(chunk);
+    }
+}
+
+// raw deflate data, without the zlib header and checksum
+pub fn deflate(data: &[u8], compression: Compression) -> Vec<u8> {
+    let mut writer = BitWriter::new();
+
+    match compression {
+        Compression::Stored => write_stored(&mut writer, data, true),
+        Compression::Deflate => {
+            let tokens = find_matches(data);
+            let mut chunks = tokens.chunks(BLOCK_TOKENS).peekable();
+            let mut start = 0;
+
+            if chunks.peek().is_none() {
+                write_stored(&mut writer, data, true);
+            }
+            while let Some(tokens) = chunks.next() {
+                let length: usize = tokens
+                    .iter()
+                    .map(|token| match *token {
+                        Token::Literal(_) => 1,
+                        Token::Match { length, .. } => length as usize,
+                    })
+                    .sum();
+                let block = Block {
+                    tokens,
+                    bytes: &data[start..start + length],
+                };
+                block.write(&mut writer, chunks.peek().is_none());
+                start += length;
+            }
+        }
+    }
+
+    writer.finish()
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+    buffer: u32,
+    count: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn bits(&mut self, bits: u32) -> Result<u32, DeflateError> {
+        while self.count < bits {
+            match self.data.get(self.position) {
+                Some(&byte) => self.buffer |= (byte as u32) << self.count,
+                None => return invalid("unexpected end of deflate data"),
+            }
+            self.position += 1;
+            self.count += 8;
+        }
+
+        let value = self.buffer & ((1u64 << bits) - 1) as u32;
+        self.buffer >>= bits;
+        self.count -= bits;
+        Ok(value)
+    }
+
+    // drops what is left of the current byte
+    fn align(&mut self) {
+        self.buffer = 0;
+        self.count = 0;
+    }
+}
+
+// a canonical Huffman code as counts per length and symbols ordered by code
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Huffman, DeflateError> {
+        let mut counts = [0u16; 16];
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+
+        let mut left: i32 = 1;
+        for &count in counts[1..].iter() {
+            left = (left << 1) - count as i32;
+            if left < 0 {
+                return invalid("oversubscribed Huffman code");
+            }
+        }
+
+        let mut offsets = [0u16; 16];
+        for length in 1..15 {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                symbols[offsets[length as usize] as usize] = symbol as u16;
+                offsets[length as usize] += 1;
+            }
+        }
+
+        Ok(Huffman { counts, symbols })
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<usize, DeflateError> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+
+        for length in 1..16 {
+            code |= reader.bits(1)? as i32;
+            let count = self.counts[length] as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize] as usize);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+
+        invalid("invalid Huffman code")
+    }
+}
+
+fn inflate_block(
+    reader: &mut BitReader,
+    output: &mut Vec<u8>,
+    literals: &Huffman,
+    distances: &Huffman,
+) -> Result<(), DeflateError> {
+    loop {
+        let symbol = literals.decode(reader)?;
+        if symbol < END_OF_BLOCK {
+            output.push(symbol as u8);
+            continue;
+        }
+        if symbol == END_OF_BLOCK {
+            return Ok(());
+        }
+
+        let code = symbol - 257;
+        if code >= LENGTH_BASE.len() {
+            return invalid(format!("invalid length symbol {}", symbol));
+        }
+        let length = LENGTH_BASE[code] as usize + reader.bits(LENGTH_EXTRA[code] as u32)? as usize;
+
+        let code = distances.decode(reader)?;
+        if code >= DISTANCE_BASE.len() {
+            return invalid(format!("invalid distance symbol {}", code));
+        }
+        let distance =
+            DISTANCE_BASE[code] as usize + reader.bits(DISTANCE_EXTRA[code] as u32)? as usize;
+        if distance > output.len() {
+            return invalid("distance points before the start of the data");
+        }
+
+        // the match may overlap what it is copying
+        let start = output.len() - distance;
+        for offset in 0..length {
+            let byte = output[start + offset];
+            output.push(byte);
+        }
+    }
+}
+
+fn read_dynamic_codes(reader: &mut BitReader) -> Result<(Huffman, Huffman), DeflateError> {
+    let literal_count = reader.bits(5)? as usize + 257;
+    let distance_count = reader.bits(5)? as usize + 1;
+    let code_length_count = reader.bits(4)? as usize + 4;
+    if literal_count > LITERAL_CODES || distance_count > DISTANCE_CODES {
+        return invalid("too many length or distance codes");
+    }
+
+    let mut code_length_lengths = [0u8; 19];
+    for &symbol in CODE_LENGTH_ORDER[..code_length_count].iter() {
+        code_length_lengths[symbol] = reader.bits(3)? as u8;
+    }
+    let code_lengths = Huffman::new(&code_length_lengths)?;
+
+    let mut lengths = Vec::with_capacity(literal_count + distance_count);
+    while lengths.len() < literal_count + distance_count {
+        let symbol = code_lengths.decode(reader)?;
+        let (length, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => match lengths.last() {
+                Some(&previous) => (previous, 3 + reader.bits(2)? as usize),
+                None => return invalid("repeated code length without a previous length"),
+            },
+            17 => (0, 3 + reader.bits(3)? as usize),
+            _ => (0, 11 + reader.bits(7)? as usize),
+        };
+        if lengths.len() + repeat > literal_count + distance_count {
+            return invalid("too many code lengths");
+        }
+        lengths.extend(std::iter::repeat(length).take(repeat));
+    }
+
+    if lengths[END_OF_BLOCK] == 0 {
+        return invalid("missing end of block code");
+    }
+    Ok((
+        Huffman::new(&lengths[..literal_count])?,
+        Huffman::new(&lengths[literal_count..])?,
+    ))
+}
+
+// returns the data and how many input bytes the deflate stream took
+fn inflate_with_length(data: &[u8]) -> Result<(Vec<u8>, usize), DeflateError> {
+    let mut reader = BitReader {
+        data,
+        position: 0,
+        buffer: 0,
+        count: 0,
+    };
+    let mut output = vec![];
+
+    loop {
+        let last = reader.bits(1)? == 1;
+        match reader.bits(2)? {
+            0 => {
+                reader.align();
+                let header = match data.get(reader.position..reader.position + 4) {
+                    Some(header) => header,
+                    None => return invalid("unexpected end of deflate data"),
+                };
+                let length = u16::from_le_bytes([header[0], header[1]]);
+                let complement = u16::from_le_bytes([header[2], header[3]]);
+                if length != !complement {
+                    return invalid("stored block length does not match its complement");
+                }
+
+                let start = reader.position + 4;
+                match data.get(start..start + length as usize) {
+                    Some(bytes) => output.extend_from_slice(bytes),
+                    None => return invalid("unexpected end of deflate data"),
+                }
+                reader.position = start + length as usize;
+            }
+            1 => {
+                let (literal_lengths, distance_lengths) = fixed_lengths();
+                let literals = Huffman::new(&literal_lengths)?;
+                let distances = Huffman::new(&distance_lengths)?;
+                inflate_block(&mut reader, &mut output, &literals, &distances)?;
+            }
+            2 => {
+                let (literals, distances) = read_dynamic_codes(&mut reader)?;
+                inflate_block(&mut reader, &mut output, &literals, &distances)?;
+            }
+            _ => return invalid("invalid deflate block type"),
+        }
+
+        if last {
+            return Ok((output, reader.position));
+        }
+    }
+}
+
+pub fn inflate(data: &[u8]) -> Result<Vec<u8>, DeflateError> {
+    inflate_with_length(data).map(|(output, _)| output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // deterministic bytes that do not compress well
+    fn noise(length: usize) -> Vec<u8> {
+        let mut state: u32 = 12345;
+        (0..length)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                (state >> 16) as u8
+            })
+            .collect()
+    }
+
+    fn samples() -> Vec<Vec<u8>> {
+        vec![
+            vec![],
+            b"a".to_vec(),
+            b"hello hello hello hello, deflate!".to_vec(),
+            vec![0; 100_000],
+            (0..70_000).map(|i| (i % 251) as u8).collect(),
+            (0..5000)
+                .map(|i| b"aaaaaaabbbbccd"[(i * i + i / 7) % 14])
+                .collect(),
+            noise(80_000),
+        ]
+    }
+
+    #[test]
+    fn test_adler32() {
+        assert_eq!(adler32(b""), 1);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+        assert_eq!(adler32(&vec![0xff; 100_000]), 0x149a_302c);
+    }
+
+    #[test]
+    fn test_zlib_round_trip() {
+        for data in samples() {
+            for &compression in [Compression::Stored, Compression::Deflate].iter() {
+                let compressed = zlib_compress(&data, compression);
+
+                assert_eq!(zlib_decompress(&compressed), Ok(data.clone()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_deflate_compresses_repetitive_data() {
+        let data = vec![7; 100_000];
+
+        assert!(zlib_compress(&data, Compression::Deflate).len() < 1000);
+        assert!(zlib_compress(&data, Compression::Stored).len() > 100_000);
+    }
+
+    #[test]
+    fn test_deflate_does_not_grow_noise_much() {
+        let data = noise(80_000);
+
+        assert!(deflate(&data, Compression::Deflate).len() < 80_000 + 100);
+    }
+
+    #[test]
+    fn test_huffman_lengths_are_limited_and_complete() {
+        // Fibonacci frequencies give the deepest possible tree
+        let mut frequencies = vec![1, 1];
+        while frequencies.len() < 30 {
+            let next = frequencies[frequencies.len() - 1] + frequencies[frequencies.len() - 2];
+            frequencies.push(next);
+        }
+
+        let lengths = huffman_lengths(&frequencies, 7);
+
+        assert!(lengths.iter().all(|&length| (1..=7).contains(&length)));
+        let kraft: u32 = lengths.iter().map(|&length| 1 << (7 - length)).sum();
+        assert_eq!(kraft, 1 << 7);
+    }
+
+    #[test]
+    fn test_huffman_lengths_for_a_single_symbol() {
+        assert_eq!(huffman_lengths(&[0, 0, 5], 15), vec![1, 0, 1]);
+        assert_eq!(huffman_lengths(&[5, 0, 0], 15), vec![1, 1, 0]);
+    }
+
+    #[test]
+    fn test_inflating_fixed_huffman_from_zlib() {
+        let compressed = [
+            120, 218, 203, 72, 205, 201, 201, 87, 200, 64, 39, 117, 20, 82, 82, 211, 114, 18, 75,
+            82, 21, 1, 207, 243, 11, 243,
+        ];
+
+        assert_eq!(
+            zlib_decompress(&compressed),
+            Ok(b"hello hello hello hello, deflate!".to_vec())
+        );
+    }
+
+    #[test]
+    fn test_inflating_dynamic_huffman_from_zlib() {
+        let compressed = [
+            120, 218, 229, 139, 193, 17, 0, 48, 8, 194, 102, 37, 184, 255, 12, 141, 119, 110, 81,
+            62, 16, 148, 36, 164, 64, 245, 85, 51, 11, 118, 2, 99, 232, 120, 146, 224, 94, 126, 29,
+            60, 93, 175, 114, 142,
+        ];
+        let expected: Vec<u8> = (0..300)
+            .map(|i| b"aaaaaaabbbbccd"[(i * i + i / 7) % 14])
+            .collect();
+
+        assert_eq!(zlib_decompress(&compressed), Ok(expected));
+    }
+
+    #[test]
+    fn test_zlib_errors() {
+        let mut compressed = zlib_compress(b"some data", Compression::Deflate);
+        let length = compressed.len();
+
+        assert!(zlib_decompress(&compressed[..length - 1]).is_err());
+        assert!(zlib_decompress(&compressed[..length - 6]).is_err());
+        assert!(zlib_decompress(&[0x78]).is_err());
+        assert!(zlib_decompress(&[0x78, 0x02, 0x03, 0x00]).is_err());
+
+        compressed[length - 1] ^= 1;
+        assert_eq!(
+            zlib_decompress(&compressed).unwrap_err().message,
+            "zlib checksum mismatch"
+        );
+    }
+
+    #[test]
+    fn test_inflate_errors() {
+        // block type 3 is reserved
+        assert!(inflate(&[0b111]).is_err());
+        // a stored block whose length does not match its complement
+        assert!(inflate(&[0b001, 4, 0, 0, 0]).is_err());
+        // a distance larger than the output so far
+        let mut writer = BitWriter::new();
+        writer.write_bits(1, 1);
+        writer.write_bits(1, 2);
+        let (literals, distances) = fixed_lengths();
+        let block = Block {
+            tokens: &[Token::Match {
+                length: 3,
+                distance: 1,
+            }],
+            bytes: &[],
+        };
+        block.write_tokens(&mut writer, &literals, &distances);
+        assert_eq!(
+            inflate(&writer.finish()).unwrap_err().message,
+            "distance points before the start of the data"
+        );
+    }
+}
@@ -0,0 +1,298 @@
+use crate::bounds::BoundingBox;
+use crate::bvh::Bvh;
+use crate::material::*;
+use crate::matrix::*;
+use crate::ray::*;
+use crate::shape::Shape;
+use crate::tuple::*;
+use std::sync::OnceLock;
+
+// A collection of shapes transformed as a single unit. Children keep their
+// own transform, which is applied after the transform of the group.
+#[derive(Debug)]
+pub struct Group {
+    // private, as every change has to be pushed down to the children
+    transform: Transform,
+    pub material: Material,
+    parent_transform: Transform,
+    children: Vec<Box<dyn Shape>>,
+    // around all children, in the space of the group
+    bounds: BoundingBox,
+    // built on the first intersection and dropped whenever a child is added
+    bvh: OnceLock<Bvh>,
+}
+
+impl Group {
+    pub fn init() -> Group {
+        Group {
+            transform: Transform::default(),
+            material: Material::default(),
+            parent_transform: Transform::default(),
+            children: vec![],
+            bounds: BoundingBox::empty(),
+            bvh: OnceLock::new(),
+        }
+    }
+
+    pub fn add_child(&mut self, mut child: Box<dyn Shape>) {
+        child.set_parent_transform(self.world_transform());
+        self.bounds.merge(&child.parent_space_bounds());
+        self.children.push(child);
+        self.bvh = OnceLock::new();
+    }
+
+    pub fn children(&self) -> &[Box<dyn Shape>] {
+        &self.children
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.children.is_empty()
+    }
+
+    // everything between world space and the space of the children
+    fn world_transform(&self) -> Matrix4 {
+        self.parent_transform.matrix() * self.transform.matrix()
+    }
+
+    fn update_children(&mut self) {
+        let world_transform = self.world_transform();
+        for child in self.children.iter_mut() {
+            child.set_parent_transform(world_transform);
+        }
+    }
+}
+
+impl Shape for Group {
+    fn transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    fn set_transform(&mut self, transform: Matrix4) {
+        self.transform = Transform::new(transform);
+        self.update_children();
+    }
+
+    fn parent_transform(&self) -> &Transform {
+        &self.parent_transform
+    }
+
+    fn set_parent_transform(&mut self, transform: Matrix4) {
+        self.parent_transform = Transform::new(transform);
+        self.update_children();
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn material_mut(&mut self) -> &mut Material {
+        &mut self.material
+    }
+
+    fn local_intersect(&self, ray: &Ray) -> Vec<Intersection> {
+        if !self.bounds.intersects(ray) {
+            return vec![];
+        }
+
+        let bvh = self.bvh.get_or_init(|| Bvh::build(&self.children));
+        let mut intersections = bvh.intersect(&self.children, ray);
+        intersections.sort_by(|a, b| a.t.partial_cmp(&b.t).unwrap());
+
+        intersections
+    }
+
+    fn bounds(&self) -> BoundingBox {
+        self.bounds
+    }
+
+    fn local_normal_at(&self, _object_point: Tuple) -> Tuple {
+        unreachable!("groups have no surface, intersections always point to a child")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cylinder::Cylinder;
+    use crate::point;
+    use crate::sphere::Sphere;
+    use crate::vector;
+    use std::f64::consts::FRAC_PI_2;
+
+    #[test]
+    fn test_creating_a_group() {
+        let group = Group::init();
+
+        assert_eq!(group.transform(), &Matrix4::identity());
+        assert!(group.is_empty());
+    }
+
+    #[test]
+    fn test_adding_a_child() {
+        let mut group = Group::init();
+        group.add_child(Box::new(Sphere::init()));
+
+        assert_eq!(group.children().len(), 1);
+    }
+
+    #[test]
+    fn test_intersecting_empty_group() {
+        let group = Group::init();
+        let ray = Ray::new(point!(0.0, 0.0, 0.0), vector!(0.0, 0.0, 1.0));
+
+        assert_eq!(group.local_intersect(&ray).len(), 0);
+    }
+
+    #[test]
+    fn test_intersecting_group_with_children() {
+        let mut group = Group::init();
+        let s1 = Sphere::init();
+        let mut s2 = Sphere::init();
+        s2.set_transform(Matrix4::identity().translation(0.0, 0.0, -3.0));
+        let mut s3 = Sphere::init();
+        s3.set_transform(Matrix4::identity().translation(5.0, 0.0, 0.0));
+        group.add_child(Box::new(s1));
+        group.add_child(Box::new(s2));
+        group.add_child(Box::new(s3));
+
+        let ray = Ray::new(point!(0.0, 0.0, -5.0), vector!(0.0, 0.0, 1.0));
+        let intersections = group.local_intersect(&ray);
+        let children = group.children();
+
+        assert_eq!(intersections.len(), 4);
+        assert_eq!(intersections[0].object, children[1].as_ref());
+        assert_eq!(intersections[1].object, children[1].as_ref());
+        assert_eq!(intersections[2].object, children[0].as_ref());
+        assert_eq!(intersections[3].object, children[0].as_ref());
+    }
+
+    #[test]
+    fn test_intersecting_transformed_group() {
+        let mut group = Group::init();
+        group.set_transform(Matrix4::identity().scaling(2.0, 2.0, 2.0));
+        let mut sphere = Sphere::init();
+        sphere.set_transform(Matrix4::identity().translation(5.0, 0.0, 0.0));
+        group.add_child(Box::new(sphere));
+
+        let ray = Ray::new(point!(10.0, 0.0, -10.0), vector!(0.0, 0.0, 1.0));
+
+        assert_eq!(ray.intersect(&group).len(), 2);
+    }
+
+    #[test]
+    fn test_group_bounds_contain_children() {
+        let mut group = Group::init();
+        let mut sphere = Sphere::init();
+        sphere.set_transform(
+            Matrix4::identity()
+                .scaling(2.0, 2.0, 2.0)
+                .translation(2.0, 5.0, -3.0),
+        );
+        let mut cylinder = Cylinder::init();
+        cylinder.minimum = -2.0;
+        cylinder.maximum = 2.0;
+        cylinder.set_transform(
+            Matrix4::identity()
+                .scaling(0.5, 1.0, 0.5)
+                .translation(-4.0, -1.0, 4.0),
+        );
+        group.add_child(Box::new(sphere));
+        group.add_child(Box::new(cylinder));
+
+        let bounds = group.bounds();
+
+        assert_eq!(bounds.min, point!(-4.5, -3.0, -5.0));
+        assert_eq!(bounds.max, point!(4.0, 7.0, 4.5));
+    }
+
+    #[test]
+    fn test_ray_missing_group_bounds_skips_children() {
+        let mut group = Group::init();
+        group.add_child(Box::new(Sphere::init()));
+        let ray = Ray::new(point!(5.0, 5.0, -5.0), vector!(0.0, -1.0, 0.0));
+
+        assert!(!group.bounds().intersects(&ray));
+        assert_eq!(group.local_intersect(&ray).len(), 0);
+    }
+
+    #[test]
+    fn test_intersecting_large_group() {
+        let mut group = Group::init();
+        for i in 0..100 {
+            let mut sphere = Sphere::init();
+            sphere.set_transform(Matrix4::identity().translation(0.0, 0.0, i as f64 * 3.0));
+            group.add_child(Box::new(sphere));
+        }
+
+        let ray = Ray::new(point!(0.0, 0.0, -5.0), vector!(0.0, 0.0, 1.0));
+        let intersections = group.local_intersect(&ray);
+
+        assert_eq!(intersections.len(), 200);
+        assert_eq!(intersections[0].t, 4.0);
+        assert_eq!(intersections[199].t, 303.0);
+    }
+
+    fn nested_groups(scaling: Matrix4) -> Group {
+        let mut g1 = Group::init();
+        g1.set_transform(Matrix4::identity().rotation_y(FRAC_PI_2));
+        let mut g2 = Group::init();
+        g2.set_transform(scaling);
+        let mut sphere = Sphere::init();
+        sphere.set_transform(Matrix4::identity().translation(5.0, 0.0, 0.0));
+        g2.add_child(Box::new(sphere));
+        g1.add_child(Box::new(g2));
+        g1
+    }
+
+    // the sphere inside nested_groups, found through an intersection as
+    // children are only reachable as shapes
+    fn nested_sphere(g1: &Group) -> &dyn Shape {
+        let ray = Ray::new(point!(0.0, 0.0, -20.0), vector!(0.0, 0.0, 1.0));
+        ray.intersect(g1)[0].object
+    }
+
+    #[test]
+    fn test_converting_point_from_world_to_object_space() {
+        let g1 = nested_groups(Matrix4::identity().scaling(2.0, 2.0, 2.0));
+        let sphere = nested_sphere(&g1);
+
+        let point = sphere.world_to_object(point!(-2.0, 0.0, -10.0));
+
+        assert_eq!(point, point!(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn test_converting_normal_from_object_to_world_space() {
+        let g1 = nested_groups(Matrix4::identity().scaling(1.0, 2.0, 3.0));
+        let sphere = nested_sphere(&g1);
+        let third = 3.0_f64.sqrt() / 3.0;
+
+        let normal = sphere.normal_to_world(vector!(third, third, third));
+
+        assert_eq!(normal, vector!(0.2857, 0.4286, -0.8571));
+    }
+
+    #[test]
+    fn test_finding_normal_on_child_object() {
+        let g1 = nested_groups(Matrix4::identity().scaling(1.0, 2.0, 3.0));
+        let sphere = nested_sphere(&g1);
+
+        let normal = sphere.normal_at(point!(1.7321, 1.1547, -5.5774));
+
+        assert_eq!(normal, vector!(0.2857, 0.4286, -0.8571));
+    }
+
+    #[test]
+    fn test_moving_group_after_adding_children() {
+        let mut group = Group::init();
+        group.add_child(Box::new(Sphere::init()));
+        group.set_transform(Matrix4::identity().translation(0.0, 0.0, 10.0));
+
+        let child = &group.children()[0];
+
+        assert_eq!(
+            child.world_to_object(point!(0.0, 0.0, 10.0)),
+            point!(0.0, 0.0, 0.0)
+        );
+    }
+}
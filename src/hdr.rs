@@ -0,0 +1,424 @@
+use std::fmt;
+use std::io::{self, Read, Write};
+
+use crate::canvas::Canvas;
+use crate::color::Color;
+
+// Radiance RGBE: three 8-bit mantissas sharing an exponent byte, scanlines run length encoded
+
+// scanlines outside this width range cannot be run length encoded
+const MIN_RLE_WIDTH: usize = 8;
+const MAX_RLE_WIDTH: usize = 0x7fff;
+// shorter runs are cheaper to store as literals
+const MIN_RUN: usize = 4;
+const MAX_RUN: usize = 127;
+const MAX_LITERALS: usize = 128;
+
+#[derive(Debug)]
+pub enum HdrError {
+    Io(io::Error),
+    Invalid(String),
+}
+
+impl fmt::Display for HdrError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HdrError::Io(error) => write!(f, "{}", error),
+            HdrError::Invalid(message) => write!(f, "invalid Radiance HDR: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for HdrError {}
+
+impl From<io::Error> for HdrError {
+    fn from(error: io::Error) -> Self {
+        HdrError::Io(error)
+    }
+}
+
+fn invalid<T>(message: impl Into<String>) -> Result<T, HdrError> {
+    Err(HdrError::Invalid(message.into()))
+}
+
+// negative channels are clamped to zero
+fn to_rgbe(color: &Color) -> [u8; 4] {
+    let red = color.red.max(0.0);
+    let green = color.green.max(0.0);
+    let blue = color.blue.max(0.0);
+    let brightest = red.max(green).max(blue);
+    if brightest < 1e-32 {
+        return [0, 0, 0, 0];
+    }
+    // 2^127 is the largest exponent the format can hold
+    let brightest = brightest.min(f64::powi(2.0, 127) * 0.999);
+
+    // brightest = mantissa * 2^exponent, with mantissa in [0.5, 1)
+    let exponent = ((brightest.to_bits() >> 52) & 0x7ff) as i32 - 1022;
+    let scale = 256.0 / f64::powi(2.0, exponent);
+    [
+        (red * scale).min(255.0) as u8,
+        (green * scale).min(255.0) as u8,
+        (blue * scale).min(255.0) as u8,
+        (exponent + 128) as u8,
+    ]
+}
+
+fn from_rgbe(rgbe: &[u8]) -> Color {
+    if rgbe[3] == 0 {
+        return Color::new(0.0, 0.0, 0.0);
+    }
+    // mantissas are truncated when written, so read them back from the middle of their range
+    let scale = f64::powi(2.0, rgbe[3] as i32 - (128 + 8));
+    Color::new(
+        (rgbe[0] as f64 + 0.5) * scale,
+        (rgbe[1] as f64 + 0.5) * scale,
+        (rgbe[2] as f64 + 0.5) * scale,
+    )
+}
+
+// one channel of a scanline as runs and literal spans
+fn encode_channel(bytes: &[u8], output: &mut Vec<u8>) {
+    let mut position = 0;
+    while position < bytes.len() {
+        // find where the next run worth encoding starts
+        let mut run_start = position;
+        let mut run_length = 0;
+        while run_start < bytes.len() {
+            run_length = bytes[run_start..]
+                .iter()
+                .take(MAX_RUN)
+                .take_while(|&&byte| byte == bytes[run_start])
+                .count();
+            if run_length >= MIN_RUN {
+                break;
+            }
+            run_start += run_length;
+        }
+
+        for literals in bytes[position..run_start].chunks(MAX_LITERALS) {
+            output.push(literals.len() as u8);
+            output.extend_from_slice(literals);
+        }
+        if run_start < bytes.len() {
+            output.push(128 + run_length as u8);
+            output.push(bytes[run_start]);
+            position = run_start + run_length;
+        } else {
+            position = run_start;
+        }
+    }
+}
+
+pub fn write_hdr<W: Write>(canvas: &Canvas, mut writer: W) -> io::Result<()> {
+    if canvas.width == 0 || canvas.height == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "HDR images cannot be empty",
+        ));
+    }
+    write!(
+        writer,
+        "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+        canvas.height, canvas.width
+    )?;
+
+    let rle = (MIN_RLE_WIDTH..=MAX_RLE_WIDTH).contains(&canvas.width);
+    let mut line = Vec::with_capacity(canvas.width * 4);
+    let mut channel = Vec::with_capacity(canvas.width);
+    for row in canvas.pixels.iter() {
+        let pixels: Vec<[u8; 4]> = row.iter().map(to_rgbe).collect();
+        line.clear();
+
+        if rle {
+            line.extend_from_slice(&[2, 2, (canvas.width >> 8) as u8, canvas.width as u8]);
+            for index in 0..4 {
+                channel.clear();
+                channel.extend(pixels.iter().map(|rgbe| rgbe[index]));
+                encode_channel(&channel, &mut line);
+            }
+        } else {
+            line.extend(pixels.iter().flatten());
+        }
+        writer.write_all(&line)?;
+    }
+
+    writer.flush()
+}
+
+fn read_line<'a>(data: &'a [u8], position: &mut usize) -> Result<&'a str, HdrError> {
+    let start = *position;
+    let end = match data[start..].iter().position(|&byte| byte == b'\n') {
+        Some(length) => start + length,
+        None => return invalid("unexpected end of file in header"),
+    };
+    *position = end + 1;
+
+    match std::str::from_utf8(&data[start..end]) {
+        Ok(line) => Ok(line.trim_end_matches('\r')),
+        Err(_) => invalid("header is not text"),
+    }
+}
+
+fn read_scanline(
+    data: &[u8],
+    position: &mut usize,
+    width: usize,
+    line: &mut [u8],
+) -> Result<(), HdrError> {
+    let rest = &data[*position..];
+    let rle = (MIN_RLE_WIDTH..=MAX_RLE_WIDTH).contains(&width)
+        && rest.len() >= 4
+        && rest[0] == 2
+        && rest[1] == 2
+        && rest[2] & 0x80 == 0;
+
+    if !rle {
+        match rest.get(..width * 4) {
+            Some(bytes) => line.copy_from_slice(bytes),
+            None => return invalid("unexpected end of pixel data"),
+        }
+        *position += width * 4;
+        return Ok(());
+    }
+
+    if (rest[2] as usize) << 8 | rest[3] as usize != width {
+        return invalid("scanline width does not match the image width");
+    }
+    let mut offset = 4;
+    let mut next = || match rest.get(offset) {
+        Some(&byte) => {
+            offset += 1;
+            Ok(byte)
+        }
+        None => invalid("unexpected end of pixel data"),
+    };
+    for index in 0..4 {
+        let mut x = 0;
+        while x < width {
+            let count = next()? as usize;
+            let (length, run) = if count > 128 {
+                (count - 128, true)
+            } else {
+                (count, false)
+            };
+            if length == 0 || x + length > width {
+                return invalid("run overflows the scanline");
+            }
+
+            let value = if run { next()? } else { 0 };
+            for _ in 0..length {
+                line[x * 4 + index] = if run { value } else { next()? };
+                x += 1;
+            }
+        }
+    }
+    *position += offset;
+
+    Ok(())
+}
+
+// reads top-down images with the usual -Y height +X width orientation; the exposure is ignored
+pub fn parse_hdr(data: &[u8]) -> Result<Canvas, HdrError> {
+    if !data.starts_with(b"#?") {
+        return invalid("missing #? signature");
+    }
+
+    let mut position = 0;
+    loop {
+        let line = read_line(data, &mut position)?;
+        if line.is_empty() {
+            break;
+        }
+        if let Some(format) = line.strip_prefix("FORMAT=") {
+            if format != "32-bit_rle_rgbe" {
+                return invalid(format!("unsupported format {}", format));
+            }
+        }
+    }
+
+    let resolution = read_line(data, &mut position)?;
+    let parts: Vec<&str> = resolution.split_whitespace().collect();
+    let (width, height): (usize, usize) = match parts.as_slice() {
+        ["-Y", height, "+X", width] => match (width.parse(), height.parse()) {
+            (Ok(width), Ok(height)) => (width, height),
+            _ => return invalid(format!("invalid resolution `{}`", resolution)),
+        },
+        _ => {
+            return invalid(format!(
+                "unsupported resolution `{}`, expected -Y height +X width",
+                resolution
+            ))
+        }
+    };
+    // every scanline takes at least a byte, and wide ones cannot be run length encoded
+    let fits =
+        height <= data.len() && (width <= MAX_RLE_WIDTH || width.saturating_mul(4) <= data.len());
+    if !fits {
+        return invalid(format!(
+            "image size {}x{} is too large for the file",
+            width, height
+        ));
+    }
+
+    let mut canvas = Canvas::init(width, height);
+    let mut line = vec![0; width * 4];
+    for y in 0..height {
+        read_scanline(data, &mut position, width, &mut line)?;
+        for (x, rgbe) in line.chunks_exact(4).enumerate() {
+            canvas.write_pixel(x, y, from_rgbe(rgbe));
+        }
+    }
+
+    Ok(canvas)
+}
+
+pub fn read_hdr<R: Read>(mut reader: R) -> Result<Canvas, HdrError> {
+    let mut data = vec![];
+    reader.read_to_end(&mut data)?;
+    parse_hdr(&data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: &Color, expected: &Color) {
+        for (a, e) in [
+            (actual.red, expected.red),
+            (actual.green, expected.green),
+            (actual.blue, expected.blue),
+        ]
+        .iter()
+        {
+            // eight bits of mantissa relative to the brightest channel
+            let brightest = expected.red.max(expected.green).max(expected.blue);
+            assert!(
+                (a - e).abs() <= brightest / 128.0,
+                "{:?} != {:?}",
+                actual,
+                expected
+            );
+        }
+    }
+
+    fn assert_invalid(data: &[u8], message: &str) {
+        match parse_hdr(data) {
+            Err(HdrError::Invalid(error)) => assert_eq!(error, message),
+            Err(error) => panic!("unexpected error {}", error),
+            Ok(_) => panic!("expected an error"),
+        }
+    }
+
+    #[test]
+    fn test_rgbe_encoding() {
+        assert_eq!(to_rgbe(&Color::new(0.0, 0.0, 0.0)), [0, 0, 0, 0]);
+        assert_eq!(to_rgbe(&Color::new(1.0, 0.5, 0.0)), [128, 64, 0, 129]);
+        assert_eq!(to_rgbe(&Color::new(-1.0, 0.0, 0.0)), [0, 0, 0, 0]);
+
+        let color = Color::new(1.9, 0.25, 1000.0);
+        assert_close(&from_rgbe(&to_rgbe(&color)), &color);
+    }
+
+    #[test]
+    fn test_encode_channel() {
+        let mut output = vec![];
+        encode_channel(&[1, 2, 3, 7, 7, 7, 7, 7, 4], &mut output);
+
+        assert_eq!(output, vec![3, 1, 2, 3, 128 + 5, 7, 1, 4]);
+    }
+
+    #[test]
+    fn test_encode_channel_splits_long_spans() {
+        let bytes: Vec<u8> = (0..300)
+            .map(|i| if i < 150 { 9 } else { i as u8 })
+            .collect();
+        let mut output = vec![];
+        encode_channel(&bytes, &mut output);
+
+        assert_eq!(&output[..4], &[128 + 127, 9, 128 + 23, 9]);
+        assert_eq!(output[4], 128);
+        assert_eq!(output[4 + 129], 22);
+    }
+
+    #[test]
+    fn test_hdr_round_trip() {
+        // narrow images are stored flat, wider ones run length encoded
+        for &width in [3, 40].iter() {
+            let mut canvas = Canvas::init(width, 4);
+            for y in 0..4 {
+                for x in 0..width {
+                    let color = if x < width / 2 {
+                        Color::new(2.5, 1.0, 0.1)
+                    } else {
+                        Color::new(x as f64 * 0.3, y as f64 * 10.0, 0.01)
+                    };
+                    canvas.write_pixel(x, y, color);
+                }
+            }
+
+            let mut hdr = vec![];
+            write_hdr(&canvas, &mut hdr).unwrap();
+            let read = read_hdr(&hdr[..]).unwrap();
+
+            assert_eq!((read.width, read.height), (width, 4));
+            for y in 0..4 {
+                for x in 0..width {
+                    assert_close(read.pixel_at(x, y), canvas.pixel_at(x, y));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_write_hdr_header() {
+        let mut hdr = vec![];
+        write_hdr(&Canvas::init(2, 1), &mut hdr).unwrap();
+
+        assert_eq!(
+            hdr,
+            b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 1 +X 2\n\0\0\0\0\0\0\0\0".to_vec()
+        );
+    }
+
+    #[test]
+    fn test_write_hdr_rejects_empty_canvas() {
+        let mut hdr = vec![];
+
+        let error = write_hdr(&Canvas::init(3, 0), &mut hdr).unwrap_err();
+
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert!(hdr.is_empty());
+    }
+
+    #[test]
+    fn test_parse_header_with_comments_and_exposure() {
+        let mut hdr = b"#?RGBE\n# made by hand\nEXPOSURE=2.0\n\n-Y 1 +X 1\n".to_vec();
+        hdr.extend_from_slice(&[128, 64, 0, 129]);
+
+        let canvas = parse_hdr(&hdr).unwrap();
+
+        assert_close(canvas.pixel_at(0, 0), &Color::new(1.0, 0.5, 0.0));
+    }
+
+    #[test]
+    fn test_parse_hdr_errors() {
+        assert_invalid(b"P6\n", "missing #? signature");
+        assert_invalid(
+            b"#?RADIANCE\nFORMAT=32-bit_rle_xyze\n\n",
+            "unsupported format 32-bit_rle_xyze",
+        );
+        assert_invalid(b"#?RADIANCE\n", "unexpected end of file in header");
+        assert_invalid(
+            b"#?RADIANCE\n\n+Y 1 +X 1\n\0\0\0\0",
+            "unsupported resolution `+Y 1 +X 1`, expected -Y height +X width",
+        );
+        assert_invalid(
+            b"#?RADIANCE\n\n-Y 1 +X 2\n\0\0\0\0",
+            "unexpected end of pixel data",
+        );
+        let mut overflow = b"#?RADIANCE\n\n-Y 1 +X 8\n".to_vec();
+        overflow.extend_from_slice(&[2, 2, 0, 8, 128 + 9, 0]);
+        assert_invalid(&overflow, "run overflows the scanline");
+    }
+}
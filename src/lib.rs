@@ -1,10 +1,31 @@
+pub mod bounds;
+pub mod bvh;
+pub mod camera;
 pub mod canvas;
 pub mod color;
+pub mod cone;
+pub mod csg;
+pub mod cube;
+pub mod cylinder;
+pub mod deflate;
+pub mod group;
+pub mod hdr;
 pub mod light;
 pub mod material;
 pub mod matrix;
+pub mod obj;
+pub mod pattern;
+pub mod pfm;
+pub mod plane;
+pub mod png;
 pub mod ppm;
 pub mod ray;
+pub mod sampling;
+pub mod scene;
+pub mod shape;
+pub mod sphere;
+pub mod tonemap;
+pub mod triangle;
 pub mod tuple;
 pub mod world;
-pub mod camera;
+pub mod yaml;
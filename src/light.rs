@@ -1,5 +1,6 @@
 use crate::color::*;
 use crate::material::*;
+use crate::shape::Shape;
 use crate::tuple::*;
 
 #[derive(Debug)]
@@ -10,12 +11,18 @@ pub struct Light {
 
 pub fn lightning(
     material: &Material,
+    object: &dyn Shape,
     light: &Light,
     position: Tuple,
     eyev: Tuple,
     normalv: Tuple,
+    in_shadow: bool,
 ) -> Color {
-    let effective_color = &(&material.color * &light.intensity);
+    let color = match &material.pattern {
+        Some(pattern) => pattern.pattern_at_shape(object, position),
+        None => material.color,
+    };
+    let effective_color = &(color * light.intensity);
 
     let lightv = (light.position - position).norm();
 
@@ -26,7 +33,8 @@ pub fn lightning(
     let mut diffuse = Color::new(0.0, 0.0, 0.0);
     let mut specular = Color::new(0.0, 0.0, 0.0);
 
-    if light_dot_normal < 0.0 {
+    // points in shadow, or facing away from the light, only get ambient light
+    if in_shadow || light_dot_normal < 0.0 {
         return ambient + diffuse + specular;
     }
 
@@ -40,15 +48,18 @@ pub fn lightning(
     }
 
     let factor = reflect_dot_eye.powf(material.shininess);
-    specular = &light.intensity * material.specular * factor;
-    return ambient + diffuse + specular;
+    specular = light.intensity * material.specular * factor;
+    ambient + diffuse + specular
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::pattern::Stripe;
     use crate::point;
+    use crate::sphere::Sphere;
     use crate::vector;
+    use std::sync::Arc;
 
     #[test]
     fn test_light_has_position_and_intensity() {
@@ -56,8 +67,8 @@ mod tests {
         let position = point!(0.0, 0.0, 0.0);
 
         let light = Light {
-            intensity: intensity.clone(),
-            position: position,
+            intensity,
+            position,
         };
 
         assert_eq!(light.intensity, intensity);
@@ -77,7 +88,15 @@ mod tests {
             intensity: Color::new(1.0, 1.0, 1.0),
         };
 
-        let result = lightning(&material, &light, position, eyev, normalv);
+        let result = lightning(
+            &material,
+            &Sphere::init(),
+            &light,
+            position,
+            eyev,
+            normalv,
+            false,
+        );
 
         assert_eq!(result, Color::new(1.9, 1.9, 1.9));
     }
@@ -87,7 +106,7 @@ mod tests {
         let material = Material::default();
         let position = point!(0.0, 0.0, 0.0);
 
-        let eyev = vector!(0.0, 2.0_f64.sqrt() / 2.0, -2.0_f64.sqrt() / 2.0);
+        let eyev = vector!(0.0, 2.0_f64.sqrt() / 2.0, -(2.0_f64.sqrt()) / 2.0);
         let normalv = vector!(0.0, 0.0, -1.0);
 
         let light = Light {
@@ -95,8 +114,86 @@ mod tests {
             intensity: Color::new(1.0, 1.0, 1.0),
         };
 
-        let result = lightning(&material, &light, position, eyev, normalv);
+        let result = lightning(
+            &material,
+            &Sphere::init(),
+            &light,
+            position,
+            eyev,
+            normalv,
+            false,
+        );
 
         assert_eq!(result, Color::new(1.0, 1.0, 1.0));
     }
+
+    #[test]
+    fn test_lightning_surface_in_shadow() {
+        let material = Material::default();
+        let position = point!(0.0, 0.0, 0.0);
+
+        let eyev = vector!(0.0, 0.0, -1.0);
+        let normalv = vector!(0.0, 0.0, -1.0);
+
+        let light = Light {
+            position: point!(0.0, 0.0, -10.0),
+            intensity: Color::new(1.0, 1.0, 1.0),
+        };
+
+        let result = lightning(
+            &material,
+            &Sphere::init(),
+            &light,
+            position,
+            eyev,
+            normalv,
+            true,
+        );
+
+        assert_eq!(result, Color::new(0.1, 0.1, 0.1));
+    }
+
+    #[test]
+    fn test_lightning_with_pattern() {
+        let material = Material {
+            pattern: Some(Arc::new(Stripe::new(
+                Color::new(1.0, 1.0, 1.0),
+                Color::new(0.0, 0.0, 0.0),
+            ))),
+            ambient: 1.0,
+            diffuse: 0.0,
+            specular: 0.0,
+            ..Material::default()
+        };
+        let object = Sphere::init();
+
+        let eyev = vector!(0.0, 0.0, -1.0);
+        let normalv = vector!(0.0, 0.0, -1.0);
+        let light = Light {
+            position: point!(0.0, 0.0, -10.0),
+            intensity: Color::new(1.0, 1.0, 1.0),
+        };
+
+        let c1 = lightning(
+            &material,
+            &object,
+            &light,
+            point!(0.9, 0.0, 0.0),
+            eyev,
+            normalv,
+            false,
+        );
+        let c2 = lightning(
+            &material,
+            &object,
+            &light,
+            point!(1.1, 0.0, 0.0),
+            eyev,
+            normalv,
+            false,
+        );
+
+        assert_eq!(c1, Color::new(1.0, 1.0, 1.0));
+        assert_eq!(c2, Color::new(0.0, 0.0, 0.0));
+    }
 }
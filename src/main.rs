@@ -1,84 +1,618 @@
-use std::f64::consts::{FRAC_PI_3, PI};
+use std::env;
+use std::fs;
+use std::io::{self, BufWriter, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process;
 
-use raytracer::tuple::Tuple;
-use raytracer::vector;
-use raytracer::*;
-// use std::f64::consts::PI;
+use raytracer::camera::{self, CancellationToken, Progress};
+use raytracer::canvas::Canvas;
+use raytracer::hdr;
+use raytracer::pfm;
+use raytracer::png::{self, BitDepth, PngOptions};
+use raytracer::ppm;
+use raytracer::sampling::{Filter, SamplePattern};
+use raytracer::scene::{self, Scene};
+use raytracer::tonemap::{Aces, Clamp, Reinhard, ToneMap, ToneMapping};
 
-fn main() {
-    let mut floor = ray::Sphere::init();
-    floor.transform = matrix::Matrix::identity(4).scaling(10.0, 0.01, 10.0);
-    let mut floor_material = material::Material::default();
-    floor_material.color = color::Color::new(1.0, 0.9, 0.9);
-    floor_material.specular = 0.0;
-    floor.material = floor_material;
-
-    let mut left_wall = ray::Sphere::init();
-    left_wall.transform = matrix::Matrix::identity(4)
-        .scaling(10.0, 0.01, 10.0)
-        .rotation_x(PI / 2.0)
-        .rotation_y(-PI / 4.0)
-        .translation(0.0, 0.0, 5.0);
-    left_wall.material = floor_material;
-
-    let mut right_wall = ray::Sphere::init();
-    right_wall.transform = matrix::Matrix::identity(4)
-        .scaling(10.0, 0.01, 10.0)
-        .rotation_x(PI / 2.0)
-        .rotation_y(PI / 4.0)
-        .translation(0.0, 0.0, 5.0);
-    right_wall.material = floor_material;
-
-    let mut middle = ray::Sphere::init();
-    middle.transform = matrix::Matrix::identity(4).translation(-0.5, 1.0, 0.5);
-    let mut middle_material = material::Material::default();
-    middle_material.color = color::Color::new(0.1, 1.0, 0.5);
-    middle_material.diffuse = 0.7;
-    middle_material.specular = 0.3;
-    middle.material = middle_material;
-
-    let mut right = ray::Sphere::init();
-    right.transform = matrix::Matrix::identity(4)
-        .scaling(0.5, 0.5, 0.5)
-        .translation(1.5, 0.5, -0.5);
-    let mut right_material = material::Material::default();
-    right_material.color = color::Color::new(0.5, 1.0, 0.1);
-    right_material.diffuse = 0.7;
-    right_material.specular = 0.3;
-    right.material = right_material;
-
-    let mut left = ray::Sphere::init();
-    left.transform = matrix::Matrix::identity(4)
-        .scaling(0.33, 0.33, 0.33)
-        .translation(-1.5, 0.33, -0.75);
-    let mut left_material = material::Material::default();
-    left_material.color = color::Color::new(1.0, 0.8, 0.1);
-    left_material.diffuse = 0.7;
-    left_material.specular = 0.3;
-    left.material = left_material;
-
-    let mut world = world::World::new();
-    world.lights.push(light::Light {
-        intensity: color::Color {
-            red: 1.0,
-            green: 1.0,
-            blue: 1.0,
+const USAGE: &str = "Usage: raytracer <scene.yml> [options]
+
+Renders a scene written in the YAML format of the book.
+
+Options:
+  -o, --output <path>        where to write the image, stdout when missing
+  -f, --format <format>      image format: ppm (plain P3), p6 (binary PPM),
+                             png, png16 (16 bits per channel), or the
+                             unclamped pfm and hdr (Radiance)
+                             (default: from the output extension, ppm
+                             when there is none)
+  -m, --tone-map <operator>  clamp, reinhard or aces (default: clamp)
+  -e, --exposure <stops>     scales the image by 2^stops before tone mapping
+                             (default: 0)
+  -g, --encoding <encoding>  linear or srgb (default: linear)
+                             (tone mapping and encoding do not apply to the
+                             pfm and hdr formats)
+  -r, --resolution <WxH>     override the size of the camera, like 640x480
+  -s, --samples <count>      samples per pixel (default: 1)
+      --sampler <pattern>    where the samples go: regular, jittered or
+                             random (default: regular)
+      --filter <filter>      how samples are weighted: box, tent, gaussian
+                             or mitchell (default: box)
+  -t, --threads <count>      render threads (default: all cores)
+  -d, --max-depth <depth>    maximum recursion depth for reflection and
+                             refraction (default: 5)
+  -h, --help                 show this message";
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Format {
+    Ppm,
+    P6,
+    Png,
+    Png16,
+    Pfm,
+    Hdr,
+}
+
+impl Format {
+    fn from_name(name: &str) -> Option<Format> {
+        match name.to_ascii_lowercase().as_str() {
+            "ppm" | "p3" => Some(Format::Ppm),
+            "p6" => Some(Format::P6),
+            "png" => Some(Format::Png),
+            "png16" => Some(Format::Png16),
+            "pfm" => Some(Format::Pfm),
+            "hdr" => Some(Format::Hdr),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Operator {
+    Clamp,
+    Reinhard,
+    Aces,
+}
+
+impl Operator {
+    fn from_name(name: &str) -> Option<Operator> {
+        match name.to_ascii_lowercase().as_str() {
+            "clamp" => Some(Operator::Clamp),
+            "reinhard" => Some(Operator::Reinhard),
+            "aces" => Some(Operator::Aces),
+            _ => None,
+        }
+    }
+
+    fn tone_map(self) -> Box<dyn ToneMap> {
+        match self {
+            Operator::Clamp => Box::new(Clamp),
+            Operator::Reinhard => Box::new(Reinhard::new()),
+            Operator::Aces => Box::new(Aces),
+        }
+    }
+}
+
+fn sample_pattern_from_name(name: &str) -> Option<SamplePattern> {
+    match name.to_ascii_lowercase().as_str() {
+        "regular" => Some(SamplePattern::Regular),
+        "jittered" => Some(SamplePattern::Jittered),
+        "random" => Some(SamplePattern::Random),
+        _ => None,
+    }
+}
+
+fn filter_from_name(name: &str) -> Option<Filter> {
+    match name.to_ascii_lowercase().as_str() {
+        "box" => Some(Filter::Box),
+        "tent" => Some(Filter::Tent),
+        "gaussian" => Some(Filter::Gaussian),
+        "mitchell" => Some(Filter::Mitchell),
+        _ => None,
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Options {
+    scene: PathBuf,
+    output: Option<PathBuf>,
+    format: Format,
+    operator: Operator,
+    exposure: f64,
+    srgb: bool,
+    resolution: Option<(usize, usize)>,
+    samples: usize,
+    sample_pattern: SamplePattern,
+    filter: Filter,
+    threads: Option<usize>,
+    max_depth: Option<usize>,
+}
+
+fn parse_count(option: &str, value: &str) -> Result<usize, String> {
+    match value.parse() {
+        Ok(count) if count > 0 => Ok(count),
+        _ => Err(format!(
+            "{} expects a positive integer, got `{}`",
+            option, value
+        )),
+    }
+}
+
+fn parse_resolution(value: &str) -> Result<(usize, usize), String> {
+    let error = || format!("--resolution expects WIDTHxHEIGHT, got `{}`", value);
+    let (width, height) = value.split_once('x').ok_or_else(error)?;
+    match (width.parse(), height.parse()) {
+        (Ok(width), Ok(height)) if width > 0 && height > 0 => Ok((width, height)),
+        _ => Err(error()),
+    }
+}
+
+// Ok(None) when help was asked for
+fn parse_args(args: &[String]) -> Result<Option<Options>, String> {
+    let mut scene = None;
+    let mut output: Option<PathBuf> = None;
+    let mut format = None;
+    let mut operator = Operator::Clamp;
+    let mut exposure = 0.0;
+    let mut srgb = false;
+    let mut resolution = None;
+    let mut samples = 1;
+    let mut sample_pattern = SamplePattern::Regular;
+    let mut filter = Filter::Box;
+    let mut threads = None;
+    let mut max_depth = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "-h" || arg == "--help" {
+            return Ok(None);
+        }
+        if !arg.starts_with('-') {
+            if scene.is_some() {
+                return Err(format!("unexpected argument `{}`", arg));
+            }
+            scene = Some(PathBuf::from(arg));
+            continue;
+        }
+
+        let value = args
+            .next()
+            .ok_or_else(|| format!("{} expects a value", arg))?;
+        match arg.as_str() {
+            "-o" | "--output" => output = Some(PathBuf::from(value)),
+            "-f" | "--format" => {
+                format = Some(
+                    Format::from_name(value)
+                        .ok_or_else(|| format!("unknown format `{}`", value))?,
+                )
+            }
+            "-m" | "--tone-map" => {
+                operator = Operator::from_name(value)
+                    .ok_or_else(|| format!("unknown tone map `{}`", value))?
+            }
+            "-e" | "--exposure" => {
+                exposure = match value.parse::<f64>() {
+                    Ok(stops) if stops.is_finite() => stops,
+                    _ => return Err(format!("{} expects a number, got `{}`", arg, value)),
+                }
+            }
+            "-g" | "--encoding" => {
+                srgb = match value.to_ascii_lowercase().as_str() {
+                    "linear" => false,
+                    "srgb" => true,
+                    _ => return Err(format!("unknown encoding `{}`", value)),
+                }
+            }
+            "-r" | "--resolution" => resolution = Some(parse_resolution(value)?),
+            "-s" | "--samples" => samples = parse_count(arg, value)?,
+            "--sampler" => {
+                sample_pattern = sample_pattern_from_name(value)
+                    .ok_or_else(|| format!("unknown sampler `{}`", value))?
+            }
+            "--filter" => {
+                filter =
+                    filter_from_name(value).ok_or_else(|| format!("unknown filter `{}`", value))?
+            }
+            "-t" | "--threads" => threads = Some(parse_count(arg, value)?),
+            "-d" | "--max-depth" => {
+                max_depth = Some(
+                    value
+                        .parse()
+                        .map_err(|_| format!("{} expects an integer, got `{}`", arg, value))?,
+                )
+            }
+            _ => return Err(format!("unknown option `{}`", arg)),
+        }
+    }
+
+    let scene = scene.ok_or("missing scene file")?;
+    // without an explicit format, go by the extension of the output, and
+    // refuse to write PPM data into a file named like something else
+    let format = match format {
+        Some(format) => format,
+        None => match output.as_deref().and_then(Path::extension) {
+            Some(extension) => {
+                let extension = extension.to_string_lossy();
+                Format::from_name(&extension).ok_or_else(|| {
+                    format!(
+                        "unknown format `{}` for the output, use --format to choose one",
+                        extension
+                    )
+                })?
+            }
+            None => Format::Ppm,
         },
-        position: point!(-10, 10, -10),
-    });
-    world.objects.push(floor);
-    world.objects.push(left_wall);
-    world.objects.push(right_wall);
-    world.objects.push(middle);
-    world.objects.push(right);
-    world.objects.push(left);
-
-    let mut camera = camera::Camera::new(500, 250, FRAC_PI_3);
-    camera.transform =
-        matrix::view_transform(point!(0, 1.5, -5), point!(0, 1, 0), vector!(0, 1, 0));
-
-    let canvas = camera::render(&camera, &world);
-
-    let string = ppm::canvas_to_ppm(canvas);
-    println!("{}", string);
+    };
+
+    Ok(Some(Options {
+        scene,
+        output,
+        format,
+        operator,
+        exposure,
+        srgb,
+        resolution,
+        samples,
+        sample_pattern,
+        filter,
+        threads,
+        max_depth,
+    }))
+}
+
+fn format_duration(seconds: f64) -> String {
+    if seconds < 60.0 {
+        format!("{:.1}s", seconds)
+    } else {
+        format!("{}m{:02}s", (seconds / 60.0) as u64, seconds as u64 % 60)
+    }
+}
+
+fn report_progress(progress: Progress) {
+    let eta = match progress.eta() {
+        Some(eta) => format_duration(eta.as_secs_f64()),
+        None => "?".to_string(),
+    };
+    eprint!(
+        "\rrendering {:5.1}% ({}/{} tiles), {} elapsed, {} left   ",
+        progress.fraction() * 100.0,
+        progress.tiles_done,
+        progress.tiles_total,
+        format_duration(progress.elapsed.as_secs_f64()),
+        eta
+    );
+}
+
+// the float formats keep the radiance as it is, so they ignore tone_mapping
+fn write_image<W: Write>(
+    canvas: &Canvas,
+    format: Format,
+    tone_mapping: &ToneMapping,
+    writer: W,
+) -> io::Result<()> {
+    let writer = BufWriter::new(writer);
+    match format {
+        Format::Ppm => ppm::write_ppm_with(canvas, tone_mapping, writer),
+        Format::P6 => ppm::write_ppm_binary_with(canvas, tone_mapping, writer),
+        Format::Png => png::write_png_with(canvas, PngOptions::default(), tone_mapping, writer),
+        Format::Png16 => {
+            let options = PngOptions {
+                bit_depth: BitDepth::Sixteen,
+                ..PngOptions::default()
+            };
+            png::write_png_with(canvas, options, tone_mapping, writer)
+        }
+        Format::Pfm => pfm::write_pfm(canvas, writer),
+        Format::Hdr => hdr::write_hdr(canvas, writer),
+    }
+}
+
+// removes the file again when the image cannot be written, rather than
+// leaving a truncated one behind
+fn write_file(
+    path: &Path,
+    canvas: &Canvas,
+    format: Format,
+    tone_mapping: &ToneMapping,
+) -> Result<(), String> {
+    let error = |error: io::Error| format!("{}: {}", path.display(), error);
+    let file = fs::File::create(path).map_err(error)?;
+    write_image(canvas, format, tone_mapping, file).map_err(|write_error| {
+        let _ = fs::remove_file(path);
+        error(write_error)
+    })
+}
+
+fn run(options: &Options) -> Result<(), String> {
+    let Scene {
+        mut world,
+        mut camera,
+    } = scene::parse_scene_file(&options.scene)
+        .map_err(|error| format!("{}: {}", options.scene.display(), error))?;
+
+    if let Some((width, height)) = options.resolution {
+        let transform = camera.transform().matrix();
+        let threads = camera.threads;
+        camera = camera::Camera::new(width, height, camera.field_of_view);
+        camera.set_transform(transform);
+        camera.threads = threads;
+    }
+    if let Some(threads) = options.threads {
+        camera.threads = threads;
+    }
+    if let Some(max_depth) = options.max_depth {
+        world.max_depth = max_depth;
+    }
+    camera.samples = options.samples;
+    camera.sample_pattern = options.sample_pattern;
+    camera.filter = options.filter;
+
+    let show_progress = io::stderr().is_terminal();
+    let rendered = camera::render_with_progress(
+        &camera,
+        &world,
+        |progress| {
+            if show_progress {
+                report_progress(progress)
+            }
+        },
+        &CancellationToken::new(),
+    );
+    if show_progress {
+        eprintln!();
+    }
+
+    let tone_mapping = ToneMapping {
+        exposure: options.exposure,
+        operator: options.operator.tone_map(),
+        srgb: options.srgb,
+    };
+    let canvas = &rendered.canvas;
+    match &options.output {
+        Some(path) => write_file(path, canvas, options.format, &tone_mapping)?,
+        None => write_image(canvas, options.format, &tone_mapping, io::stdout().lock())
+            .map_err(|error| error.to_string())?,
+    }
+
+    let seconds = rendered.elapsed.as_secs_f64();
+    let pixels = camera.hsize * camera.vsize;
+    eprintln!(
+        "{} objects, {} lights, {}x{} pixels, {} samples, {} threads, max depth {}",
+        world.objects().len(),
+        world.lights.len(),
+        camera.hsize,
+        camera.vsize,
+        camera.samples,
+        camera.threads,
+        world.max_depth
+    );
+    eprintln!(
+        "rendered in {} ({:.0} pixels/s)",
+        format_duration(seconds),
+        pixels as f64 / seconds.max(f64::EPSILON)
+    );
+
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let options = match parse_args(&args) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return;
+        }
+        Err(error) => {
+            eprintln!("error: {}\n\n{}", error, USAGE);
+            process::exit(2);
+        }
+    };
+
+    if let Err(error) = run(&options) {
+        eprintln!("error: {}", error);
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_parsing_defaults() {
+        let options = parse_args(&args("scene.yml")).unwrap().unwrap();
+
+        assert_eq!(
+            options,
+            Options {
+                scene: PathBuf::from("scene.yml"),
+                output: None,
+                format: Format::Ppm,
+                operator: Operator::Clamp,
+                exposure: 0.0,
+                srgb: false,
+                resolution: None,
+                samples: 1,
+                sample_pattern: SamplePattern::Regular,
+                filter: Filter::Box,
+                threads: None,
+                max_depth: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_parsing_every_option() {
+        let options = parse_args(&args(
+            "-o out.ppm scene.yml --resolution 640x480 -s 4 --threads 3 -d 2",
+        ))
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(options.output, Some(PathBuf::from("out.ppm")));
+        assert_eq!(options.resolution, Some((640, 480)));
+        assert_eq!(options.samples, 4);
+        assert_eq!(options.threads, Some(3));
+        assert_eq!(options.max_depth, Some(2));
+    }
+
+    #[test]
+    fn test_parsing_help() {
+        assert_eq!(parse_args(&args("scene.yml --help")), Ok(None));
+    }
+
+    #[test]
+    fn test_parsing_format() {
+        let format = |line| parse_args(&args(line)).unwrap().unwrap().format;
+
+        assert_eq!(format("scene.yml -f p6"), Format::P6);
+        assert_eq!(format("scene.yml -f P3"), Format::Ppm);
+        assert_eq!(format("scene.yml -o out.p6"), Format::P6);
+        assert_eq!(format("scene.yml -o out.png -f ppm"), Format::Ppm);
+        assert_eq!(format("scene.yml -o out.png"), Format::Png);
+        assert_eq!(format("scene.yml -f png16"), Format::Png16);
+        assert_eq!(format("scene.yml -o out.pfm"), Format::Pfm);
+        assert_eq!(format("scene.yml -o out.HDR"), Format::Hdr);
+        assert_eq!(format("scene.yml -o out"), Format::Ppm);
+        assert_eq!(format("scene.yml -o out.jpg -f png"), Format::Png);
+    }
+
+    #[test]
+    fn test_parsing_tone_mapping() {
+        let options = parse_args(&args("scene.yml -m ACES --exposure -1.5 -g srgb"))
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(options.operator, Operator::Aces);
+        assert_eq!(options.exposure, -1.5);
+        assert!(options.srgb);
+    }
+
+    #[test]
+    fn test_parsing_sampling() {
+        let options = parse_args(&args(
+            "scene.yml -s 16 --sampler Jittered --filter mitchell",
+        ))
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(options.samples, 16);
+        assert_eq!(options.sample_pattern, SamplePattern::Jittered);
+        assert_eq!(options.filter, Filter::Mitchell);
+    }
+
+    #[test]
+    fn test_parsing_errors() {
+        assert!(parse_args(&args("")).is_err());
+        assert!(parse_args(&args("a.yml b.yml")).is_err());
+        assert!(parse_args(&args("scene.yml --threads 0")).is_err());
+        assert!(parse_args(&args("scene.yml --resolution 640")).is_err());
+        assert!(parse_args(&args("scene.yml --format gif")).is_err());
+        assert!(parse_args(&args("scene.yml -o out.jpg")).is_err());
+        assert!(parse_args(&args("scene.yml --bogus 1")).is_err());
+        assert!(parse_args(&args("scene.yml --tone-map filmic")).is_err());
+        assert!(parse_args(&args("scene.yml --exposure bright")).is_err());
+        assert!(parse_args(&args("scene.yml --encoding gamma")).is_err());
+        assert!(parse_args(&args("scene.yml --sampler sobol")).is_err());
+        assert!(parse_args(&args("scene.yml --filter lanczos")).is_err());
+        assert!(parse_args(&args("scene.yml --output")).is_err());
+    }
+
+    #[test]
+    fn test_rendering_scene_to_file() {
+        let output = env::temp_dir().join(format!("raytracer-cli-{}.ppm", process::id()));
+        let options = Options {
+            scene: PathBuf::from("scenes/demo.yml"),
+            output: Some(output.clone()),
+            format: Format::Ppm,
+            operator: Operator::Clamp,
+            exposure: 0.0,
+            srgb: false,
+            resolution: Some((10, 5)),
+            samples: 1,
+            sample_pattern: SamplePattern::Regular,
+            filter: Filter::Box,
+            threads: Some(2),
+            max_depth: Some(1),
+        };
+
+        run(&options).unwrap();
+        let image = fs::read_to_string(&output).unwrap();
+        fs::remove_file(&output).unwrap();
+
+        assert!(image.starts_with("P3\n10 5\n255\n"));
+    }
+
+    #[test]
+    fn test_failed_write_removes_the_file() {
+        let output = env::temp_dir().join(format!("raytracer-cli-empty-{}.png", process::id()));
+
+        let result = write_file(
+            &output,
+            &Canvas::init(0, 10),
+            Format::Png,
+            &ToneMapping::default(),
+        );
+
+        assert!(result.unwrap_err().ends_with("PNG images cannot be empty"));
+        assert!(!output.exists());
+    }
+
+    #[test]
+    fn test_rendering_binary_ppm() {
+        let output = env::temp_dir().join(format!("raytracer-cli-p6-{}.ppm", process::id()));
+        let options = Options {
+            scene: PathBuf::from("scenes/demo.yml"),
+            output: Some(output.clone()),
+            format: Format::P6,
+            operator: Operator::Clamp,
+            exposure: 0.0,
+            srgb: false,
+            resolution: Some((10, 5)),
+            samples: 1,
+            sample_pattern: SamplePattern::Regular,
+            filter: Filter::Box,
+            threads: Some(2),
+            max_depth: Some(1),
+        };
+
+        run(&options).unwrap();
+        let image = fs::read(&output).unwrap();
+        fs::remove_file(&output).unwrap();
+
+        assert!(image.starts_with(b"P6\n10 5\n255\n"));
+        assert_eq!(image.len(), b"P6\n10 5\n255\n".len() + 10 * 5 * 3);
+    }
+
+    #[test]
+    fn test_rendering_png() {
+        let output = env::temp_dir().join(format!("raytracer-cli-{}.png", process::id()));
+        let options = parse_args(&[
+            "scenes/demo.yml".to_string(),
+            "-o".to_string(),
+            output.to_string_lossy().into_owned(),
+            "-r".to_string(),
+            "10x5".to_string(),
+        ])
+        .unwrap()
+        .unwrap();
+
+        run(&options).unwrap();
+        let image = png::read_png(fs::File::open(&output).unwrap()).unwrap();
+        fs::remove_file(&output).unwrap();
+
+        assert_eq!((image.width, image.height), (10, 5));
+    }
+
+    #[test]
+    fn test_missing_scene_is_an_error() {
+        let options = parse_args(&args("no-such-scene.yml")).unwrap().unwrap();
+
+        assert!(run(&options).is_err());
+    }
 }
@@ -1,22 +1,33 @@
 use crate::color::*;
+use crate::pattern::Pattern;
+use std::sync::Arc;
 
-#[derive(Debug, PartialEq, Copy, Clone)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct Material {
     pub color: Color,
+    // when set, the color comes from the pattern instead of `color`
+    pub pattern: Option<Arc<dyn Pattern>>,
     pub ambient: f64,
     pub diffuse: f64,
     pub specular: f64,
     pub shininess: f64,
+    pub reflective: f64,
+    pub transparency: f64,
+    pub refractive_index: f64,
 }
 
-impl Material {
-    pub fn default() -> Material {
+impl Default for Material {
+    fn default() -> Material {
         Material {
             ambient: 0.1,
             diffuse: 0.9,
             specular: 0.9,
             shininess: 200.0,
+            reflective: 0.0,
+            transparency: 0.0,
+            refractive_index: 1.0,
             color: Color::new(1.0, 1.0, 1.0),
+            pattern: None,
         }
     }
 }
@@ -30,12 +41,17 @@ mod tests {
     fn test_default_material() {
         let m = Material {
             color: Color::new(1.0, 1.0, 1.0),
+            pattern: None,
             ambient: 0.1,
             diffuse: 0.9,
             specular: 0.9,
             shininess: 200.0,
+            reflective: 0.0,
+            transparency: 0.0,
+            refractive_index: 1.0,
         };
 
         assert_eq!(m.ambient, 0.1);
+        assert_eq!(m, Material::default());
     }
 }
@@ -1,7 +1,7 @@
 use crate::tuple::Tuple;
 use std::ops::Mul;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Matrix {
     pub matrix: Vec<Vec<f64>>,
 }
@@ -91,7 +91,7 @@ impl Matrix {
         } else {
             let first_line = &self.matrix[0];
             first_line
-                .into_iter()
+                .iter()
                 .enumerate()
                 .fold(0.0, |acc, (y, item)| acc + (item * self.cofactor(0, y)))
         }
@@ -106,7 +106,7 @@ impl Matrix {
                 let current_line = &self.matrix[x];
                 submatrix.push(
                     current_line
-                        .into_iter()
+                        .iter()
                         .enumerate()
                         .filter_map(
                             |(index, item)| if index != column { Some(*item) } else { None },
@@ -142,7 +142,7 @@ impl Matrix {
             let current_line = &self.matrix[x];
             inversed.push(
                 current_line
-                    .into_iter()
+                    .iter()
                     .enumerate()
                     .map(|(y, _item)| self.cofactor(x, y) / determinant)
                     .collect::<Vec<f64>>(),
@@ -167,20 +167,6 @@ impl Matrix {
     }
 }
 
-pub fn view_transform(from: Tuple, to: Tuple, up: Tuple) -> Matrix {
-    let forward = (to - from).norm();
-    let upn = up.norm();
-    let left = forward.cross_product(upn);
-    let true_up = left.cross_product(forward);
-
-    let orientation = matrix![ left.x, left.y, left.z, 0.0;
-                               true_up.x, true_up.y, true_up.z, 0.0;
-                               -forward.x, -forward.y, -forward.z, 0.0;
-                               0.0, 0.0, 0.0, 1.0];
-
-    return orientation * Matrix::identity(4).translation(-from.x, -from.y, -from.z);
-}
-
 pub const EPSILON: f64 = 0.001;
 
 impl PartialEq for Matrix {
@@ -239,7 +225,7 @@ impl Mul<Tuple> for Matrix {
     }
 }
 
-impl<'a> Mul<&Matrix> for &Matrix {
+impl Mul<&Matrix> for &Matrix {
     type Output = Matrix;
 
     fn mul(self, other: &Matrix) -> Matrix {
@@ -248,15 +234,15 @@ impl<'a> Mul<&Matrix> for &Matrix {
 
         let mut matrix = vec![vec![0.0; columns]; lines];
 
-        for x in 0..lines {
-            for y in 0..columns {
+        for (x, line) in matrix.iter_mut().enumerate() {
+            for (y, item) in line.iter_mut().enumerate() {
                 let mut sum = 0.0;
                 for pos in 0..lines {
-                    let item = self.matrix[x][pos];
+                    let item_a = self.matrix[x][pos];
                     let item_b = other.matrix[pos][y];
-                    sum += item * item_b;
+                    sum += item_a * item_b;
                 }
-                matrix[x][y] = sum
+                *item = sum
             }
         }
 
@@ -273,15 +259,15 @@ impl Mul<&Matrix> for Matrix {
 
         let mut matrix = vec![vec![0.0; columns]; lines];
 
-        for x in 0..lines {
-            for y in 0..columns {
+        for (x, line) in matrix.iter_mut().enumerate() {
+            for (y, item) in line.iter_mut().enumerate() {
                 let mut sum = 0.0;
                 for pos in 0..lines {
-                    let item = self.matrix[x][pos];
+                    let item_a = self.matrix[x][pos];
                     let item_b = other.matrix[pos][y];
-                    sum += item * item_b;
+                    sum += item_a * item_b;
                 }
-                matrix[x][y] = sum
+                *item = sum
             }
         }
 
@@ -298,15 +284,15 @@ impl Mul for Matrix {
 
         let mut matrix = vec![vec![0.0; columns]; lines];
 
-        for x in 0..lines {
-            for y in 0..columns {
+        for (x, line) in matrix.iter_mut().enumerate() {
+            for (y, item) in line.iter_mut().enumerate() {
                 let mut sum = 0.0;
                 for pos in 0..lines {
-                    let item = self.matrix[x][pos];
+                    let item_a = self.matrix[x][pos];
                     let item_b = other.matrix[pos][y];
-                    sum += item * item_b;
+                    sum += item_a * item_b;
                 }
-                matrix[x][y] = sum
+                *item = sum
             }
         }
 
@@ -314,12 +300,313 @@ impl Mul for Matrix {
     }
 }
 
+// A 4x4 matrix stored on the stack, used for every transform in the render
+// path. It is Copy and has a closed-form inverse, so it never allocates.
+// Matrix is still there for matrices of other sizes.
+#[derive(Debug, Clone, Copy)]
+pub struct Matrix4 {
+    pub matrix: [[f64; 4]; 4],
+}
+
+#[macro_export]
+macro_rules! matrix4 {
+    ($( $( $x: expr ),*);*) => {
+        {
+            Matrix4::init([ $( [ $($x),* ] ),* ])
+        }
+    }
+}
+
+impl Default for Matrix4 {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl Matrix4 {
+    pub fn init(matrix: [[f64; 4]; 4]) -> Self {
+        Self { matrix }
+    }
+
+    pub fn identity() -> Self {
+        matrix4![1.0, 0.0, 0.0, 0.0;
+                 0.0, 1.0, 0.0, 0.0;
+                 0.0, 0.0, 1.0, 0.0;
+                 0.0, 0.0, 0.0, 1.0]
+    }
+
+    pub fn at(&self, x: usize, y: usize) -> f64 {
+        self.matrix[x][y]
+    }
+
+    pub fn translation(&self, x: f64, y: f64, z: f64) -> Self {
+        let transform = matrix4![ 1.0, 0.0, 0.0, x;
+                                  0.0, 1.0, 0.0, y;
+                                  0.0, 0.0, 1.0, z;
+                                  0.0, 0.0, 0.0, 1.0];
+        transform * *self
+    }
+
+    pub fn rotation_x(&self, x: f64) -> Self {
+        let transform = matrix4![ 1.0, 0.0, 0.0, 0.0;
+                                  0.0, x.cos(), -x.sin(), 0.0;
+                                  0.0, x.sin(), x.cos(), 0.0;
+                                  0.0, 0.0, 0.0, 1.0];
+        transform * *self
+    }
+
+    pub fn rotation_y(&self, x: f64) -> Self {
+        let transform = matrix4![ x.cos(), 0.0, x.sin(), 0.0;
+                                  0.0, 1.0, 0.0, 0.0;
+                                  -x.sin(), 0.0, x.cos(), 0.0;
+                                  0.0, 0.0, 0.0, 1.0];
+        transform * *self
+    }
+
+    pub fn rotation_z(&self, x: f64) -> Self {
+        let transform = matrix4![ x.cos(), -x.sin(), 0.0, 0.0;
+                                  x.sin(), x.cos(), 0.0, 0.0;
+                                  0.0, 0.0, 1.0, 0.0;
+                                  0.0, 0.0, 0.0, 1.0];
+        transform * *self
+    }
+
+    pub fn scaling(&self, x: f64, y: f64, z: f64) -> Self {
+        let transform = matrix4![   x, 0.0, 0.0, 0.0;
+                                  0.0,   y, 0.0, 0.0;
+                                  0.0, 0.0,   z, 0.0;
+                                  0.0, 0.0, 0.0, 1.0];
+        transform * *self
+    }
+
+    pub fn skew(&self, x_y: f64, x_z: f64, y_x: f64, y_z: f64, z_x: f64, z_y: f64) -> Self {
+        let transform = matrix4![ 1.0, x_y, x_z, 0.0;
+                                  y_x, 1.0, y_z, 0.0;
+                                  z_x, z_y, 1.0, 0.0;
+                                  0.0, 0.0, 0.0, 1.0];
+        transform * *self
+    }
+
+    pub fn transpose(&self) -> Self {
+        let mut matrix = [[0.0; 4]; 4];
+        for (x, line) in matrix.iter_mut().enumerate() {
+            for (y, item) in line.iter_mut().enumerate() {
+                *item = self.matrix[y][x];
+            }
+        }
+
+        Self { matrix }
+    }
+
+    // The 2x2 determinants of the top two rows (s) and the bottom two rows
+    // (c), which the determinant and the inverse are both built from
+    fn sub_determinants(&self) -> ([f64; 6], [f64; 6]) {
+        let m = &self.matrix;
+        let s = [
+            m[0][0] * m[1][1] - m[1][0] * m[0][1],
+            m[0][0] * m[1][2] - m[1][0] * m[0][2],
+            m[0][0] * m[1][3] - m[1][0] * m[0][3],
+            m[0][1] * m[1][2] - m[1][1] * m[0][2],
+            m[0][1] * m[1][3] - m[1][1] * m[0][3],
+            m[0][2] * m[1][3] - m[1][2] * m[0][3],
+        ];
+        let c = [
+            m[2][0] * m[3][1] - m[3][0] * m[2][1],
+            m[2][0] * m[3][2] - m[3][0] * m[2][2],
+            m[2][0] * m[3][3] - m[3][0] * m[2][3],
+            m[2][1] * m[3][2] - m[3][1] * m[2][2],
+            m[2][1] * m[3][3] - m[3][1] * m[2][3],
+            m[2][2] * m[3][3] - m[3][2] * m[2][3],
+        ];
+        (s, c)
+    }
+
+    pub fn determinant(&self) -> f64 {
+        let (s, c) = self.sub_determinants();
+        s[0] * c[5] - s[1] * c[4] + s[2] * c[3] + s[3] * c[2] - s[4] * c[1] + s[5] * c[0]
+    }
+
+    pub fn is_invertible(&self) -> bool {
+        self.determinant() != 0.0
+    }
+
+    // Like Matrix::inverse, a matrix that can't be inverted gives infinities
+    pub fn inverse(&self) -> Self {
+        let m = &self.matrix;
+        let (s, c) = self.sub_determinants();
+        let determinant =
+            s[0] * c[5] - s[1] * c[4] + s[2] * c[3] + s[3] * c[2] - s[4] * c[1] + s[5] * c[0];
+        let inv = 1.0 / determinant;
+
+        let matrix = [
+            [
+                (m[1][1] * c[5] - m[1][2] * c[4] + m[1][3] * c[3]) * inv,
+                (-m[0][1] * c[5] + m[0][2] * c[4] - m[0][3] * c[3]) * inv,
+                (m[3][1] * s[5] - m[3][2] * s[4] + m[3][3] * s[3]) * inv,
+                (-m[2][1] * s[5] + m[2][2] * s[4] - m[2][3] * s[3]) * inv,
+            ],
+            [
+                (-m[1][0] * c[5] + m[1][2] * c[2] - m[1][3] * c[1]) * inv,
+                (m[0][0] * c[5] - m[0][2] * c[2] + m[0][3] * c[1]) * inv,
+                (-m[3][0] * s[5] + m[3][2] * s[2] - m[3][3] * s[1]) * inv,
+                (m[2][0] * s[5] - m[2][2] * s[2] + m[2][3] * s[1]) * inv,
+            ],
+            [
+                (m[1][0] * c[4] - m[1][1] * c[2] + m[1][3] * c[0]) * inv,
+                (-m[0][0] * c[4] + m[0][1] * c[2] - m[0][3] * c[0]) * inv,
+                (m[3][0] * s[4] - m[3][1] * s[2] + m[3][3] * s[0]) * inv,
+                (-m[2][0] * s[4] + m[2][1] * s[2] - m[2][3] * s[0]) * inv,
+            ],
+            [
+                (-m[1][0] * c[3] + m[1][1] * c[1] - m[1][2] * c[0]) * inv,
+                (m[0][0] * c[3] - m[0][1] * c[1] + m[0][2] * c[0]) * inv,
+                (-m[3][0] * s[3] + m[3][1] * s[1] - m[3][2] * s[0]) * inv,
+                (m[2][0] * s[3] - m[2][1] * s[1] + m[2][2] * s[0]) * inv,
+            ],
+        ];
+
+        Self { matrix }
+    }
+}
+
+impl From<Matrix4> for Matrix {
+    fn from(matrix: Matrix4) -> Self {
+        Matrix::init(matrix.matrix.iter().map(|line| line.to_vec()).collect())
+    }
+}
+
+impl PartialEq for Matrix4 {
+    fn eq(&self, other: &Self) -> bool {
+        self.matrix
+            .iter()
+            .flatten()
+            .zip(other.matrix.iter().flatten())
+            .all(|(a, b)| (a - b).abs() <= EPSILON)
+    }
+}
+
+impl Mul for Matrix4 {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        let mut matrix = [[0.0; 4]; 4];
+
+        for (x, line) in matrix.iter_mut().enumerate() {
+            for (y, item) in line.iter_mut().enumerate() {
+                *item = self.matrix[x][0] * other.matrix[0][y]
+                    + self.matrix[x][1] * other.matrix[1][y]
+                    + self.matrix[x][2] * other.matrix[2][y]
+                    + self.matrix[x][3] * other.matrix[3][y];
+            }
+        }
+
+        Self { matrix }
+    }
+}
+
+impl Mul<Tuple> for &Matrix4 {
+    type Output = Tuple;
+
+    fn mul(self, tuple: Tuple) -> Tuple {
+        let m = &self.matrix;
+        let row = |x: usize| {
+            m[x][0] * tuple.x + m[x][1] * tuple.y + m[x][2] * tuple.z + m[x][3] * tuple.w
+        };
+
+        Tuple {
+            x: row(0),
+            y: row(1),
+            z: row(2),
+            w: row(3),
+        }
+    }
+}
+
+impl Mul<Tuple> for Matrix4 {
+    type Output = Tuple;
+
+    fn mul(self, tuple: Tuple) -> Tuple {
+        &self * tuple
+    }
+}
+
+// A transform together with its inverse and the transpose of its inverse,
+// computed once when it is created. Shapes, patterns and the camera keep
+// their transforms this way, so the render loop never inverts a matrix.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    matrix: Matrix4,
+    inverse: Matrix4,
+    inverse_transpose: Matrix4,
+}
+
+impl Transform {
+    pub fn new(matrix: Matrix4) -> Self {
+        let inverse = matrix.inverse();
+        Self {
+            matrix,
+            inverse,
+            inverse_transpose: inverse.transpose(),
+        }
+    }
+
+    pub fn matrix(&self) -> Matrix4 {
+        self.matrix
+    }
+
+    pub fn inverse(&self) -> Matrix4 {
+        self.inverse
+    }
+
+    // used to take normals back out of object space
+    pub fn inverse_transpose(&self) -> Matrix4 {
+        self.inverse_transpose
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            matrix: Matrix4::identity(),
+            inverse: Matrix4::identity(),
+            inverse_transpose: Matrix4::identity(),
+        }
+    }
+}
+
+impl From<Matrix4> for Transform {
+    fn from(matrix: Matrix4) -> Self {
+        Self::new(matrix)
+    }
+}
+
+impl PartialEq<Matrix4> for Transform {
+    fn eq(&self, other: &Matrix4) -> bool {
+        self.matrix == *other
+    }
+}
+
+pub fn view_transform(from: Tuple, to: Tuple, up: Tuple) -> Matrix4 {
+    let forward = (to - from).norm();
+    let upn = up.norm();
+    let left = forward.cross_product(upn);
+    let true_up = left.cross_product(forward);
+
+    let orientation = matrix4![ left.x, left.y, left.z, 0.0;
+                                true_up.x, true_up.y, true_up.z, 0.0;
+                                -forward.x, -forward.y, -forward.z, 0.0;
+                                0.0, 0.0, 0.0, 1.0];
+
+    orientation * Matrix4::identity().translation(-from.x, -from.y, -from.z)
+}
+
 #[cfg(test)]
 mod tests {
     use proptest::prelude::*;
 
     use super::*;
     use crate::matrix;
+    use crate::matrix4;
     use crate::point;
     use crate::vector;
     use std::f64::consts::{FRAC_PI_2, FRAC_PI_4, PI};
@@ -535,6 +822,104 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_matrix4_multiply() {
+        let matrix_a = matrix4![1.0, 2.0, 3.0, 4.0;
+                                5.0, 6.0, 7.0, 8.0;
+                                9.0, 8.0, 7.0, 6.0;
+                                5.0, 4.0, 3.0, 2.0];
+
+        let matrix_b = matrix4![-2.0, 1.0, 2.0, 3.0;
+                                3.0, 2.0, 1.0, -1.0;
+                                4.0, 3.0, 6.0, 5.0;
+                                1.0, 2.0, 7.0, 8.0];
+
+        let matrix_result = matrix4![20.0, 22.0, 50.0, 48.0;
+                                     44.0, 54.0, 114.0, 108.0;
+                                     40.0, 58.0, 110.0, 102.0;
+                                     16.0, 26.0, 46.0, 42.0];
+
+        assert_eq!((matrix_a * matrix_b).matrix, matrix_result.matrix);
+        assert_eq!(Matrix4::identity() * matrix_a, matrix_a);
+    }
+
+    #[test]
+    fn test_matrix4_multiply_tuple() {
+        let matrix_a = matrix4![1.0, 2.0, 3.0, 4.0;
+                                2.0, 4.0, 4.0, 2.0;
+                                8.0, 6.0, 4.0, 1.0;
+                                0.0, 0.0, 0.0, 1.0];
+
+        assert_eq!(matrix_a * point!(1.0, 2.0, 3.0), point!(18.0, 24.0, 33.0));
+    }
+
+    #[test]
+    fn test_matrix4_determinant_and_transpose() {
+        let matrix_a = matrix4![-2.0, -8.0, 3.0, 5.0;
+                                -3.0, 1.0, 7.0, 3.0;
+                                1.0, 2.0, -9.0, 6.0;
+                                -6.0, 7.0, 7.0, -9.0];
+
+        assert_eq!(matrix_a.determinant(), -4071.0);
+        assert!(matrix_a.is_invertible());
+        assert_eq!(
+            Matrix::from(matrix_a.transpose()),
+            Matrix::from(matrix_a).transpose()
+        );
+    }
+
+    #[test]
+    fn test_matrix4_not_invertible() {
+        let matrix_a = matrix4![-4.0, 2.0, -2.0, -3.0;
+                                9.0, 6.0, 2.0, 6.0;
+                                0.0, -5.0, 1.0, -5.0;
+                                0.0, 0.0, 0.0, 0.0];
+
+        assert_eq!(matrix_a.determinant(), 0.0);
+        assert!(!matrix_a.is_invertible());
+    }
+
+    #[test]
+    fn test_matrix4_inverse() {
+        let matrix_a = matrix4![8.0, -5.0, 9.0, 2.0;
+                                7.0, 5.0, 6.0, 1.0;
+                                -6.0, 0.0, 9.0, 6.0;
+                                -3.0, 0.0, -9.0, -4.0];
+
+        assert_eq!(
+            matrix_a.inverse(),
+            matrix4![-0.15385, -0.15385, -0.28205, -0.53846;
+                     -0.07692, 0.12308, 0.02564, 0.03077;
+                     0.35897, 0.35897, 0.43590, 0.92308;
+                     -0.69231, -0.69231, -0.76923, -1.92308]
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn test_matrix4_inverse_matches_matrix(values in prop::array::uniform4(prop::array::uniform4(-10.0..10.0))) {
+            let m = Matrix4::init(values);
+            prop_assume!(m.determinant().abs() > 1.0);
+
+            assert_eq!(Matrix::from(m.inverse()), Matrix::from(m).inverse());
+            assert_eq!(m * m.inverse(), Matrix4::identity());
+        }
+    }
+
+    #[test]
+    fn test_transform_keeps_inverse() {
+        let matrix = Matrix4::identity()
+            .scaling(2.0, 3.0, 4.0)
+            .translation(1.0, 2.0, 3.0);
+
+        let transform = Transform::new(matrix);
+
+        assert_eq!(transform, matrix);
+        assert_eq!(transform.inverse(), matrix.inverse());
+        assert_eq!(transform.inverse_transpose(), matrix.inverse().transpose());
+        assert_eq!(Transform::default(), Matrix4::identity());
+    }
+
     #[test]
     fn test_transpose() {
         let matrix_a = matrix![0.0, 9.0, 3.0, 0.0;
@@ -559,7 +944,7 @@ mod tests {
 
         let t = view_transform(from, to, up);
 
-        assert_eq!(t, Matrix::identity(4));
+        assert_eq!(t, Matrix4::identity());
     }
 
     #[test]
@@ -570,7 +955,7 @@ mod tests {
 
         let t = view_transform(from, to, up);
 
-        assert_eq!(t, Matrix::identity(4).scaling(-1.0, 1.0, -1.0));
+        assert_eq!(t, Matrix4::identity().scaling(-1.0, 1.0, -1.0));
     }
 
     #[test]
@@ -581,7 +966,7 @@ mod tests {
 
         let t = view_transform(from, to, up);
 
-        assert_eq!(t, Matrix::identity(4).translation(0.0, 0.0, -8.0));
+        assert_eq!(t, Matrix4::identity().translation(0.0, 0.0, -8.0));
     }
 
     #[test]
@@ -594,7 +979,7 @@ mod tests {
 
         assert_eq!(
             t,
-            matrix![-0.50709, 0.50709, 0.67612, -2.36643;
+            matrix4![-0.50709, 0.50709, 0.67612, -2.36643;
                            0.76772, 0.60609, 0.12122, -2.82843;
                            -0.35857, 0.59761, -0.71714, 0.00000;
                            0.00000, 0.00000, 0.00000, 1.00000]
@@ -630,7 +1015,9 @@ mod tests {
     //      |
     // -------------> x
     //	    |
+    // expected values are the book's, rounded to 5 digits
     #[test]
+    #[allow(clippy::approx_constant)]
     fn test_rotation_x() {
         let point_a = point!(0.0, 1.0, 0.0);
 
@@ -640,7 +1027,9 @@ mod tests {
 
     // rotation y
 
+    // expected values are the book's, rounded to 5 digits
     #[test]
+    #[allow(clippy::approx_constant)]
     fn test_rotation_y() {
         let point_a = point!(0.0, 0.0, 1.0);
 
@@ -650,7 +1039,9 @@ mod tests {
 
     // rotation z
 
+    // expected values are the book's, rounded to 5 digits
     #[test]
+    #[allow(clippy::approx_constant)]
     fn test_rotation_z() {
         let point_a = point!(0.0, 1.0, 0.0);
 
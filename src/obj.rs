@@ -0,0 +1,456 @@
+use crate::group::Group;
+use crate::point;
+use crate::triangle::{SmoothTriangle, Triangle};
+use crate::tuple::*;
+use crate::vector;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// A face of the mesh, already triangulated
+#[derive(Debug, PartialEq, Clone)]
+pub struct Face {
+    pub points: [Tuple; 3],
+    // per vertex normals, when the file has them for every vertex of the face
+    pub normals: Option<[Tuple; 3]>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ObjGroup {
+    pub name: String,
+    pub faces: Vec<Face>,
+}
+
+// A line the parser skipped, either because the statement is not supported
+// or because it could not make sense of it
+#[derive(Debug, PartialEq)]
+pub struct IgnoredLine {
+    pub number: usize,
+    pub line: String,
+    pub reason: String,
+}
+
+#[derive(Debug, PartialEq, Default)]
+pub struct ObjFile {
+    pub vertices: Vec<Tuple>,
+    pub normals: Vec<Tuple>,
+    pub texture_coords: Vec<(f64, f64)>,
+    // faces that come before any `g` statement
+    pub default_group: Vec<Face>,
+    pub groups: Vec<ObjGroup>,
+    pub ignored: Vec<IgnoredLine>,
+    // index in groups of the last `g` statement
+    current_group: Option<usize>,
+}
+
+impl ObjFile {
+    // Builds a group of triangles ready to be added to a World. Each named
+    // group of the file becomes a nested group.
+    pub fn to_group(&self) -> Group {
+        let mut group = faces_to_group(&self.default_group);
+        for named in &self.groups {
+            group.add_child(Box::new(faces_to_group(&named.faces)));
+        }
+        group
+    }
+
+    fn current_faces(&mut self) -> &mut Vec<Face> {
+        match self.current_group {
+            Some(index) => &mut self.groups[index].faces,
+            None => &mut self.default_group,
+        }
+    }
+
+    fn parse_line(&mut self, line: &str) -> Result<(), String> {
+        let mut words = line.split_whitespace();
+        let keyword = words.next().unwrap_or_default();
+        let args: Vec<&str> = words.collect();
+
+        match keyword {
+            // the optional weight of rational curves means nothing for a mesh
+            "v" => {
+                let [x, y, z, _w] = parse_floats(&args, 3)?;
+                self.vertices.push(point!(x, y, z));
+            }
+            "vn" => {
+                let [x, y, z] = parse_floats(&args, 3)?;
+                self.normals.push(vector!(x, y, z));
+            }
+            // v and the depth w default to 0, and only u and v are kept
+            "vt" => {
+                let [u, v, _w] = parse_floats(&args, 1)?;
+                self.texture_coords.push((u, v));
+            }
+            "f" => {
+                let faces = self.parse_face(&args)?;
+                self.current_faces().extend(faces);
+            }
+            "g" => {
+                let name = args.join(" ");
+                // going back to an existing group appends to it
+                let index = match self.groups.iter().position(|group| group.name == name) {
+                    Some(index) => index,
+                    None => {
+                        self.groups.push(ObjGroup {
+                            name,
+                            faces: vec![],
+                        });
+                        self.groups.len() - 1
+                    }
+                };
+                self.current_group = Some(index);
+            }
+            _ => return Err(format!("unsupported statement `{}`", keyword)),
+        }
+
+        Ok(())
+    }
+
+    // faces are `f v1 v2 v3 ...` where each vertex is `v`, `v/vt`, `v//vn`
+    // or `v/vt/vn`. Polygons are split into a fan of triangles.
+    fn parse_face(&self, args: &[&str]) -> Result<Vec<Face>, String> {
+        if args.len() < 3 {
+            return Err(format!("a face needs 3 vertices, found {}", args.len()));
+        }
+
+        let mut points = vec![];
+        let mut normals = vec![];
+        for arg in args {
+            let mut indices = arg.split('/');
+            let vertex = indices.next().unwrap_or_default();
+            points.push(lookup(&self.vertices, vertex, "vertex")?);
+
+            let _texture = indices.next();
+            if let Some(normal) = indices.next().filter(|index| !index.is_empty()) {
+                normals.push(lookup(&self.normals, normal, "normal")?);
+            }
+        }
+        let has_normals = normals.len() == points.len();
+
+        Ok((1..points.len() - 1)
+            .map(|i| Face {
+                points: [points[0], points[i], points[i + 1]],
+                normals: if has_normals {
+                    Some([normals[0], normals[i], normals[i + 1]])
+                } else {
+                    None
+                },
+            })
+            .collect())
+    }
+}
+
+fn faces_to_group(faces: &[Face]) -> Group {
+    let mut group = Group::init();
+    for face in faces {
+        let [p1, p2, p3] = face.points;
+        match face.normals {
+            Some([n1, n2, n3]) => {
+                group.add_child(Box::new(SmoothTriangle::new(p1, p2, p3, n1, n2, n3)))
+            }
+            None => group.add_child(Box::new(Triangle::new(p1, p2, p3))),
+        }
+    }
+    group
+}
+
+// between required and N numbers, the missing ones being 0
+fn parse_floats<const N: usize>(args: &[&str], required: usize) -> Result<[f64; N], String> {
+    if args.len() < required || args.len() > N {
+        let expected = if required == N {
+            N.to_string()
+        } else {
+            format!("{} to {}", required, N)
+        };
+        return Err(format!(
+            "expected {} numbers, found {}",
+            expected,
+            args.len()
+        ));
+    }
+
+    let mut values = [0.0; N];
+    for (value, arg) in values.iter_mut().zip(args) {
+        *value = arg
+            .parse()
+            .map_err(|_| format!("`{}` is not a number", arg))?;
+    }
+    Ok(values)
+}
+
+// OBJ indices start at 1, and negative ones count back from the last element
+fn lookup(elements: &[Tuple], index: &str, kind: &str) -> Result<Tuple, String> {
+    let parsed: i64 = index
+        .parse()
+        .map_err(|_| format!("`{}` is not a valid {} index", index, kind))?;
+
+    let position = if parsed < 0 {
+        elements.len() as i64 + parsed
+    } else {
+        parsed - 1
+    };
+
+    if position < 0 || position >= elements.len() as i64 {
+        return Err(format!("{} {} does not exist", kind, index));
+    }
+    Ok(elements[position as usize])
+}
+
+pub fn parse_obj(input: &str) -> ObjFile {
+    let mut obj = ObjFile::default();
+
+    for (number, line) in input.lines().enumerate() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+
+        if let Err(reason) = obj.parse_line(trimmed) {
+            obj.ignored.push(IgnoredLine {
+                number: number + 1,
+                line: line.to_string(),
+                reason,
+            });
+        }
+    }
+
+    obj
+}
+
+pub fn parse_obj_file(path: &Path) -> io::Result<ObjFile> {
+    Ok(parse_obj(&fs::read_to_string(path)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ray::Ray;
+    use crate::shape::Shape;
+
+    #[test]
+    fn test_ignoring_unrecognized_lines() {
+        let gibberish = "There was a young lady named Bright
+who traveled much faster than light.
+She set out one day
+in a relative way,
+and came back the previous night.";
+
+        let obj = parse_obj(gibberish);
+
+        assert_eq!(obj.ignored.len(), 5);
+        assert_eq!(obj.ignored[0].number, 1);
+        assert_eq!(obj.ignored[0].reason, "unsupported statement `There`");
+    }
+
+    #[test]
+    fn test_vertex_records() {
+        let obj = parse_obj(
+            "v -1 1 0
+v -1.0000 0.5000 0.0000
+v 1 0 0
+v 1 1 0",
+        );
+
+        assert_eq!(obj.vertices.len(), 4);
+        assert_eq!(obj.vertices[0], point!(-1.0, 1.0, 0.0));
+        assert_eq!(obj.vertices[1], point!(-1.0, 0.5, 0.0));
+        assert_eq!(obj.vertices[2], point!(1.0, 0.0, 0.0));
+        assert_eq!(obj.vertices[3], point!(1.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn test_vertices_with_a_weight() {
+        let obj = parse_obj(
+            "v -1 1 0 1.0
+v -1 0 0
+v 1 0 0 0.5
+f 1 2 3",
+        );
+
+        assert!(obj.ignored.is_empty());
+        assert_eq!(obj.vertices[2], point!(1.0, 0.0, 0.0));
+        assert_eq!(
+            obj.default_group[0].points,
+            [
+                point!(-1.0, 1.0, 0.0),
+                point!(-1.0, 0.0, 0.0),
+                point!(1.0, 0.0, 0.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parsing_triangle_faces() {
+        let obj = parse_obj(
+            "v -1 1 0
+v -1 0 0
+v 1 0 0
+v 1 1 0
+
+f 1 2 3
+f 1 3 4",
+        );
+
+        let faces = &obj.default_group;
+        assert_eq!(faces.len(), 2);
+        assert_eq!(
+            faces[0].points,
+            [obj.vertices[0], obj.vertices[1], obj.vertices[2]]
+        );
+        assert_eq!(
+            faces[1].points,
+            [obj.vertices[0], obj.vertices[2], obj.vertices[3]]
+        );
+        assert!(obj.ignored.is_empty());
+    }
+
+    #[test]
+    fn test_triangulating_polygons() {
+        let obj = parse_obj(
+            "v -1 1 0
+v -1 0 0
+v 1 0 0
+v 1 1 0
+v 0 2 0
+
+f 1 2 3 4 5",
+        );
+
+        let v = &obj.vertices;
+        let faces = &obj.default_group;
+        assert_eq!(faces.len(), 3);
+        assert_eq!(faces[0].points, [v[0], v[1], v[2]]);
+        assert_eq!(faces[1].points, [v[0], v[2], v[3]]);
+        assert_eq!(faces[2].points, [v[0], v[3], v[4]]);
+    }
+
+    #[test]
+    fn test_triangles_in_named_groups() {
+        let obj = parse_obj(
+            "v -1 1 0
+v -1 0 0
+v 1 0 0
+v 1 1 0
+
+g FirstGroup
+f 1 2 3
+g SecondGroup
+f 1 3 4",
+        );
+
+        assert!(obj.default_group.is_empty());
+        assert_eq!(obj.groups.len(), 2);
+        assert_eq!(obj.groups[0].name, "FirstGroup");
+        assert_eq!(obj.groups[0].faces.len(), 1);
+        assert_eq!(obj.groups[1].name, "SecondGroup");
+        assert_eq!(obj.groups[1].faces.len(), 1);
+    }
+
+    #[test]
+    fn test_converting_to_group() {
+        let obj = parse_obj(
+            "v -1 1 0
+v -1 0 0
+v 1 0 0
+v 1 1 0
+
+f 1 2 3
+g FirstGroup
+f 1 2 3
+g SecondGroup
+f 1 3 4",
+        );
+
+        let group = obj.to_group();
+
+        // one triangle and the two named groups
+        assert_eq!(group.children().len(), 3);
+
+        let ray = Ray::new(point!(0.5, 0.5, -5.0), vector!(0.0, 0.0, 1.0));
+        assert_eq!(group.local_intersect(&ray).len(), 1);
+    }
+
+    #[test]
+    fn test_vertex_normal_and_texture_records() {
+        let obj = parse_obj(
+            "vn 0 0 1
+vn 0.707 0 -0.707
+vn 1 2 3
+vt 0.5 0.25
+vt 0.1 0.2 0.3
+vt 0.75",
+        );
+
+        assert_eq!(obj.normals.len(), 3);
+        assert_eq!(obj.normals[0], vector!(0.0, 0.0, 1.0));
+        assert_eq!(obj.normals[1], vector!(0.707, 0.0, -0.707));
+        assert_eq!(obj.normals[2], vector!(1.0, 2.0, 3.0));
+        assert_eq!(
+            obj.texture_coords,
+            vec![(0.5, 0.25), (0.1, 0.2), (0.75, 0.0)]
+        );
+    }
+
+    #[test]
+    fn test_faces_with_normals() {
+        let obj = parse_obj(
+            "v 0 1 0
+v -1 0 0
+v 1 0 0
+
+vn -1 0 0
+vn 1 0 0
+vn 0 1 0
+
+f 1//3 2//1 3//2
+f 1/0/3 2/102/1 3/14/2",
+        );
+
+        let expected = Face {
+            points: [obj.vertices[0], obj.vertices[1], obj.vertices[2]],
+            normals: Some([obj.normals[2], obj.normals[0], obj.normals[1]]),
+        };
+        assert_eq!(obj.default_group, vec![expected.clone(), expected]);
+    }
+
+    #[test]
+    fn test_negative_indices_are_relative() {
+        let obj = parse_obj(
+            "v 0 1 0
+v -1 0 0
+v 1 0 0
+f -3 -2 -1",
+        );
+
+        assert_eq!(
+            obj.default_group[0].points,
+            [obj.vertices[0], obj.vertices[1], obj.vertices[2]]
+        );
+    }
+
+    #[test]
+    fn test_reporting_malformed_lines() {
+        let obj = parse_obj(
+            "v 0 1
+v 0 one 0
+v 0 1 0
+f 1 2
+f 1 2 9
+mtllib scene.mtl",
+        );
+
+        let reasons: Vec<&str> = obj.ignored.iter().map(|i| i.reason.as_str()).collect();
+        assert_eq!(
+            reasons,
+            vec![
+                "expected 3 to 4 numbers, found 2",
+                "`one` is not a number",
+                "a face needs 3 vertices, found 2",
+                "vertex 2 does not exist",
+                "unsupported statement `mtllib`",
+            ]
+        );
+        assert_eq!(obj.ignored[4].number, 6);
+        assert_eq!(obj.vertices.len(), 1);
+    }
+}
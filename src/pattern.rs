@@ -0,0 +1,329 @@
+use crate::color::*;
+use crate::matrix::*;
+use crate::shape::Shape;
+use crate::tuple::*;
+use std::fmt::Debug;
+
+// Pattern is anything that gives a color for a point in pattern space.
+// Patterns have their own transform, applied on top of the object transform,
+// so they can be scaled or rotated independently of the shape they are on.
+pub trait Pattern: Debug + Send + Sync {
+    fn transform(&self) -> &Transform;
+    fn set_transform(&mut self, transform: Matrix4);
+    fn pattern_at(&self, pattern_point: Tuple) -> Color;
+
+    fn pattern_at_shape(&self, object: &dyn Shape, world_point: Tuple) -> Color {
+        let object_point = object.world_to_object(world_point);
+        let pattern_point = self.transform().inverse() * object_point;
+        self.pattern_at(pattern_point)
+    }
+}
+
+// Patterns are compared by identity, like shapes
+impl PartialEq for dyn Pattern + '_ {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::addr_eq(self, other)
+    }
+}
+
+fn is_even(value: f64) -> bool {
+    (value.floor() as i64).rem_euclid(2) == 0
+}
+
+// Alternates between two colors every unit along x
+#[derive(Debug, PartialEq)]
+pub struct Stripe {
+    pub a: Color,
+    pub b: Color,
+    transform: Transform,
+}
+
+impl Stripe {
+    pub fn new(a: Color, b: Color) -> Self {
+        Self {
+            a,
+            b,
+            transform: Transform::default(),
+        }
+    }
+}
+
+impl Pattern for Stripe {
+    fn transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    fn set_transform(&mut self, transform: Matrix4) {
+        self.transform = Transform::new(transform);
+    }
+
+    fn pattern_at(&self, point: Tuple) -> Color {
+        if is_even(point.x) {
+            self.a
+        } else {
+            self.b
+        }
+    }
+}
+
+// Linearly blends from one color to the other along x, repeating every unit
+#[derive(Debug, PartialEq)]
+pub struct Gradient {
+    pub a: Color,
+    pub b: Color,
+    transform: Transform,
+}
+
+impl Gradient {
+    pub fn new(a: Color, b: Color) -> Self {
+        Self {
+            a,
+            b,
+            transform: Transform::default(),
+        }
+    }
+}
+
+impl Pattern for Gradient {
+    fn transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    fn set_transform(&mut self, transform: Matrix4) {
+        self.transform = Transform::new(transform);
+    }
+
+    fn pattern_at(&self, point: Tuple) -> Color {
+        let distance = self.b - self.a;
+        let fraction = point.x - point.x.floor();
+        self.a + distance * fraction
+    }
+}
+
+// Concentric rings around the y axis
+#[derive(Debug, PartialEq)]
+pub struct Ring {
+    pub a: Color,
+    pub b: Color,
+    transform: Transform,
+}
+
+impl Ring {
+    pub fn new(a: Color, b: Color) -> Self {
+        Self {
+            a,
+            b,
+            transform: Transform::default(),
+        }
+    }
+}
+
+impl Pattern for Ring {
+    fn transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    fn set_transform(&mut self, transform: Matrix4) {
+        self.transform = Transform::new(transform);
+    }
+
+    fn pattern_at(&self, point: Tuple) -> Color {
+        if is_even((point.x * point.x + point.z * point.z).sqrt()) {
+            self.a
+        } else {
+            self.b
+        }
+    }
+}
+
+// Alternating unit cubes in all three dimensions
+#[derive(Debug, PartialEq)]
+pub struct Checker {
+    pub a: Color,
+    pub b: Color,
+    transform: Transform,
+}
+
+impl Checker {
+    pub fn new(a: Color, b: Color) -> Self {
+        Self {
+            a,
+            b,
+            transform: Transform::default(),
+        }
+    }
+}
+
+impl Pattern for Checker {
+    fn transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    fn set_transform(&mut self, transform: Matrix4) {
+        self.transform = Transform::new(transform);
+    }
+
+    fn pattern_at(&self, point: Tuple) -> Color {
+        if is_even(point.x.floor() + point.y.floor() + point.z.floor()) {
+            self.a
+        } else {
+            self.b
+        }
+    }
+}
+
+// Returns the pattern space point as a color, handy to check transformations
+#[cfg(test)]
+#[derive(Debug)]
+pub struct TestPattern {
+    transform: Transform,
+}
+
+#[cfg(test)]
+pub fn test_pattern() -> TestPattern {
+    TestPattern {
+        transform: Transform::default(),
+    }
+}
+
+#[cfg(test)]
+impl Pattern for TestPattern {
+    fn transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    fn set_transform(&mut self, transform: Matrix4) {
+        self.transform = Transform::new(transform);
+    }
+
+    fn pattern_at(&self, point: Tuple) -> Color {
+        Color::new(point.x, point.y, point.z)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::point;
+    use crate::sphere::Sphere;
+
+    const BLACK: Color = Color {
+        red: 0.0,
+        green: 0.0,
+        blue: 0.0,
+    };
+
+    #[test]
+    fn test_stripe_is_constant_in_y_and_z() {
+        let pattern = Stripe::new(WHITE, BLACK);
+
+        assert_eq!(pattern.pattern_at(point!(0, 0, 0)), WHITE);
+        assert_eq!(pattern.pattern_at(point!(0, 1, 0)), WHITE);
+        assert_eq!(pattern.pattern_at(point!(0, 2, 0)), WHITE);
+        assert_eq!(pattern.pattern_at(point!(0, 0, 1)), WHITE);
+        assert_eq!(pattern.pattern_at(point!(0, 0, 2)), WHITE);
+    }
+
+    #[test]
+    fn test_stripe_alternates_in_x() {
+        let pattern = Stripe::new(WHITE, BLACK);
+
+        assert_eq!(pattern.pattern_at(point!(0.0, 0, 0)), WHITE);
+        assert_eq!(pattern.pattern_at(point!(0.9, 0, 0)), WHITE);
+        assert_eq!(pattern.pattern_at(point!(1.0, 0, 0)), BLACK);
+        assert_eq!(pattern.pattern_at(point!(-0.1, 0, 0)), BLACK);
+        assert_eq!(pattern.pattern_at(point!(-1.0, 0, 0)), BLACK);
+        assert_eq!(pattern.pattern_at(point!(-1.1, 0, 0)), WHITE);
+    }
+
+    #[test]
+    fn test_pattern_with_object_transformation() {
+        let mut shape = Sphere::init();
+        shape.set_transform(Matrix4::identity().scaling(2.0, 2.0, 2.0));
+        let pattern = test_pattern();
+
+        let color = pattern.pattern_at_shape(&shape, point!(2, 3, 4));
+
+        assert_eq!(color, Color::new(1.0, 1.5, 2.0));
+    }
+
+    #[test]
+    fn test_pattern_with_pattern_transformation() {
+        let shape = Sphere::init();
+        let mut pattern = test_pattern();
+        pattern.set_transform(Matrix4::identity().scaling(2.0, 2.0, 2.0));
+
+        let color = pattern.pattern_at_shape(&shape, point!(2, 3, 4));
+
+        assert_eq!(color, Color::new(1.0, 1.5, 2.0));
+    }
+
+    #[test]
+    fn test_pattern_with_object_and_pattern_transformation() {
+        let mut shape = Sphere::init();
+        shape.set_transform(Matrix4::identity().scaling(2.0, 2.0, 2.0));
+        let mut pattern = test_pattern();
+        pattern.set_transform(Matrix4::identity().translation(0.5, 1.0, 1.5));
+
+        let color = pattern.pattern_at_shape(&shape, point!(2.5, 3, 3.5));
+
+        assert_eq!(color, Color::new(0.75, 0.5, 0.25));
+    }
+
+    #[test]
+    fn test_gradient_interpolates_between_colors() {
+        let pattern = Gradient::new(WHITE, BLACK);
+
+        assert_eq!(pattern.pattern_at(point!(0, 0, 0)), WHITE);
+        assert_eq!(
+            pattern.pattern_at(point!(0.25, 0, 0)),
+            Color::new(0.75, 0.75, 0.75)
+        );
+        assert_eq!(
+            pattern.pattern_at(point!(0.5, 0, 0)),
+            Color::new(0.5, 0.5, 0.5)
+        );
+        assert_eq!(
+            pattern.pattern_at(point!(0.75, 0, 0)),
+            Color::new(0.25, 0.25, 0.25)
+        );
+    }
+
+    #[test]
+    fn test_ring_extends_in_x_and_z() {
+        let pattern = Ring::new(WHITE, BLACK);
+
+        assert_eq!(pattern.pattern_at(point!(0, 0, 0)), WHITE);
+        assert_eq!(pattern.pattern_at(point!(1, 0, 0)), BLACK);
+        assert_eq!(pattern.pattern_at(point!(0, 0, 1)), BLACK);
+        // 0.708 = just slightly more than sqrt(2) / 2
+        assert_eq!(pattern.pattern_at(point!(0.708, 0, 0.708)), BLACK);
+    }
+
+    #[test]
+    fn test_checkers_repeat_in_x() {
+        let pattern = Checker::new(WHITE, BLACK);
+
+        assert_eq!(pattern.pattern_at(point!(0, 0, 0)), WHITE);
+        assert_eq!(pattern.pattern_at(point!(0.99, 0, 0)), WHITE);
+        assert_eq!(pattern.pattern_at(point!(1.01, 0, 0)), BLACK);
+    }
+
+    #[test]
+    fn test_checkers_repeat_in_y() {
+        let pattern = Checker::new(WHITE, BLACK);
+
+        assert_eq!(pattern.pattern_at(point!(0, 0, 0)), WHITE);
+        assert_eq!(pattern.pattern_at(point!(0, 0.99, 0)), WHITE);
+        assert_eq!(pattern.pattern_at(point!(0, 1.01, 0)), BLACK);
+    }
+
+    #[test]
+    fn test_checkers_repeat_in_z() {
+        let pattern = Checker::new(WHITE, BLACK);
+
+        assert_eq!(pattern.pattern_at(point!(0, 0, 0)), WHITE);
+        assert_eq!(pattern.pattern_at(point!(0, 0, 0.99)), WHITE);
+        assert_eq!(pattern.pattern_at(point!(0, 0, 1.01)), BLACK);
+    }
+}
@@ -0,0 +1,254 @@
+use std::fmt;
+use std::io::{self, Read, Write};
+
+use crate::canvas::Canvas;
+use crate::color::Color;
+use crate::ppm::{is_whitespace, Scanner};
+
+// Portable Float Map: a PPM-like header and unclamped 32-bit floats,
+// with rows stored from the bottom of the image up
+
+#[derive(Debug)]
+pub enum PfmError {
+    Io(io::Error),
+    Invalid(String),
+}
+
+impl fmt::Display for PfmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PfmError::Io(error) => write!(f, "{}", error),
+            PfmError::Invalid(message) => write!(f, "invalid PFM: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for PfmError {}
+
+impl From<io::Error> for PfmError {
+    fn from(error: io::Error) -> Self {
+        PfmError::Io(error)
+    }
+}
+
+fn invalid<T>(message: impl Into<String>) -> Result<T, PfmError> {
+    Err(PfmError::Invalid(message.into()))
+}
+
+// color, little endian
+pub fn write_pfm<W: Write>(canvas: &Canvas, mut writer: W) -> io::Result<()> {
+    // parse_pfm rejects images without pixels
+    if canvas.width == 0 || canvas.height == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "PFM images cannot be empty",
+        ));
+    }
+    // a negative scale marks little endian data
+    write!(writer, "PF\n{} {}\n-1.0\n", canvas.width, canvas.height)?;
+
+    let mut bytes = Vec::with_capacity(canvas.width * 12);
+    for row in canvas.pixels.iter().rev() {
+        bytes.clear();
+        for pixel in row.iter() {
+            for &channel in [pixel.red, pixel.green, pixel.blue].iter() {
+                bytes.extend_from_slice(&(channel as f32).to_le_bytes());
+            }
+        }
+        writer.write_all(&bytes)?;
+    }
+
+    writer.flush()
+}
+
+fn header_token<'a>(scanner: &mut Scanner<'a>, what: &str) -> Result<&'a str, PfmError> {
+    match scanner.token() {
+        Some(token) => match std::str::from_utf8(token) {
+            Ok(token) => Ok(token),
+            Err(_) => invalid(format!("expected {}, found binary data", what)),
+        },
+        None => invalid(format!("expected {}, found end of file", what)),
+    }
+}
+
+fn header_number<T: std::str::FromStr>(scanner: &mut Scanner, what: &str) -> Result<T, PfmError> {
+    let token = header_token(scanner, what)?;
+    match token.parse() {
+        Ok(number) => Ok(number),
+        Err(_) => invalid(format!("expected {}, found `{}`", what, token)),
+    }
+}
+
+// reads color (PF) and grayscale (Pf) maps in either byte order; the magnitude of the scale is ignored
+pub fn parse_pfm(data: &[u8]) -> Result<Canvas, PfmError> {
+    let mut scanner = Scanner::new(data);
+
+    let channels = match header_token(&mut scanner, "magic number")? {
+        "PF" => 3,
+        "Pf" => 1,
+        magic => {
+            return invalid(format!(
+                "unsupported magic number `{}`, expected PF or Pf",
+                magic
+            ))
+        }
+    };
+    let width: usize = header_number(&mut scanner, "width")?;
+    let height: usize = header_number(&mut scanner, "height")?;
+    let scale: f32 = header_number(&mut scanner, "scale")?;
+    if scale == 0.0 || !scale.is_finite() {
+        return invalid(format!("scale must be a non-zero number, found {}", scale));
+    }
+    let little_endian = scale < 0.0;
+    // a zero side passes the data length check whatever the other side is,
+    // and Canvas::init would still allocate a row for every line of it
+    if width == 0 || height == 0 {
+        return invalid(format!("image size {}x{} is empty", width, height));
+    }
+
+    match data.get(scanner.position) {
+        Some(&byte) if is_whitespace(byte) => scanner.position += 1,
+        _ => return invalid("expected whitespace after scale"),
+    }
+    let raster = &data[scanner.position..];
+    let floats = match width
+        .checked_mul(height)
+        .and_then(|pixels| pixels.checked_mul(channels))
+    {
+        Some(floats) => floats,
+        None => return invalid(format!("image size {}x{} is too large", width, height)),
+    };
+    if raster.len() / 4 < floats {
+        return invalid(format!(
+            "expected {} bytes of pixel data, found {}",
+            floats * 4,
+            raster.len()
+        ));
+    }
+
+    let mut canvas = Canvas::init(width, height);
+    let mut values = raster.chunks_exact(4).map(|bytes| {
+        let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+        let value = if little_endian {
+            f32::from_le_bytes(bytes)
+        } else {
+            f32::from_be_bytes(bytes)
+        };
+        value as f64
+    });
+    for y in (0..height).rev() {
+        for x in 0..width {
+            let color = if channels == 3 {
+                let red = values.next().unwrap();
+                let green = values.next().unwrap();
+                let blue = values.next().unwrap();
+                Color::new(red, green, blue)
+            } else {
+                let gray = values.next().unwrap();
+                Color::new(gray, gray, gray)
+            };
+            canvas.write_pixel(x, y, color);
+        }
+    }
+
+    Ok(canvas)
+}
+
+pub fn read_pfm<R: Read>(mut reader: R) -> Result<Canvas, PfmError> {
+    let mut data = vec![];
+    reader.read_to_end(&mut data)?;
+    parse_pfm(&data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_invalid(data: &[u8], message: &str) {
+        match parse_pfm(data) {
+            Err(PfmError::Invalid(error)) => assert_eq!(error, message),
+            Err(error) => panic!("unexpected error {}", error),
+            Ok(_) => panic!("expected an error"),
+        }
+    }
+
+    #[test]
+    fn test_write_pfm() {
+        let mut canvas = Canvas::init(2, 2);
+        canvas.write_pixel(0, 0, Color::new(1.0, 2.0, 3.0));
+        canvas.write_pixel(1, 1, Color::new(-0.5, 0.25, 1000.0));
+
+        let mut pfm = vec![];
+        write_pfm(&canvas, &mut pfm).unwrap();
+
+        let header = b"PF\n2 2\n-1.0\n";
+        assert_eq!(&pfm[..header.len()], header);
+        let floats: Vec<f32> = pfm[header.len()..]
+            .chunks_exact(4)
+            .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            .collect();
+        // the bottom row comes first
+        assert_eq!(
+            floats,
+            vec![0.0, 0.0, 0.0, -0.5, 0.25, 1000.0, 1.0, 2.0, 3.0, 0.0, 0.0, 0.0]
+        );
+    }
+
+    #[test]
+    fn test_write_pfm_rejects_empty_canvas() {
+        let mut pfm = vec![];
+
+        let error = write_pfm(&Canvas::init(0, 3), &mut pfm).unwrap_err();
+
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert!(pfm.is_empty());
+    }
+
+    #[test]
+    fn test_pfm_round_trip_keeps_values_above_one() {
+        let mut canvas = Canvas::init(3, 2);
+        canvas.write_pixel(0, 0, Color::new(1.9, 0.1, 42.0));
+        canvas.write_pixel(2, 1, Color::new(0.001, 7.5, 0.0));
+
+        let mut pfm = vec![];
+        write_pfm(&canvas, &mut pfm).unwrap();
+        let read = read_pfm(&pfm[..]).unwrap();
+
+        assert_eq!((read.width, read.height), (3, 2));
+        for y in 0..2 {
+            for x in 0..3 {
+                assert_eq!(read.pixel_at(x, y), canvas.pixel_at(x, y));
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_big_endian_grayscale() {
+        let mut pfm = b"Pf\n2 1\n1.0\n".to_vec();
+        pfm.extend_from_slice(&0.5f32.to_be_bytes());
+        pfm.extend_from_slice(&3.0f32.to_be_bytes());
+
+        let canvas = parse_pfm(&pfm).unwrap();
+
+        assert_eq!(*canvas.pixel_at(0, 0), Color::new(0.5, 0.5, 0.5));
+        assert_eq!(*canvas.pixel_at(1, 0), Color::new(3.0, 3.0, 3.0));
+    }
+
+    #[test]
+    fn test_parse_pfm_errors() {
+        assert_invalid(
+            b"P6\n1 1\n255\n",
+            "unsupported magic number `P6`, expected PF or Pf",
+        );
+        assert_invalid(b"PF\n1", "expected height, found end of file");
+        assert_invalid(b"PF\n1 1\n0\n", "scale must be a non-zero number, found 0");
+        assert_invalid(
+            b"PF 0 1000000000000 -1\n",
+            "image size 0x1000000000000 is empty",
+        );
+        assert_invalid(
+            b"PF\n1 1\n-1.0\n\0\0\0\0",
+            "expected 12 bytes of pixel data, found 4",
+        );
+    }
+}
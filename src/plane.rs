@@ -0,0 +1,162 @@
+use crate::bounds::BoundingBox;
+use crate::material::*;
+use crate::matrix::*;
+use crate::point;
+use crate::ray::*;
+use crate::shape::Shape;
+use crate::tuple::PARALLEL_EPSILON;
+use crate::tuple::*;
+use crate::vector;
+
+// An infinite plane, which in object space is the xz plane (y = 0)
+#[derive(Debug, PartialEq)]
+pub struct Plane {
+    transform: Transform,
+    pub material: Material,
+    parent_transform: Transform,
+}
+
+impl Plane {
+    pub fn init() -> Plane {
+        Plane {
+            transform: Transform::default(),
+            material: Material::default(),
+            parent_transform: Transform::default(),
+        }
+    }
+}
+
+impl Shape for Plane {
+    fn transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    fn set_transform(&mut self, transform: Matrix4) {
+        self.transform = Transform::new(transform);
+    }
+
+    fn parent_transform(&self) -> &Transform {
+        &self.parent_transform
+    }
+
+    fn set_parent_transform(&mut self, transform: Matrix4) {
+        self.parent_transform = Transform::new(transform);
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn material_mut(&mut self) -> &mut Material {
+        &mut self.material
+    }
+
+    fn local_intersect(&self, ray: &Ray) -> Vec<Intersection> {
+        // a ray parallel to the plane (or coplanar with it) never hits it
+        if ray.direction.y.abs() < PARALLEL_EPSILON {
+            return vec![];
+        }
+
+        let t = -ray.origin.y / ray.direction.y;
+        vec![Intersection::new(t, self)]
+    }
+
+    fn bounds(&self) -> BoundingBox {
+        BoundingBox::new(
+            point!(f64::NEG_INFINITY, 0.0, f64::NEG_INFINITY),
+            point!(f64::INFINITY, 0.0, f64::INFINITY),
+        )
+    }
+
+    fn local_normal_at(&self, _object_point: Tuple) -> Tuple {
+        vector!(0.0, 1.0, 0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::point;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_plane_normal_is_constant() {
+        let plane = Plane::init();
+
+        assert_eq!(
+            plane.local_normal_at(point!(0.0, 0.0, 0.0)),
+            vector!(0.0, 1.0, 0.0)
+        );
+        assert_eq!(
+            plane.local_normal_at(point!(10.0, 0.0, -10.0)),
+            vector!(0.0, 1.0, 0.0)
+        );
+        assert_eq!(
+            plane.local_normal_at(point!(-5.0, 0.0, 150.0)),
+            vector!(0.0, 1.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn test_intersect_parallel_ray() {
+        let plane = Plane::init();
+        let ray = Ray::new(point!(0.0, 10.0, 0.0), vector!(0.0, 0.0, 1.0));
+
+        assert_eq!(plane.local_intersect(&ray).len(), 0);
+    }
+
+    #[test]
+    fn test_intersect_coplanar_ray() {
+        let plane = Plane::init();
+        let ray = Ray::new(point!(0.0, 0.0, 0.0), vector!(0.0, 0.0, 1.0));
+
+        assert_eq!(plane.local_intersect(&ray).len(), 0);
+    }
+
+    #[test]
+    fn test_intersect_from_above() {
+        let plane = Plane::init();
+        let ray = Ray::new(point!(0.0, 1.0, 0.0), vector!(0.0, -1.0, 0.0));
+
+        let intersections = plane.local_intersect(&ray);
+
+        assert_eq!(intersections.len(), 1);
+        assert_eq!(intersections[0].t, 1.0);
+        assert_eq!(intersections[0].object, &plane as &dyn Shape);
+    }
+
+    #[test]
+    fn test_intersect_from_below() {
+        let plane = Plane::init();
+        let ray = Ray::new(point!(0.0, -1.0, 0.0), vector!(0.0, 1.0, 0.0));
+
+        let intersections = plane.local_intersect(&ray);
+
+        assert_eq!(intersections.len(), 1);
+        assert_eq!(intersections[0].t, 1.0);
+        assert_eq!(intersections[0].object, &plane as &dyn Shape);
+    }
+
+    #[test]
+    fn test_intersect_plane_scaled_along_y() {
+        let mut plane = Plane::init();
+        plane.set_transform(Matrix4::identity().scaling(1.0, 1000.0, 1.0));
+        let ray = Ray::new(point!(0.0, -1.0, 0.0), vector!(0.0, 0.5, 0.866));
+
+        let intersections = ray.intersect(&plane);
+
+        assert_eq!(intersections.len(), 1);
+        assert_relative_eq!(intersections[0].t, 2.0, epsilon = 1e-9);
+    }
+
+    #[test]
+    fn test_transformed_plane_normal() {
+        let mut plane = Plane::init();
+        plane.set_transform(Matrix4::identity().rotation_z(std::f64::consts::FRAC_PI_2));
+
+        assert_eq!(
+            plane.normal_at(point!(0.0, 0.0, 0.0)),
+            vector!(-1.0, 0.0, 0.0)
+        );
+    }
+}
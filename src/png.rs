@@ -0,0 +1,725 @@
+use std::fmt;
+use std::io::{self, Read, Write};
+
+use crate::canvas::Canvas;
+use crate::color::Color;
+use crate::deflate::{self, Compression, DeflateError};
+use crate::ppm::scale_color;
+use crate::tonemap::ToneMapping;
+
+const SIGNATURE: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+// image data is split into IDAT chunks of at most this many bytes
+const IDAT_SIZE: usize = 1 << 16;
+// PNG dimensions are limited to 2^31 - 1
+const MAX_DIMENSION: usize = i32::MAX as usize;
+
+const COLOR_GRAY: u8 = 0;
+const COLOR_RGB: u8 = 2;
+const COLOR_PALETTE: u8 = 3;
+const COLOR_GRAY_ALPHA: u8 = 4;
+const COLOR_RGBA: u8 = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BitDepth {
+    Eight,
+    Sixteen,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PngOptions {
+    pub bit_depth: BitDepth,
+    pub compression: Compression,
+}
+
+impl Default for PngOptions {
+    fn default() -> Self {
+        PngOptions {
+            bit_depth: BitDepth::Eight,
+            compression: Compression::Deflate,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum PngError {
+    Io(io::Error),
+    Invalid(String),
+}
+
+impl fmt::Display for PngError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PngError::Io(error) => write!(f, "{}", error),
+            PngError::Invalid(message) => write!(f, "invalid PNG: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for PngError {}
+
+impl From<io::Error> for PngError {
+    fn from(error: io::Error) -> Self {
+        PngError::Io(error)
+    }
+}
+
+impl From<DeflateError> for PngError {
+    fn from(error: DeflateError) -> Self {
+        PngError::Invalid(error.message)
+    }
+}
+
+fn invalid<T>(message: impl Into<String>) -> Result<T, PngError> {
+    Err(PngError::Invalid(message.into()))
+}
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut n = 0;
+    while n < 256 {
+        let mut c = n as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 == 1 {
+                0xedb8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+            k += 1;
+        }
+        table[n] = c;
+        n += 1;
+    }
+    table
+}
+
+const CRC_TABLE: [u32; 256] = crc_table();
+
+pub fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0, |crc, &byte| {
+        CRC_TABLE[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+fn write_chunk<W: Write>(writer: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    let mut crc_data = kind.to_vec();
+    crc_data.extend_from_slice(data);
+
+    writer.write_all(&(data.len() as u32).to_be_bytes())?;
+    writer.write_all(&crc_data)?;
+    writer.write_all(&crc32(&crc_data).to_be_bytes())
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = (
+        (p - a as i16).abs(),
+        (p - b as i16).abs(),
+        (p - c as i16).abs(),
+    );
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+// applies filter to row, given the previous row and the bytes per pixel
+fn filter_row(filter: u8, row: &[u8], previous: &[u8], bpp: usize, output: &mut Vec<u8>) {
+    output.push(filter);
+    for i in 0..row.len() {
+        let a = if i >= bpp { row[i - bpp] } else { 0 };
+        let b = previous[i];
+        let c = if i >= bpp { previous[i - bpp] } else { 0 };
+        let predicted = match filter {
+            0 => 0,
+            1 => a,
+            2 => b,
+            3 => ((a as u16 + b as u16) / 2) as u8,
+            _ => paeth(a, b, c),
+        };
+        output.push(row[i].wrapping_sub(predicted));
+    }
+}
+
+fn unfilter_row(filter: u8, row: &mut [u8], previous: &[u8], bpp: usize) -> Result<(), PngError> {
+    for i in 0..row.len() {
+        let a = if i >= bpp { row[i - bpp] } else { 0 };
+        let b = previous[i];
+        let c = if i >= bpp { previous[i - bpp] } else { 0 };
+        let predicted = match filter {
+            0 => 0,
+            1 => a,
+            2 => b,
+            3 => ((a as u16 + b as u16) / 2) as u8,
+            4 => paeth(a, b, c),
+            _ => return invalid(format!("unknown filter type {}", filter)),
+        };
+        row[i] = row[i].wrapping_add(predicted);
+    }
+    Ok(())
+}
+
+// every row gets the filter whose output has the smallest sum of absolute values
+fn filter_image(raw: &[u8], stride: usize, bpp: usize, compression: Compression) -> Vec<u8> {
+    let mut output = Vec::with_capacity(raw.len() + raw.len() / stride);
+    let mut previous = vec![0; stride];
+    let mut candidate = Vec::with_capacity(stride + 1);
+    let mut best = Vec::with_capacity(stride + 1);
+
+    for row in raw.chunks_exact(stride) {
+        match compression {
+            // filters only help the compressor
+            Compression::Stored => filter_row(0, row, &previous, bpp, &mut output),
+            Compression::Deflate => {
+                let mut best_score = u64::MAX;
+                for filter in 0..5 {
+                    candidate.clear();
+                    filter_row(filter, row, &previous, bpp, &mut candidate);
+                    let score = candidate[1..]
+                        .iter()
+                        .map(|&byte| (byte as i8).unsigned_abs() as u64)
+                        .sum();
+                    if score < best_score {
+                        best_score = score;
+                        std::mem::swap(&mut best, &mut candidate);
+                    }
+                }
+                output.extend_from_slice(&best);
+            }
+        }
+        previous.copy_from_slice(row);
+    }
+
+    output
+}
+
+// RGB without alpha, 8 or 16 bits per channel
+pub fn write_png<W: Write>(canvas: &Canvas, options: PngOptions, writer: W) -> io::Result<()> {
+    write_png_with(canvas, options, &ToneMapping::default(), writer)
+}
+
+pub fn write_png_with<W: Write>(
+    canvas: &Canvas,
+    options: PngOptions,
+    tone_mapping: &ToneMapping,
+    mut writer: W,
+) -> io::Result<()> {
+    if canvas.width == 0 || canvas.height == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "PNG images cannot be empty",
+        ));
+    }
+    if canvas.width > MAX_DIMENSION || canvas.height > MAX_DIMENSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "PNG images are limited to 2147483647 pixels per side",
+        ));
+    }
+
+    let (depth, bytes_per_channel) = match options.bit_depth {
+        BitDepth::Eight => (8, 1),
+        BitDepth::Sixteen => (16, 2),
+    };
+    let bpp = 3 * bytes_per_channel;
+    let stride = canvas.width * bpp;
+
+    let mut raw = Vec::with_capacity(stride * canvas.height);
+    for row in canvas.pixels.iter() {
+        for pixel in row.iter() {
+            let pixel = tone_mapping.apply(*pixel);
+            for &channel in [pixel.red, pixel.green, pixel.blue].iter() {
+                match options.bit_depth {
+                    BitDepth::Eight => raw.push(scale_color(channel, 255) as u8),
+                    BitDepth::Sixteen => {
+                        raw.extend_from_slice(&scale_color(channel, 65535).to_be_bytes())
+                    }
+                }
+            }
+        }
+    }
+    let filtered = filter_image(&raw, stride, bpp, options.compression);
+    let compressed = deflate::zlib_compress(&filtered, options.compression);
+
+    let mut header = vec![];
+    header.extend_from_slice(&(canvas.width as u32).to_be_bytes());
+    header.extend_from_slice(&(canvas.height as u32).to_be_bytes());
+    // bit depth, color type, compression, filter and interlace methods
+    header.extend_from_slice(&[depth, COLOR_RGB, 0, 0, 0]);
+
+    writer.write_all(&SIGNATURE)?;
+    write_chunk(&mut writer, b"IHDR", &header)?;
+    for data in compressed.chunks(IDAT_SIZE) {
+        write_chunk(&mut writer, b"IDAT", data)?;
+    }
+    write_chunk(&mut writer, b"IEND", &[])?;
+
+    writer.flush()
+}
+
+struct Header {
+    width: usize,
+    height: usize,
+    bit_depth: u8,
+    color_type: u8,
+}
+
+impl Header {
+    fn parse(data: &[u8]) -> Result<Header, PngError> {
+        if data.len() != 13 {
+            return invalid("IHDR chunk must be 13 bytes long");
+        }
+        let width = u32::from_be_bytes([data[0], data[1], data[2], data[3]]) as usize;
+        let height = u32::from_be_bytes([data[4], data[5], data[6], data[7]]) as usize;
+        let (bit_depth, color_type) = (data[8], data[9]);
+
+        if width == 0 || height == 0 || width > MAX_DIMENSION || height > MAX_DIMENSION {
+            return invalid(format!("invalid image size {}x{}", width, height));
+        }
+        let allowed: &[u8] = match color_type {
+            COLOR_GRAY => &[1, 2, 4, 8, 16],
+            COLOR_PALETTE => &[1, 2, 4, 8],
+            COLOR_RGB | COLOR_GRAY_ALPHA | COLOR_RGBA => &[8, 16],
+            _ => return invalid(format!("unknown color type {}", color_type)),
+        };
+        if !allowed.contains(&bit_depth) {
+            return invalid(format!(
+                "bit depth {} is not allowed for color type {}",
+                bit_depth, color_type
+            ));
+        }
+        if data[10] != 0 || data[11] != 0 {
+            return invalid("unknown compression or filter method");
+        }
+        match data[12] {
+            0 => {}
+            1 => return invalid("interlaced PNGs are not supported"),
+            method => return invalid(format!("unknown interlace method {}", method)),
+        }
+
+        Ok(Header {
+            width,
+            height,
+            bit_depth,
+            color_type,
+        })
+    }
+
+    fn channels(&self) -> usize {
+        match self.color_type {
+            COLOR_RGB => 3,
+            COLOR_GRAY_ALPHA => 2,
+            COLOR_RGBA => 4,
+            _ => 1,
+        }
+    }
+}
+
+// reads a non-interlaced PNG of any color type; alpha is dropped and samples are not gamma corrected
+pub fn parse_png(data: &[u8]) -> Result<Canvas, PngError> {
+    if !data.starts_with(&SIGNATURE) {
+        return invalid("missing PNG signature");
+    }
+
+    let mut header = None;
+    let mut palette: Option<&[u8]> = None;
+    let mut compressed = vec![];
+    let mut position = SIGNATURE.len();
+    loop {
+        let chunk_header = match data.get(position..position + 8) {
+            Some(bytes) => bytes,
+            None => return invalid("unexpected end of file, missing IEND chunk"),
+        };
+        let length = u32::from_be_bytes([
+            chunk_header[0],
+            chunk_header[1],
+            chunk_header[2],
+            chunk_header[3],
+        ]) as usize;
+        let kind = &chunk_header[4..8];
+        let name = String::from_utf8_lossy(kind);
+        let crc_data = match data.get(position + 4..position + 8 + length) {
+            Some(bytes) => bytes,
+            None => return invalid(format!("{} chunk is truncated", name)),
+        };
+        let crc = match data.get(position + 8 + length..position + 12 + length) {
+            Some(bytes) => u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            None => return invalid(format!("{} chunk is missing its CRC", name)),
+        };
+        if crc != crc32(crc_data) {
+            return invalid(format!("CRC mismatch in {} chunk", name));
+        }
+        let chunk = &crc_data[4..];
+        position += 12 + length;
+
+        if header.is_none() && kind != b"IHDR" {
+            return invalid("the first chunk must be IHDR");
+        }
+        match kind {
+            b"IHDR" if header.is_none() => header = Some(Header::parse(chunk)?),
+            b"IHDR" => return invalid("more than one IHDR chunk"),
+            b"PLTE" => {
+                if chunk.len() % 3 != 0 || chunk.len() > 256 * 3 {
+                    return invalid("PLTE chunk has an invalid length");
+                }
+                palette = Some(chunk);
+            }
+            b"IDAT" => compressed.extend_from_slice(chunk),
+            b"IEND" => break,
+            // ancillary chunks have a lowercase first letter and can be skipped
+            _ if kind[0].is_ascii_lowercase() => {}
+            _ => return invalid(format!("unknown critical chunk {}", name)),
+        }
+    }
+
+    let header = header.unwrap();
+    if header.color_type == COLOR_PALETTE && palette.is_none() {
+        return invalid("missing PLTE chunk");
+    }
+    let bits_per_pixel = header.channels() * header.bit_depth as usize;
+    let stride = match header.width.checked_mul(bits_per_pixel) {
+        Some(bits) => (bits + 7) / 8,
+        None => return invalid("image is too large"),
+    };
+    let bpp = (bits_per_pixel / 8).max(1);
+
+    let mut raw = deflate::zlib_decompress(&compressed)?;
+    match header.height.checked_mul(stride + 1) {
+        Some(expected) if raw.len() >= expected => {}
+        _ => return invalid("not enough image data"),
+    }
+
+    let mut canvas = Canvas::init(header.width, header.height);
+    let mut previous = vec![0; stride];
+    let maxval = ((1u32 << header.bit_depth) - 1) as f64;
+    for (y, line) in raw
+        .chunks_exact_mut(stride + 1)
+        .take(header.height)
+        .enumerate()
+    {
+        let (filter, row) = line.split_first_mut().unwrap();
+        unfilter_row(*filter, row, &previous, bpp)?;
+        previous.copy_from_slice(row);
+
+        let sample = |index: usize| -> usize {
+            match header.bit_depth {
+                16 => (row[2 * index] as usize) << 8 | row[2 * index + 1] as usize,
+                8 => row[index] as usize,
+                depth => {
+                    let bit = index * depth as usize;
+                    let shift = 8 - depth as usize - bit % 8;
+                    (row[bit / 8] as usize >> shift) & ((1 << depth) - 1)
+                }
+            }
+        };
+
+        for x in 0..header.width {
+            let first = x * header.channels();
+            let color = match header.color_type {
+                COLOR_RGB | COLOR_RGBA => Color::new(
+                    sample(first) as f64 / maxval,
+                    sample(first + 1) as f64 / maxval,
+                    sample(first + 2) as f64 / maxval,
+                ),
+                COLOR_PALETTE => {
+                    let palette = palette.unwrap();
+                    let index = sample(first);
+                    match palette.get(3 * index..3 * index + 3) {
+                        Some(entry) => Color::new(
+                            entry[0] as f64 / 255.0,
+                            entry[1] as f64 / 255.0,
+                            entry[2] as f64 / 255.0,
+                        ),
+                        None => return invalid(format!("palette index {} is out of range", index)),
+                    }
+                }
+                _ => {
+                    let gray = sample(first) as f64 / maxval;
+                    Color::new(gray, gray, gray)
+                }
+            };
+            canvas.write_pixel(x, y, color);
+        }
+    }
+
+    Ok(canvas)
+}
+
+pub fn read_png<R: Read>(mut reader: R) -> Result<Canvas, PngError> {
+    let mut data = vec![];
+    reader.read_to_end(&mut data)?;
+    parse_png(&data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tonemap::srgb_to_linear;
+
+    fn gradient(width: usize, height: usize) -> Canvas {
+        let mut canvas = Canvas::init(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                let color = Color::new(
+                    x as f64 / width as f64,
+                    y as f64 / height as f64,
+                    ((x * y) % 7) as f64 / 7.0,
+                );
+                canvas.write_pixel(x, y, color);
+            }
+        }
+        canvas
+    }
+
+    fn encode(canvas: &Canvas, bit_depth: BitDepth, compression: Compression) -> Vec<u8> {
+        let mut png = vec![];
+        let options = PngOptions {
+            bit_depth,
+            compression,
+        };
+        write_png(canvas, options, &mut png).unwrap();
+        png
+    }
+
+    // a PNG made of the given chunks, with valid CRCs
+    fn build_png(chunks: &[(&[u8; 4], Vec<u8>)]) -> Vec<u8> {
+        let mut png = SIGNATURE.to_vec();
+        for (kind, data) in chunks.iter() {
+            write_chunk(&mut png, kind, data).unwrap();
+        }
+        png
+    }
+
+    fn header(width: u32, height: u32, bit_depth: u8, color_type: u8) -> Vec<u8> {
+        let mut data = width.to_be_bytes().to_vec();
+        data.extend_from_slice(&height.to_be_bytes());
+        data.extend_from_slice(&[bit_depth, color_type, 0, 0, 0]);
+        data
+    }
+
+    fn assert_invalid(data: &[u8], message: &str) {
+        match parse_png(data) {
+            Err(PngError::Invalid(error)) => assert_eq!(error, message),
+            Err(error) => panic!("unexpected error {}", error),
+            Ok(_) => panic!("expected an error"),
+        }
+    }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"IEND"), 0xae42_6082);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn test_write_png_layout() {
+        let png = encode(&Canvas::init(3, 2), BitDepth::Eight, Compression::Stored);
+
+        assert_eq!(&png[..8], &SIGNATURE);
+        assert_eq!(&png[8..16], &[0, 0, 0, 13, b'I', b'H', b'D', b'R']);
+        assert_eq!(&png[16..29], &header(3, 2, 8, COLOR_RGB)[..]);
+        assert_eq!(
+            &png[png.len() - 12..],
+            &[0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xae, 0x42, 0x60, 0x82]
+        );
+    }
+
+    #[test]
+    fn test_write_png_rejects_empty_canvas() {
+        let mut png = vec![];
+
+        assert!(write_png(&Canvas::init(0, 3), PngOptions::default(), &mut png).is_err());
+    }
+
+    #[test]
+    fn test_round_trip_eight_bit() {
+        let canvas = gradient(37, 23);
+
+        for &compression in [Compression::Stored, Compression::Deflate].iter() {
+            let png = encode(&canvas, BitDepth::Eight, compression);
+            let read = parse_png(&png).unwrap();
+
+            assert_eq!((read.width, read.height), (37, 23));
+            for y in 0..23 {
+                for x in 0..37 {
+                    let expected = canvas.pixel_at(x, y);
+                    let actual = read.pixel_at(x, y);
+                    assert!((expected.red - actual.red).abs() < 1.0 / 255.0);
+                    assert!((expected.green - actual.green).abs() < 1.0 / 255.0);
+                    assert!((expected.blue - actual.blue).abs() < 1.0 / 255.0);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_round_trip_sixteen_bit() {
+        let mut canvas = gradient(5, 4);
+        canvas.write_pixel(0, 0, Color::new(0.123456, 1.5, -0.5));
+
+        let png = encode(&canvas, BitDepth::Sixteen, Compression::Deflate);
+        let read = parse_png(&png).unwrap();
+
+        assert!((read.pixel_at(0, 0).red - 0.123456).abs() < 1.0 / 65535.0);
+        assert_eq!(read.pixel_at(0, 0).green, 1.0);
+        assert_eq!(read.pixel_at(0, 0).blue, 0.0);
+        assert_eq!(read.pixel_at(4, 3), canvas.pixel_at(4, 3));
+    }
+
+    #[test]
+    fn test_write_png_with_tone_mapping() {
+        let canvas = gradient(6, 4);
+        let tone_mapping = ToneMapping {
+            srgb: true,
+            ..ToneMapping::default()
+        };
+
+        let mut png = vec![];
+        let options = PngOptions {
+            bit_depth: BitDepth::Sixteen,
+            ..PngOptions::default()
+        };
+        write_png_with(&canvas, options, &tone_mapping, &mut png).unwrap();
+        let read = parse_png(&png).unwrap();
+
+        for y in 0..4 {
+            for x in 0..6 {
+                let expected = canvas.pixel_at(x, y);
+                let actual = read.pixel_at(x, y);
+                assert!((srgb_to_linear(actual.red) - expected.red).abs() < 1e-4);
+                assert!((srgb_to_linear(actual.green) - expected.green).abs() < 1e-4);
+                assert!((srgb_to_linear(actual.blue) - expected.blue).abs() < 1e-4);
+            }
+        }
+        assert!(read.pixel_at(1, 1).red > canvas.pixel_at(1, 1).red);
+    }
+
+    #[test]
+    fn test_deflate_is_smaller_than_stored() {
+        let canvas = gradient(64, 64);
+
+        let stored = encode(&canvas, BitDepth::Eight, Compression::Stored);
+        let deflated = encode(&canvas, BitDepth::Eight, Compression::Deflate);
+
+        assert!(deflated.len() < stored.len() / 2);
+    }
+
+    #[test]
+    fn test_filters_round_trip() {
+        let previous = [10, 200, 30, 40, 250, 60];
+        let row = [15, 5, 35, 255, 0, 128];
+
+        for filter in 0..5 {
+            let mut filtered = vec![];
+            filter_row(filter, &row, &previous, 3, &mut filtered);
+            assert_eq!(filtered[0], filter);
+
+            let mut unfiltered = filtered[1..].to_vec();
+            unfilter_row(filter, &mut unfiltered, &previous, 3).unwrap();
+            assert_eq!(unfiltered, row);
+        }
+    }
+
+    #[test]
+    fn test_parse_grayscale_with_low_bit_depth() {
+        // two rows of four 2-bit pixels: 0, 1, 2, 3 and 3, 2, 1, 0
+        let data =
+            deflate::zlib_compress(&[0, 0b00_01_10_11, 0, 0b11_10_01_00], Compression::Stored);
+        let png = build_png(&[
+            (b"IHDR", header(4, 2, 2, COLOR_GRAY)),
+            (b"IDAT", data),
+            (b"IEND", vec![]),
+        ]);
+
+        let canvas = parse_png(&png).unwrap();
+
+        assert_eq!(*canvas.pixel_at(0, 0), Color::new(0.0, 0.0, 0.0));
+        assert_eq!(*canvas.pixel_at(1, 0), Color::new(0.333, 0.333, 0.333));
+        assert_eq!(*canvas.pixel_at(3, 0), Color::new(1.0, 1.0, 1.0));
+        assert_eq!(*canvas.pixel_at(1, 1), Color::new(0.667, 0.667, 0.667));
+    }
+
+    #[test]
+    fn test_parse_palette() {
+        let data = deflate::zlib_compress(&[0, 0b0001_0000], Compression::Deflate);
+        let png = build_png(&[
+            (b"IHDR", header(2, 1, 4, COLOR_PALETTE)),
+            (b"PLTE", vec![255, 0, 0, 0, 0, 255]),
+            (b"tEXt", b"Comment\0skipped".to_vec()),
+            (b"IDAT", data),
+            (b"IEND", vec![]),
+        ]);
+
+        let canvas = parse_png(&png).unwrap();
+
+        assert_eq!(*canvas.pixel_at(0, 0), Color::new(0.0, 0.0, 1.0));
+        assert_eq!(*canvas.pixel_at(1, 0), Color::new(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_parse_rgba_drops_alpha_and_accepts_split_idat() {
+        let data = deflate::zlib_compress(&[1, 255, 0, 0, 0, 1, 255, 0, 255], Compression::Deflate);
+        let (first, second) = data.split_at(3);
+        let png = build_png(&[
+            (b"IHDR", header(2, 1, 8, COLOR_RGBA)),
+            (b"IDAT", first.to_vec()),
+            (b"IDAT", second.to_vec()),
+            (b"IEND", vec![]),
+        ]);
+
+        let canvas = parse_png(&png).unwrap();
+
+        // the sub filter adds the previous pixel, alpha included
+        assert_eq!(*canvas.pixel_at(0, 0), Color::new(1.0, 0.0, 0.0));
+        assert_eq!(*canvas.pixel_at(1, 0), Color::new(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let valid = encode(&Canvas::init(2, 2), BitDepth::Eight, Compression::Deflate);
+
+        assert_invalid(b"GIF89a", "missing PNG signature");
+        assert_invalid(
+            &valid[..valid.len() - 12],
+            "unexpected end of file, missing IEND chunk",
+        );
+
+        let mut corrupt = valid.clone();
+        corrupt[20] ^= 1;
+        assert_invalid(&corrupt, "CRC mismatch in IHDR chunk");
+
+        let mut interlaced = header(2, 2, 8, COLOR_RGB);
+        interlaced[12] = 1;
+        assert_invalid(
+            &build_png(&[(b"IHDR", interlaced)]),
+            "interlaced PNGs are not supported",
+        );
+        assert_invalid(
+            &build_png(&[(b"IHDR", header(2, 2, 4, COLOR_RGB))]),
+            "bit depth 4 is not allowed for color type 2",
+        );
+        assert_invalid(
+            &build_png(&[(b"IDAT", vec![]), (b"IEND", vec![])]),
+            "the first chunk must be IHDR",
+        );
+        assert_invalid(
+            &build_png(&[(b"IHDR", header(2, 2, 8, COLOR_RGB)), (b"ABCD", vec![])]),
+            "unknown critical chunk ABCD",
+        );
+        assert_invalid(
+            &build_png(&[
+                (b"IHDR", header(2, 2, 8, COLOR_RGB)),
+                (
+                    b"IDAT",
+                    deflate::zlib_compress(&[0; 7], Compression::Stored),
+                ),
+                (b"IEND", vec![]),
+            ]),
+            "not enough image data",
+        );
+    }
+}
@@ -1,41 +1,303 @@
+use std::fmt;
+use std::io::{self, Read, Write};
+
 use crate::canvas::Canvas;
+use crate::color::Color;
+use crate::tonemap::ToneMapping;
 
-fn scale_color(color: f64) -> i32 {
-    let rs = (color * 255.0).ceil();
+// some readers refuse P3 lines longer than this
+const MAX_LINE_LENGTH: usize = 70;
 
-    if rs > 255.0 {
-        255
-    } else if rs < 0.0 {
-        0
-    } else {
-        rs as i32
+// maps an encoded 0.0..=1.0 onto the nearest of 0..=maxval, clamping anything outside
+pub(crate) fn scale_color(color: f64, maxval: u16) -> u16 {
+    (color.clamp(0.0, 1.0) * maxval as f64).round() as u16
+}
+
+// parse_ppm rejects images without pixels, so they are not written either
+fn check_not_empty(canvas: &Canvas) -> io::Result<()> {
+    if canvas.width == 0 || canvas.height == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "PPM images cannot be empty",
+        ));
     }
+    Ok(())
 }
 
+// panics when the canvas is empty
 pub fn canvas_to_ppm(canvas: Canvas) -> String {
+    canvas_to_ppm_with(&canvas, &ToneMapping::default())
+}
+
+pub fn canvas_to_ppm_with(canvas: &Canvas, tone_mapping: &ToneMapping) -> String {
     let mut ppm = vec![];
-    ppm.push("P3".to_string());
-    ppm.push(format!("{} {}", canvas.width, canvas.height));
-    ppm.push("255".to_string());
+    write_ppm_with(canvas, tone_mapping, &mut ppm).expect("PPM images cannot be empty");
+
+    String::from_utf8(ppm).expect("P3 output is ASCII")
+}
+
+// plain P3, with lines wrapped at 70 characters
+pub fn write_ppm<W: Write>(canvas: &Canvas, writer: W) -> io::Result<()> {
+    write_ppm_with(canvas, &ToneMapping::default(), writer)
+}
+
+pub fn write_ppm_with<W: Write>(
+    canvas: &Canvas,
+    tone_mapping: &ToneMapping,
+    mut writer: W,
+) -> io::Result<()> {
+    check_not_empty(canvas)?;
+    write!(writer, "P3\n{} {}\n255\n", canvas.width, canvas.height)?;
+
+    let mut line = String::with_capacity(MAX_LINE_LENGTH + 1);
+    for row in canvas.pixels.iter() {
+        for pixel in row.iter() {
+            let pixel = tone_mapping.apply(*pixel);
+            for value in [pixel.red, pixel.green, pixel.blue].iter() {
+                let value = scale_color(*value, 255).to_string();
+
+                if !line.is_empty() && line.len() + 1 + value.len() > MAX_LINE_LENGTH {
+                    line.push('\n');
+                    writer.write_all(line.as_bytes())?;
+                    line.clear();
+                }
+                if !line.is_empty() {
+                    line.push(' ');
+                }
+                line.push_str(&value);
+            }
+        }
+
+        // every row starts on a new line
+        line.push('\n');
+        writer.write_all(line.as_bytes())?;
+        line.clear();
+    }
+
+    writer.flush()
+}
+
+// binary P6, one byte per channel
+pub fn write_ppm_binary<W: Write>(canvas: &Canvas, writer: W) -> io::Result<()> {
+    write_ppm_binary_with(canvas, &ToneMapping::default(), writer)
+}
+
+pub fn write_ppm_binary_with<W: Write>(
+    canvas: &Canvas,
+    tone_mapping: &ToneMapping,
+    mut writer: W,
+) -> io::Result<()> {
+    check_not_empty(canvas)?;
+    write!(writer, "P6\n{} {}\n255\n", canvas.width, canvas.height)?;
+
+    let mut bytes = Vec::with_capacity(canvas.width * 3);
+    for row in canvas.pixels.iter() {
+        bytes.clear();
+        for pixel in row.iter() {
+            let pixel = tone_mapping.apply(*pixel);
+            bytes.push(scale_color(pixel.red, 255) as u8);
+            bytes.push(scale_color(pixel.green, 255) as u8);
+            bytes.push(scale_color(pixel.blue, 255) as u8);
+        }
+        writer.write_all(&bytes)?;
+    }
+
+    writer.flush()
+}
+
+#[derive(Debug)]
+pub enum PpmError {
+    Io(io::Error),
+    Invalid(String),
+}
+
+impl fmt::Display for PpmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PpmError::Io(error) => write!(f, "{}", error),
+            PpmError::Invalid(message) => write!(f, "invalid PPM: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for PpmError {}
+
+impl From<io::Error> for PpmError {
+    fn from(error: io::Error) -> Self {
+        PpmError::Io(error)
+    }
+}
+
+fn invalid<T>(message: impl Into<String>) -> Result<T, PpmError> {
+    Err(PpmError::Invalid(message.into()))
+}
+
+// splits the header of netpbm style formats into tokens
+pub(crate) struct Scanner<'a> {
+    data: &'a [u8],
+    pub(crate) position: usize,
+}
 
-    for line in canvas.pixels.iter() {
-        let mut ppm_line = vec![];
+impl<'a> Scanner<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Scanner<'a> {
+        Scanner { data, position: 0 }
+    }
+
+    // skips whitespace and comments, which run from # to the end of the line
+    fn skip_separators(&mut self) {
+        while let Some(&byte) = self.data.get(self.position) {
+            if byte == b'#' {
+                while self.position < self.data.len() && !is_newline(self.data[self.position]) {
+                    self.position += 1;
+                }
+            } else if is_whitespace(byte) {
+                self.position += 1;
+            } else {
+                break;
+            }
+        }
+    }
+
+    pub(crate) fn token(&mut self) -> Option<&'a [u8]> {
+        self.skip_separators();
+        let start = self.position;
+        while self.position < self.data.len()
+            && !is_whitespace(self.data[self.position])
+            && self.data[self.position] != b'#'
+        {
+            self.position += 1;
+        }
+
+        if start == self.position {
+            None
+        } else {
+            Some(&self.data[start..self.position])
+        }
+    }
+
+    fn number(&mut self, what: &str) -> Result<usize, PpmError> {
+        match self.token() {
+            Some(token) => parse_number(token, what),
+            None => invalid(format!("expected {}, found end of file", what)),
+        }
+    }
+}
+
+fn parse_number(token: &[u8], what: &str) -> Result<usize, PpmError> {
+    match std::str::from_utf8(token).ok().and_then(|t| t.parse().ok()) {
+        Some(number) => Ok(number),
+        None => invalid(format!(
+            "expected {}, found `{}`",
+            what,
+            String::from_utf8_lossy(token)
+        )),
+    }
+}
+
+pub(crate) fn is_whitespace(byte: u8) -> bool {
+    matches!(byte, b' ' | b'\t' | b'\n' | b'\r' | 0x0b | 0x0c)
+}
+
+fn is_newline(byte: u8) -> bool {
+    byte == b'\n' || byte == b'\r'
+}
 
-        for pixel in line.iter() {
-            ppm_line.push(format!("{}", scale_color(pixel.red)));
-            ppm_line.push(format!("{}", scale_color(pixel.green)));
-            ppm_line.push(format!("{}", scale_color(pixel.blue)));
+fn sample(value: usize, maxval: usize) -> Result<f64, PpmError> {
+    if value > maxval {
+        return invalid(format!(
+            "sample {} is greater than maxval {}",
+            value, maxval
+        ));
+    }
+    Ok(value as f64 / maxval as f64)
+}
+
+// reads a P3 or P6 image, with channels scaled by maxval into 0.0..=1.0
+pub fn parse_ppm(data: &[u8]) -> Result<Canvas, PpmError> {
+    let mut scanner = Scanner::new(data);
+
+    let binary = match scanner.token() {
+        Some(b"P3") => false,
+        Some(b"P6") => true,
+        Some(magic) => {
+            return invalid(format!(
+                "unsupported magic number `{}`, expected P3 or P6",
+                String::from_utf8_lossy(magic)
+            ))
+        }
+        None => return invalid("empty file"),
+    };
+    let width = scanner.number("width")?;
+    let height = scanner.number("height")?;
+    let maxval = scanner.number("maxval")?;
+    if maxval == 0 || maxval > 65535 {
+        return invalid(format!("maxval {} is not between 1 and 65535", maxval));
+    }
+    // a zero side passes the data length checks whatever the other side is,
+    // and Canvas::init would still allocate a row for every line of it
+    if width == 0 || height == 0 {
+        return invalid(format!("image size {}x{} is empty", width, height));
+    }
+
+    let samples = match width.checked_mul(height).and_then(|n| n.checked_mul(3)) {
+        Some(samples) => samples,
+        None => return invalid(format!("image size {}x{} is too large", width, height)),
+    };
+    let mut values = Vec::with_capacity(samples.min(data.len()));
+
+    if binary {
+        // a single whitespace character separates maxval from the raster
+        match data.get(scanner.position) {
+            Some(&byte) if is_whitespace(byte) => scanner.position += 1,
+            _ => return invalid("expected whitespace after maxval"),
+        }
+        let bytes_per_sample = if maxval < 256 { 1 } else { 2 };
+        let raster = &data[scanner.position..];
+        if raster.len() / bytes_per_sample < samples {
+            return invalid(format!(
+                "expected {} bytes of pixel data, found {}",
+                samples * bytes_per_sample,
+                raster.len()
+            ));
         }
-        ppm.push(ppm_line.join(" "));
+
+        for chunk in raster.chunks_exact(bytes_per_sample).take(samples) {
+            let value = chunk
+                .iter()
+                .fold(0, |value, &byte| value << 8 | byte as usize);
+            values.push(sample(value, maxval)?);
+        }
+    } else {
+        for index in 0..samples {
+            let value = match scanner.token() {
+                Some(token) => parse_number(token, "sample")?,
+                None => return invalid(format!("expected {} samples, found {}", samples, index)),
+            };
+            values.push(sample(value, maxval)?);
+        }
+    }
+
+    let mut canvas = Canvas::init(width, height);
+    for (index, color) in values.chunks_exact(3).enumerate() {
+        canvas.write_pixel(
+            index % width,
+            index / width,
+            Color::new(color[0], color[1], color[2]),
+        );
     }
 
-    ppm.join("\n")
+    Ok(canvas)
+}
+
+pub fn read_ppm<R: Read>(mut reader: R) -> Result<Canvas, PpmError> {
+    let mut data = vec![];
+    reader.read_to_end(&mut data)?;
+    parse_ppm(&data)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::color::Color;
 
     #[test]
     fn test_canvas_to_ppm() {
@@ -73,4 +335,245 @@ mod tests {
         assert_eq!(Some("0 0 0 0 0 0 0 128 0 0 0 0 0 0 0"), lines.next());
         assert_eq!(Some("0 0 0 0 0 0 0 0 0 0 0 0 0 0 255"), lines.next());
     }
+
+    #[test]
+    fn test_canvas_to_ppm_splits_long_lines() {
+        let mut canvas = Canvas::init(10, 2);
+        for y in 0..2 {
+            for x in 0..10 {
+                canvas.write_pixel(x, y, Color::new(1.0, 0.2, 0.6));
+            }
+        }
+
+        let string = canvas_to_ppm(canvas);
+
+        let lines: Vec<&str> = string.lines().skip(3).collect();
+        assert_eq!(
+            lines,
+            vec![
+                "255 51 153 255 51 153 255 51 153 255 51 153 255 51 153 255 51 153 255",
+                "51 153 255 51 153 255 51 153 255 51 153",
+                "255 51 153 255 51 153 255 51 153 255 51 153 255 51 153 255 51 153 255",
+                "51 153 255 51 153 255 51 153 255 51 153",
+            ]
+        );
+        assert!(lines.iter().all(|line| line.len() <= 70));
+    }
+
+    #[test]
+    fn test_canvas_to_ppm_ends_with_newline() {
+        let canvas = Canvas::init(5, 3);
+
+        let string = canvas_to_ppm(canvas);
+
+        assert!(string.ends_with('\n'));
+    }
+
+    #[test]
+    fn test_write_ppm_binary() {
+        let mut canvas = Canvas::init(2, 2);
+        canvas.write_pixel(0, 0, Color::new(1.5, 0.0, 0.0));
+        canvas.write_pixel(1, 1, Color::new(0.0, 0.5, -1.0));
+
+        let mut ppm = vec![];
+        write_ppm_binary(&canvas, &mut ppm).unwrap();
+
+        let mut expected = b"P6\n2 2\n255\n".to_vec();
+        expected.extend_from_slice(&[255, 0, 0, 0, 0, 0, 0, 0, 0, 0, 128, 0]);
+        assert_eq!(ppm, expected);
+    }
+
+    #[test]
+    fn test_write_ppm_does_not_consume_canvas() {
+        let canvas = Canvas::init(3, 1);
+
+        let mut first = vec![];
+        write_ppm(&canvas, &mut first).unwrap();
+        let mut second = vec![];
+        write_ppm(&canvas, &mut second).unwrap();
+
+        assert_eq!(first, second);
+        assert_eq!(first, b"P3\n3 1\n255\n0 0 0 0 0 0 0 0 0\n".to_vec());
+    }
+
+    #[test]
+    fn test_scale_color_rounds() {
+        assert_eq!(scale_color(0.001, 255), 0);
+        assert_eq!(scale_color(0.003, 255), 1);
+        assert_eq!(scale_color(0.5, 255), 128);
+        assert_eq!(scale_color(0.998, 255), 254);
+        assert_eq!(scale_color(1.5, 255), 255);
+        assert_eq!(scale_color(-0.5, 255), 0);
+        assert_eq!(scale_color(0.5, 65535), 32768);
+    }
+
+    #[test]
+    fn test_writing_with_tone_mapping() {
+        let mut canvas = Canvas::init(2, 1);
+        canvas.write_pixel(0, 0, Color::new(3.0, 0.0, -1.0));
+        canvas.write_pixel(1, 0, Color::new(0.4, 0.4, 0.4));
+        let tone_mapping = ToneMapping {
+            exposure: -1.0,
+            srgb: true,
+            ..ToneMapping::default()
+        };
+
+        let mut binary = vec![];
+        write_ppm_binary_with(&canvas, &tone_mapping, &mut binary).unwrap();
+        let plain = canvas_to_ppm_with(&canvas, &tone_mapping);
+
+        // 0.4 halves to 0.2, which sRGB encodes as 0.48 rather than the linear 51
+        assert_eq!(
+            &binary[b"P6\n2 1\n255\n".len()..],
+            &[255, 0, 0, 124, 124, 124]
+        );
+        assert_eq!(plain, "P3\n2 1\n255\n255 0 0 124 124 124\n");
+    }
+
+    #[test]
+    fn test_writing_empty_canvas_fails() {
+        for &(width, height) in [(0, 3), (3, 0)].iter() {
+            let canvas = Canvas::init(width, height);
+            let mut ppm = vec![];
+
+            let plain = write_ppm(&canvas, &mut ppm).unwrap_err();
+            let binary = write_ppm_binary(&canvas, &mut ppm).unwrap_err();
+
+            assert_eq!(plain.kind(), io::ErrorKind::InvalidInput);
+            assert_eq!(binary.kind(), io::ErrorKind::InvalidInput);
+            assert!(ppm.is_empty());
+        }
+    }
+
+    fn assert_invalid(data: &[u8], message: &str) {
+        match parse_ppm(data) {
+            Err(PpmError::Invalid(error)) => assert_eq!(error, message),
+            Err(error) => panic!("unexpected error {}", error),
+            Ok(_) => panic!("expected an error for {:?}", data),
+        }
+    }
+
+    #[test]
+    fn test_parse_ppm_rejects_wrong_magic_number() {
+        assert_invalid(
+            b"P32\n1 1\n255\n0 0 0",
+            "unsupported magic number `P32`, expected P3 or P6",
+        );
+        assert_invalid(b"", "empty file");
+    }
+
+    #[test]
+    fn test_parse_ppm_reads_dimensions() {
+        let canvas = parse_ppm(b"P3\n2 1\n255\n0 0 0 0 0 0\n").unwrap();
+        assert_eq!(canvas.width, 2);
+        assert_eq!(canvas.height, 1);
+    }
+
+    #[test]
+    fn test_parse_ppm_reads_pixel_data() {
+        let canvas = parse_ppm(
+            b"P3\n4 3\n255\n\
+            255 127 0  0 127 255  127 255 0  255 255 255\n\
+            0 0 0  255 0 0  0 255 0  0 0 255\n\
+            255 255 0  0 255 255  255 0 255  127 127 127\n",
+        )
+        .unwrap();
+
+        assert_eq!(*canvas.pixel_at(0, 0), Color::new(1.0, 0.498, 0.0));
+        assert_eq!(*canvas.pixel_at(1, 0), Color::new(0.0, 0.498, 1.0));
+        assert_eq!(*canvas.pixel_at(3, 0), Color::new(1.0, 1.0, 1.0));
+        assert_eq!(*canvas.pixel_at(1, 1), Color::new(1.0, 0.0, 0.0));
+        assert_eq!(*canvas.pixel_at(3, 2), Color::new(0.498, 0.498, 0.498));
+    }
+
+    #[test]
+    fn test_parse_ppm_ignores_comments() {
+        let canvas = parse_ppm(
+            b"P3 # plain\n# this is a comment\n2 1\n# this, too\n255\n\
+            255 255 255\n# another comment\n255 0 255\n",
+        )
+        .unwrap();
+
+        assert_eq!(*canvas.pixel_at(0, 0), Color::new(1.0, 1.0, 1.0));
+        assert_eq!(*canvas.pixel_at(1, 0), Color::new(1.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn test_parse_ppm_allows_triples_to_span_lines() {
+        let canvas = parse_ppm(b"P3\r\n1 1\r\n255\r\n51\r\n153\t\r\n\r\n204\r\n").unwrap();
+
+        assert_eq!(*canvas.pixel_at(0, 0), Color::new(0.2, 0.6, 0.8));
+    }
+
+    #[test]
+    fn test_parse_ppm_respects_maxval() {
+        let canvas = parse_ppm(b"P3\n2 2\n100\n100 100 100  50 50 50\n75 50 25  0 0 0\n").unwrap();
+
+        assert_eq!(*canvas.pixel_at(0, 1), Color::new(0.75, 0.5, 0.25));
+    }
+
+    #[test]
+    fn test_parse_ppm_binary() {
+        let mut data = b"P6 # binary\n2 1 255\n".to_vec();
+        data.extend_from_slice(&[255, 0, 51, 0, 102, 255]);
+
+        let canvas = parse_ppm(&data).unwrap();
+
+        assert_eq!(*canvas.pixel_at(0, 0), Color::new(1.0, 0.0, 0.2));
+        assert_eq!(*canvas.pixel_at(1, 0), Color::new(0.0, 0.4, 1.0));
+    }
+
+    #[test]
+    fn test_parse_ppm_binary_with_two_byte_samples() {
+        let mut data = b"P6\n1 1\n65535\n".to_vec();
+        data.extend_from_slice(&[0xff, 0xff, 0x80, 0x00, 0x00, 0x00]);
+
+        let canvas = parse_ppm(&data).unwrap();
+
+        assert_eq!(*canvas.pixel_at(0, 0), Color::new(1.0, 0.5, 0.0));
+    }
+
+    #[test]
+    fn test_parse_ppm_reports_errors() {
+        assert_invalid(b"P3\n2", "expected height, found end of file");
+        assert_invalid(b"P3\n2 x\n255\n", "expected height, found `x`");
+        assert_invalid(b"P3\n1 1\n0\n", "maxval 0 is not between 1 and 65535");
+        assert_invalid(
+            b"P6 0 1000000000000 255\n",
+            "image size 0x1000000000000 is empty",
+        );
+        assert_invalid(b"P3\n3 0\n255\n", "image size 3x0 is empty");
+        assert_invalid(b"P3\n1 1\n255\n0 0", "expected 3 samples, found 2");
+        assert_invalid(
+            b"P3\n1 1\n255\n0 256 0",
+            "sample 256 is greater than maxval 255",
+        );
+        assert_invalid(
+            b"P6\n2 1\n255\n\x00\x00\x00",
+            "expected 6 bytes of pixel data, found 3",
+        );
+    }
+
+    #[test]
+    fn test_ppm_round_trip() {
+        let mut canvas = Canvas::init(3, 2);
+        canvas.write_pixel(0, 0, Color::new(1.0, 0.2, 0.4));
+        canvas.write_pixel(2, 1, Color::new(0.0, 0.6, 0.8));
+
+        for binary in [false, true].iter() {
+            let mut data = vec![];
+            if *binary {
+                write_ppm_binary(&canvas, &mut data).unwrap();
+            } else {
+                write_ppm(&canvas, &mut data).unwrap();
+            }
+
+            let read = read_ppm(&data[..]).unwrap();
+            for y in 0..2 {
+                for x in 0..3 {
+                    assert_eq!(read.pixel_at(x, y), canvas.pixel_at(x, y));
+                }
+            }
+        }
+    }
 }
@@ -1,8 +1,15 @@
-use crate::material::*;
 use crate::matrix::*;
-use crate::point;
+use crate::shape::Shape;
 use crate::tuple::*;
 
+// How far over_point and under_point are moved away from the surface, small
+// enough not to be visible but large enough to avoid self intersections.
+// EPSILON (0.001) was too far: shadow rays started beyond anything closer
+// than that to a surface, so thin objects lying on a floor let light
+// through, and refracted rays started deep enough inside glass to shift
+// the refracted color (see the world tests).
+pub const SURFACE_EPSILON: f64 = 0.0001;
+
 #[derive(Debug)]
 pub struct Ray {
     pub origin: Tuple,
@@ -18,103 +25,114 @@ impl Ray {
         self.origin + (self.direction * time)
     }
 
-    pub fn transform(&self, transformation: &Matrix) -> Self {
+    pub fn transform(&self, transformation: &Matrix4) -> Self {
         Self {
             origin: transformation * self.origin,
             direction: transformation * self.direction,
         }
     }
 
-    pub fn intersect<'a>(&'a self, sphere: &'a Sphere) -> Vec<Intersection> {
-        let transformed = self.transform(&sphere.transform.inverse());
-        let sphere_to_ray = transformed.origin - point!(0.0, 0.0, 0.0);
-
-        let a = transformed.direction * transformed.direction;
-        let b = 2.0 * (transformed.direction * sphere_to_ray);
-
-        // let a = self.direction * self.direction;
-        // let b = 2.0 * (self.direction * sphere_to_ray);
-
-        let c = (sphere_to_ray * sphere_to_ray) - 1.0;
-        let discriminant = (b * b) - (4.0 * a * c);
-
-        if discriminant < 0.0 {
-            vec![]
-        } else {
-            let t1 = (-b - discriminant.sqrt()) / (2.0 * a);
-            let t2 = (-b + discriminant.sqrt()) / (2.0 * a);
-
-            let a = Intersection {
-                t: t1,
-                object: sphere,
-            };
-            let b = Intersection {
-                t: t2,
-                object: sphere,
-            };
-
-            vec![a, b]
-        }
+    pub fn intersect<'a>(&self, shape: &'a dyn Shape) -> Vec<Intersection<'a>> {
+        let local_ray = self.transform(&shape.transform().inverse());
+        shape.local_intersect(&local_ray)
     }
 }
 
-#[derive(Debug, PartialEq)]
-pub struct Sphere {
-    pub transform: Matrix,
-    pub material: Material,
+#[derive(Debug, PartialEq, Clone)]
+pub struct Intersection<'a> {
+    pub t: f64,
+    pub object: &'a dyn Shape,
+    // where on a triangle the intersection happened, in barycentric
+    // coordinates. Zero for every other shape.
+    pub u: f64,
+    pub v: f64,
 }
 
-impl Sphere {
-    pub fn normal_at(&self, world_point: Tuple) -> Tuple {
-        //        (point - point!(0.0, 0.0, 0.0)).norm()
-
-        let object_point = self.transform.inverse() * world_point;
-        let object_normal = object_point - point!(0.0, 0.0, 0.0);
-        let mut world_normal = self.transform.inverse().transpose() * object_normal;
-        // hacky
-        world_normal.w = 0.0;
-        world_normal.norm()
+impl<'a> Intersection<'a> {
+    pub fn new(t: f64, object: &'a dyn Shape) -> Self {
+        Self::with_uv(t, object, 0.0, 0.0)
     }
 
-    pub fn init() -> Sphere {
-        Sphere {
-            transform: Matrix::identity(4),
-            material: Material::default(),
-        }
+    pub fn with_uv(t: f64, object: &'a dyn Shape, u: f64, v: f64) -> Self {
+        Self { t, object, u, v }
     }
 
-    //    pub fn set_transform(&mut self, matrix: Matrix) {
-    //    }
-}
-
-#[derive(Debug, PartialEq, Clone)]
-pub struct Intersection<'a> {
-    pub t: f64,
-    pub object: &'a Sphere,
-}
-
-impl Intersection<'_> {
-    pub fn prepare_computations(&self, ray: &Ray) -> Computation {
+    // intersections is the full sorted list the hit came from, which is needed
+    // to know the refractive indices on both sides of the surface
+    pub fn prepare_computations(
+        &self,
+        ray: &Ray,
+        intersections: &[Intersection],
+    ) -> Computation<'a> {
         let point = ray.position(self.t);
         let eyev = ray.direction.negate();
-        let mut normalv = self.object.normal_at(point);
+        let mut normalv = self.object.normal_at_hit(point, self);
         let mut inside = false;
         if normalv * eyev < 0.0 {
             inside = true;
             normalv = normalv.negate();
         }
+        // nudge the point above the surface so it does not shadow itself,
+        // and below it to start refracted rays
+        let over_point = point + normalv * SURFACE_EPSILON;
+        let under_point = point - normalv * SURFACE_EPSILON;
+        let reflectv = ray.direction.reflect(normalv);
+        let (n1, n2) = self.refractive_indices(intersections);
         Computation {
             t: self.t,
-            object: &self.object,
+            object: self.object,
             point,
+            over_point,
+            under_point,
             eyev,
             normalv,
+            reflectv,
             inside,
+            n1,
+            n2,
         }
     }
+
+    // walks the intersections up to this one keeping track of which objects
+    // the ray is currently inside of, to find the refractive index of the
+    // material being exited (n1) and the one being entered (n2)
+    fn refractive_indices(&self, intersections: &[Intersection]) -> (f64, f64) {
+        let mut containers: Vec<&dyn Shape> = vec![];
+        let mut n1 = 1.0;
+        let mut n2 = 1.0;
+
+        for intersection in intersections {
+            let is_hit = intersection == self;
+
+            if is_hit {
+                n1 = containers
+                    .last()
+                    .map_or(1.0, |object| object.material().refractive_index);
+            }
+
+            match containers
+                .iter()
+                .position(|object| *object == intersection.object)
+            {
+                Some(index) => {
+                    containers.remove(index);
+                }
+                None => containers.push(intersection.object),
+            }
+
+            if is_hit {
+                n2 = containers
+                    .last()
+                    .map_or(1.0, |object| object.material().refractive_index);
+                break;
+            }
+        }
+
+        (n1, n2)
+    }
 }
 
-pub fn hit<'a>(intersections: &'a Vec<Intersection>) -> Option<&'a Intersection<'a>> {
+pub fn hit<'a, 'b>(intersections: &'b [Intersection<'a>]) -> Option<&'b Intersection<'a>> {
     // take the first positive intersection
     // and return the minimum of those
     intersections
@@ -125,11 +143,38 @@ pub fn hit<'a>(intersections: &'a Vec<Intersection>) -> Option<&'a Intersection<
 
 pub struct Computation<'a> {
     pub t: f64,
-    pub object: &'a Sphere,
+    pub object: &'a dyn Shape,
     pub point: Tuple,
+    pub over_point: Tuple,
+    pub under_point: Tuple,
     pub eyev: Tuple,
     pub normalv: Tuple,
+    pub reflectv: Tuple,
     pub inside: bool,
+    pub n1: f64,
+    pub n2: f64,
+}
+
+impl Computation<'_> {
+    // Schlick's approximation of the Fresnel effect: the fraction of light
+    // that is reflected rather than refracted at this point
+    pub fn schlick(&self) -> f64 {
+        let mut cos = self.eyev * self.normalv;
+
+        if self.n1 > self.n2 {
+            let n = self.n1 / self.n2;
+            let sin2_t = n * n * (1.0 - cos * cos);
+            // total internal reflection
+            if sin2_t > 1.0 {
+                return 1.0;
+            }
+
+            cos = (1.0 - sin2_t).sqrt();
+        }
+
+        let r0 = ((self.n1 - self.n2) / (self.n1 + self.n2)).powi(2);
+        r0 + (1.0 - r0) * (1.0 - cos).powi(5)
+    }
 }
 
 #[cfg(test)]
@@ -138,11 +183,13 @@ mod tests {
     use crate::color;
     use crate::color::Color;
     use crate::light::Light;
-    use crate::matrix;
+    use crate::matrix4;
+    use crate::plane::Plane;
     use crate::point;
+    use crate::sphere::Sphere;
     use crate::vector;
     use crate::world::World;
-    use core::f64::consts::PI;
+    use core::f64::consts::FRAC_1_SQRT_2;
 
     #[test]
     fn test_ray_new() {
@@ -181,14 +228,8 @@ mod tests {
         let sphere = Sphere::init();
         let intersections = charles.intersect(&sphere);
 
-        let a = Intersection {
-            t: 4.0,
-            object: &sphere,
-        };
-        let b = Intersection {
-            t: 6.0,
-            object: &sphere,
-        };
+        let a = Intersection::new(4.0, &sphere);
+        let b = Intersection::new(6.0, &sphere);
 
         assert_eq!(intersections, vec![a, b]);
         assert_eq!(intersections.len(), 2);
@@ -204,14 +245,8 @@ mod tests {
         let sphere = Sphere::init();
         let intersections = charles.intersect(&sphere);
 
-        let a = Intersection {
-            t: 5.0,
-            object: &sphere,
-        };
-        let b = Intersection {
-            t: 5.0,
-            object: &sphere,
-        };
+        let a = Intersection::new(5.0, &sphere);
+        let b = Intersection::new(5.0, &sphere);
 
         assert_eq!(intersections, vec![a, b]);
         assert_eq!(intersections.len(), 2);
@@ -239,14 +274,8 @@ mod tests {
         let sphere = Sphere::init();
         let intersections = charles.intersect(&sphere);
 
-        let a = Intersection {
-            t: -1.0,
-            object: &sphere,
-        };
-        let b = Intersection {
-            t: 1.0,
-            object: &sphere,
-        };
+        let a = Intersection::new(-1.0, &sphere);
+        let b = Intersection::new(1.0, &sphere);
 
         assert_eq!(intersections, vec![a, b]);
         assert_eq!(intersections.len(), 2);
@@ -262,14 +291,8 @@ mod tests {
         let sphere = Sphere::init();
         let intersections = charles.intersect(&sphere);
 
-        let a = Intersection {
-            t: -6.0,
-            object: &sphere,
-        };
-        let b = Intersection {
-            t: -4.0,
-            object: &sphere,
-        };
+        let a = Intersection::new(-6.0, &sphere);
+        let b = Intersection::new(-4.0, &sphere);
 
         assert_eq!(intersections, vec![a, b]);
         assert_eq!(intersections.len(), 2);
@@ -278,25 +301,17 @@ mod tests {
     #[test]
     fn test_intersection_obj() {
         let sphere = Sphere::init();
-        let intersect = Intersection {
-            t: 3.5,
-            object: &sphere,
-        };
+        let intersect = Intersection::new(3.5, &sphere);
 
         assert_eq!(intersect.t, 3.5);
-        assert_eq!(intersect.object, &sphere);
+        assert_eq!(intersect.object, &sphere as &dyn Shape);
     }
 
     #[test]
     fn test_hit() {
-        let i1 = Intersection {
-            t: 1.0,
-            object: &Sphere::init(),
-        };
-        let i2 = Intersection {
-            t: 2.0,
-            object: &Sphere::init(),
-        };
+        let sphere = Sphere::init();
+        let i1 = Intersection::new(1.0, &sphere);
+        let i2 = Intersection::new(2.0, &sphere);
 
         let intersections = vec![i1.clone(), i2];
 
@@ -305,14 +320,9 @@ mod tests {
 
     #[test]
     fn test_hit_negative() {
-        let i1 = Intersection {
-            t: -1.0,
-            object: &Sphere::init(),
-        };
-        let i2 = Intersection {
-            t: 1.0,
-            object: &Sphere::init(),
-        };
+        let sphere = Sphere::init();
+        let i1 = Intersection::new(-1.0, &sphere);
+        let i2 = Intersection::new(1.0, &sphere);
 
         let intersections = vec![i1, i2.clone()];
 
@@ -326,7 +336,7 @@ mod tests {
             direction: vector!(0.0, 1.0, 0.0),
         };
 
-        let transformation = matrix![ 1.0, 0.0, 0.0, 3.0;
+        let transformation = matrix4![ 1.0, 0.0, 0.0, 3.0;
 	                              0.0, 1.0, 0.0, 4.0;
 	                              0.0, 0.0, 1.0, 5.0;
 	                              0.0, 0.0, 0.0, 1.0];
@@ -344,7 +354,7 @@ mod tests {
             direction: vector!(0.0, 1.0, 0.0),
         };
 
-        let transformation = matrix![ 2.0, 0.0, 0.0, 0.0;
+        let transformation = matrix4![ 2.0, 0.0, 0.0, 0.0;
                                  0.0, 3.0, 0.0, 0.0;
                                  0.0, 0.0, 4.0, 0.0;
                                  0.0, 0.0, 0.0, 1.0];
@@ -358,14 +368,8 @@ mod tests {
     #[test]
     fn test_hit_all_negative() {
         let sphere = Sphere::init();
-        let i1 = Intersection {
-            t: -1.0,
-            object: &sphere,
-        };
-        let i2 = Intersection {
-            t: -1.0,
-            object: &sphere,
-        };
+        let i1 = Intersection::new(-1.0, &sphere);
+        let i2 = Intersection::new(-1.0, &sphere);
 
         let intersections = vec![i1, i2];
 
@@ -381,19 +385,16 @@ mod tests {
         };
 
         let sphere = Sphere::init();
-        let intersection = Intersection {
-            t: 4.0,
-            object: &sphere,
-        };
+        let intersection = Intersection::new(4.0, &sphere);
 
-        let comps = intersection.prepare_computations(&ray);
+        let comps = intersection.prepare_computations(&ray, &[intersection.clone()]);
 
         assert!(comps.t == intersection.t);
         assert_eq!(comps.object, intersection.object);
         assert_eq!(comps.point, point!(0.0, 0.0, -1.0));
         assert_eq!(comps.eyev, vector!(0.0, 0.0, -1.0));
         assert_eq!(comps.normalv, vector!(0.0, 0.0, -1.0));
-        assert_eq!(comps.inside, false);
+        assert!(!comps.inside);
     }
 
     // test intersection precomputation calculations when intersection occurs on the inside of the sphere
@@ -405,17 +406,14 @@ mod tests {
         };
 
         let sphere = Sphere::init();
-        let intersection = Intersection {
-            t: 1.0,
-            object: &sphere,
-        };
+        let intersection = Intersection::new(1.0, &sphere);
 
-        let comps = intersection.prepare_computations(&ray);
+        let comps = intersection.prepare_computations(&ray, &[intersection.clone()]);
         assert_eq!(comps.object, intersection.object);
         assert_eq!(comps.point, point!(0.0, 0.0, 1.0));
         assert_eq!(comps.eyev, vector!(0.0, 0.0, -1.0));
         assert_eq!(comps.normalv, vector!(0.0, 0.0, -1.0));
-        assert_eq!(comps.inside, true);
+        assert!(comps.inside);
     }
 
     // test shading an intersection
@@ -427,14 +425,11 @@ mod tests {
             direction: vector!(0.0, 0.0, 1.0),
         };
 
-        let sphere = &world.objects[0];
-        let intersection = Intersection {
-            t: 4.0,
-            object: sphere,
-        };
+        let sphere = world.objects()[0].as_ref();
+        let intersection = Intersection::new(4.0, sphere);
 
-        let comps = intersection.prepare_computations(&ray);
-        let color = world.shade_hit(&comps);
+        let comps = intersection.prepare_computations(&ray, &[intersection.clone()]);
+        let color = world.shade_hit(&comps, world.max_depth);
 
         assert_eq!(
             color,
@@ -460,14 +455,11 @@ mod tests {
             direction: vector!(0.0, 0.0, 1.0),
         };
 
-        let sphere = &world.objects[1];
-        let intersection = Intersection {
-            t: 0.5,
-            object: sphere,
-        };
+        let sphere = world.objects()[1].as_ref();
+        let intersection = Intersection::new(0.5, sphere);
 
-        let comps = intersection.prepare_computations(&ray);
-        let color = world.shade_hit(&comps);
+        let comps = intersection.prepare_computations(&ray, &[intersection.clone()]);
+        let color = world.shade_hit(&comps, world.max_depth);
 
         assert_eq!(
             color,
@@ -480,68 +472,118 @@ mod tests {
     }
 
     #[test]
-    fn test_sphere_normal() {
-        let sphere = Sphere::init();
+    fn test_hit_offsets_the_point() {
+        let ray = Ray::new(point!(0.0, 0.0, -5.0), vector!(0.0, 0.0, 1.0));
+        let mut sphere = Sphere::init();
+        sphere.set_transform(Matrix4::identity().translation(0.0, 0.0, 1.0));
+        let intersection = Intersection::new(5.0, &sphere);
 
-        assert_eq!(
-            sphere.normal_at(point!(1.0, 0.0, 0.0)),
-            vector!(1.0, 0.0, 0.0)
-        );
-        assert_eq!(
-            sphere.normal_at(point!(0.0, 1.0, 0.0)),
-            vector!(0.0, 1.0, 0.0)
-        );
-        assert_eq!(
-            sphere.normal_at(point!(0.0, 0.0, 1.0)),
-            vector!(0.0, 0.0, 1.0)
-        );
+        let comps = intersection.prepare_computations(&ray, &[intersection.clone()]);
 
-        let nonaxial = point!(
-            (3.0_f64.sqrt() / 3.0),
-            (3.0_f64.sqrt() / 3.0),
-            (3.0_f64.sqrt() / 3.0)
-        );
-        let vector_result = vector!(
-            (3.0_f64.sqrt() / 3.0),
-            (3.0_f64.sqrt() / 3.0),
-            (3.0_f64.sqrt() / 3.0)
+        assert!(comps.over_point.z < -SURFACE_EPSILON / 2.0);
+        assert!(comps.point.z > comps.over_point.z);
+    }
+
+    #[test]
+    fn test_precompute_reflection_vector() {
+        let plane = Plane::init();
+        let ray = Ray::new(
+            point!(0.0, 1.0, -1.0),
+            vector!(0.0, -FRAC_1_SQRT_2, FRAC_1_SQRT_2),
         );
+        let intersection = Intersection::new(2.0_f64.sqrt(), &plane);
 
-        assert_eq!(sphere.normal_at(nonaxial), vector_result);
+        let comps = intersection.prepare_computations(&ray, &[intersection.clone()]);
 
-        // assert a normalized vector is returned
-        let normal = sphere.normal_at(nonaxial);
-        let normalized = normal.norm();
-        assert_eq!(normal, normalized);
+        assert_eq!(comps.reflectv, vector!(0.0, FRAC_1_SQRT_2, FRAC_1_SQRT_2));
     }
 
     #[test]
-    fn test_sphere_transformation() {
-        let mut sphere = Sphere::init();
-        sphere.transform = Matrix::identity(4).translation(0.0, 1.0, 0.0);
+    fn test_finding_n1_and_n2() {
+        let mut a = Sphere::glass();
+        a.set_transform(Matrix4::identity().scaling(2.0, 2.0, 2.0));
+        a.material.refractive_index = 1.5;
+        let mut b = Sphere::glass();
+        b.set_transform(Matrix4::identity().translation(0.0, 0.0, -0.25));
+        b.material.refractive_index = 2.0;
+        let mut c = Sphere::glass();
+        c.set_transform(Matrix4::identity().translation(0.0, 0.0, 0.25));
+        c.material.refractive_index = 2.5;
+
+        let ray = Ray::new(point!(0.0, 0.0, -4.0), vector!(0.0, 0.0, 1.0));
+        let intersections = vec![
+            Intersection::new(2.0, &a),
+            Intersection::new(2.75, &b),
+            Intersection::new(3.25, &c),
+            Intersection::new(4.75, &b),
+            Intersection::new(5.25, &c),
+            Intersection::new(6.0, &a),
+        ];
+        let expected = [
+            (1.0, 1.5),
+            (1.5, 2.0),
+            (2.0, 2.5),
+            (2.5, 2.5),
+            (2.5, 1.5),
+            (1.5, 1.0),
+        ];
+
+        for (intersection, (n1, n2)) in intersections.iter().zip(expected) {
+            let comps = intersection.prepare_computations(&ray, &intersections);
+            assert_eq!(comps.n1, n1);
+            assert_eq!(comps.n2, n2);
+        }
+    }
 
-        let n = sphere.normal_at(point!(0.0, 1.70711, -0.70711));
+    #[test]
+    fn test_under_point_is_below_the_surface() {
+        let ray = Ray::new(point!(0.0, 0.0, -5.0), vector!(0.0, 0.0, 1.0));
+        let mut sphere = Sphere::glass();
+        sphere.set_transform(Matrix4::identity().translation(0.0, 0.0, 1.0));
+        let intersection = Intersection::new(5.0, &sphere);
+
+        let comps = intersection.prepare_computations(&ray, &[intersection.clone()]);
 
-        assert_eq!(n, vector!(0.0, 0.70711, -0.70711));
+        assert!(comps.under_point.z > SURFACE_EPSILON / 2.0);
+        assert!(comps.point.z < comps.under_point.z);
     }
 
     #[test]
-    fn test_sphere_transformation_two() {
-        let mut sphere = Sphere::init();
-        // order matters ?
+    fn test_schlick_under_total_internal_reflection() {
+        let sphere = Sphere::glass();
+        let ray = Ray::new(point!(0.0, 0.0, FRAC_1_SQRT_2), vector!(0.0, 1.0, 0.0));
+        let intersections = vec![
+            Intersection::new(-FRAC_1_SQRT_2, &sphere),
+            Intersection::new(FRAC_1_SQRT_2, &sphere),
+        ];
+
+        let comps = intersections[1].prepare_computations(&ray, &intersections);
+
+        assert_eq!(comps.schlick(), 1.0);
+    }
 
-        sphere.transform = Matrix::identity(4)
-            .rotation_z(PI / 5.0)
-            .scaling(1.0, 0.5, 1.0);
+    #[test]
+    fn test_schlick_with_perpendicular_viewing_angle() {
+        let sphere = Sphere::glass();
+        let ray = Ray::new(point!(0.0, 0.0, 0.0), vector!(0.0, 1.0, 0.0));
+        let intersections = vec![
+            Intersection::new(-1.0, &sphere),
+            Intersection::new(1.0, &sphere),
+        ];
 
-        let n = sphere.normal_at(point!(0.0, 2.0_f64.sqrt() / 2.0, -2.0_f64.sqrt() / 2.0));
+        let comps = intersections[1].prepare_computations(&ray, &intersections);
 
-        assert_eq!(n, vector!(0.0, 0.97014, -0.24254));
+        assert!((comps.schlick() - 0.04).abs() < crate::tuple::EPSILON);
     }
 
     #[test]
-    fn test_sphere_default_material() {
-        let sphere = Sphere::init();
-        assert_eq!(sphere.material, Material::default());
+    fn test_schlick_with_small_angle_and_n2_greater_than_n1() {
+        let sphere = Sphere::glass();
+        let ray = Ray::new(point!(0.0, 0.99, -2.0), vector!(0.0, 0.0, 1.0));
+        let intersections = vec![Intersection::new(1.8589, &sphere)];
+
+        let comps = intersections[0].prepare_computations(&ray, &intersections);
+
+        assert!((comps.schlick() - 0.48873).abs() < crate::tuple::EPSILON);
     }
 }
@@ -0,0 +1,222 @@
+// Where the samples of a pixel go, and how much each one counts
+
+// How the samples are spread over a pixel. Regular and Jittered split it into a
+// grid of strata as close to square as the sample count allows, so 4 or 9
+// samples give 2x2 or 3x3 grids and a prime count gives a single row.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SamplePattern {
+    // the center of every stratum
+    Regular,
+    // a random point in every stratum
+    Jittered,
+    // random points anywhere in the pixel
+    Random,
+}
+
+// The reconstruction filter samples are weighted with, by their offset from
+// the pixel center in pixels. Filters wider than half a pixel take samples from
+// the neighbouring pixels too.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Filter {
+    Box,
+    Tent,
+    Gaussian,
+    // Mitchell-Netravali with B = C = 1/3; its negative lobes sharpen edges
+    Mitchell,
+}
+
+const GAUSSIAN_ALPHA: f64 = 2.0;
+const MITCHELL_B: f64 = 1.0 / 3.0;
+const MITCHELL_C: f64 = 1.0 / 3.0;
+
+impl Filter {
+    // how far from the pixel center the filter reaches, in pixels
+    pub fn radius(&self) -> f64 {
+        match self {
+            Filter::Box => 0.5,
+            Filter::Tent => 1.0,
+            Filter::Gaussian => 1.5,
+            Filter::Mitchell => 2.0,
+        }
+    }
+
+    pub fn weight(&self, dx: f64, dy: f64) -> f64 {
+        self.weight_1d(dx) * self.weight_1d(dy)
+    }
+
+    fn weight_1d(&self, offset: f64) -> f64 {
+        let x = offset.abs();
+        if x > self.radius() {
+            return 0.0;
+        }
+
+        match self {
+            Filter::Box => 1.0,
+            Filter::Tent => 1.0 - x,
+            // shifted down so it reaches zero at the radius
+            Filter::Gaussian => {
+                (-GAUSSIAN_ALPHA * x * x).exp()
+                    - (-GAUSSIAN_ALPHA * self.radius() * self.radius()).exp()
+            }
+            Filter::Mitchell => {
+                let (b, c) = (MITCHELL_B, MITCHELL_C);
+                if x < 1.0 {
+                    ((12.0 - 9.0 * b - 6.0 * c) * x * x * x
+                        + (-18.0 + 12.0 * b + 6.0 * c) * x * x
+                        + (6.0 - 2.0 * b))
+                        / 6.0
+                } else {
+                    ((-b - 6.0 * c) * x * x * x
+                        + (6.0 * b + 30.0 * c) * x * x
+                        + (-12.0 * b - 48.0 * c) * x
+                        + (8.0 * b + 24.0 * c))
+                        / 6.0
+                }
+            }
+        }
+    }
+}
+
+// SplitMix64, seeded per pixel so a render does not depend on the order tiles
+// are rendered in or on the number of threads
+struct Random {
+    state: u64,
+}
+
+impl Random {
+    fn for_pixel(x: usize, y: usize) -> Random {
+        Random {
+            state: (x as u64) << 32 ^ y as u64,
+        }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    // uniform in 0.0..1.0
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+// rows and columns of the most square grid with exactly count cells
+fn strata(count: usize) -> (usize, usize) {
+    let rows = (1..=count)
+        .take_while(|rows| rows * rows <= count)
+        .filter(|rows| count % rows == 0)
+        .last()
+        .unwrap_or(1);
+    (rows, count / rows)
+}
+
+impl SamplePattern {
+    // count points of the pixel at x, y, as offsets in 0.0..1.0 from its top left corner
+    pub fn samples(&self, count: usize, x: usize, y: usize) -> Vec<(f64, f64)> {
+        let mut random = Random::for_pixel(x, y);
+        let (rows, columns) = strata(count);
+
+        (0..count)
+            .map(|index| {
+                let (row, column) = ((index / columns) as f64, (index % columns) as f64);
+                let (u, v) = match self {
+                    SamplePattern::Regular => (0.5, 0.5),
+                    SamplePattern::Jittered => (random.next_f64(), random.next_f64()),
+                    SamplePattern::Random => {
+                        return (random.next_f64(), random.next_f64());
+                    }
+                };
+                ((column + u) / columns as f64, (row + v) / rows as f64)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_strata() {
+        assert_eq!(strata(1), (1, 1));
+        assert_eq!(strata(4), (2, 2));
+        assert_eq!(strata(8), (2, 4));
+        assert_eq!(strata(9), (3, 3));
+        assert_eq!(strata(7), (1, 7));
+    }
+
+    #[test]
+    fn test_regular_samples() {
+        assert_eq!(SamplePattern::Regular.samples(1, 3, 4), vec![(0.5, 0.5)]);
+        assert_eq!(
+            SamplePattern::Regular.samples(4, 3, 4),
+            vec![(0.25, 0.25), (0.75, 0.25), (0.25, 0.75), (0.75, 0.75)]
+        );
+    }
+
+    #[test]
+    fn test_jittered_samples_stay_in_their_strata() {
+        let samples = SamplePattern::Jittered.samples(9, 10, 20);
+
+        for (index, (u, v)) in samples.into_iter().enumerate() {
+            let (row, column) = ((index / 3) as f64, (index % 3) as f64);
+            assert!(u >= column / 3.0 && u < (column + 1.0) / 3.0);
+            assert!(v >= row / 3.0 && v < (row + 1.0) / 3.0);
+        }
+    }
+
+    #[test]
+    fn test_random_samples() {
+        let samples = SamplePattern::Random.samples(64, 1, 2);
+
+        assert_eq!(samples.len(), 64);
+        assert!(samples
+            .iter()
+            .all(|&(u, v)| (0.0..1.0).contains(&u) && (0.0..1.0).contains(&v)));
+    }
+
+    #[test]
+    fn test_samples_depend_only_on_the_pixel() {
+        for pattern in [SamplePattern::Jittered, SamplePattern::Random].iter() {
+            assert_eq!(pattern.samples(4, 5, 6), pattern.samples(4, 5, 6));
+            assert_ne!(pattern.samples(4, 5, 6), pattern.samples(4, 6, 5));
+        }
+    }
+
+    #[test]
+    fn test_box_and_tent_filters() {
+        assert_eq!(Filter::Box.weight(0.4, -0.4), 1.0);
+        assert_eq!(Filter::Box.weight(0.6, 0.0), 0.0);
+        assert_eq!(Filter::Tent.weight(0.0, 0.0), 1.0);
+        assert_eq!(Filter::Tent.weight(0.5, 0.5), 0.25);
+        assert_eq!(Filter::Tent.weight(1.0, 0.0), 0.0);
+    }
+
+    #[test]
+    fn test_gaussian_filter() {
+        let center = Filter::Gaussian.weight(0.0, 0.0);
+        let near = Filter::Gaussian.weight(0.5, 0.0);
+
+        assert_relative_eq!(center, (1.0 - (-4.5f64).exp()).powi(2));
+        assert!(near < center && near > 0.0);
+        assert_relative_eq!(Filter::Gaussian.weight(1.5, 0.0), 0.0);
+    }
+
+    #[test]
+    fn test_mitchell_filter() {
+        assert_relative_eq!(Filter::Mitchell.weight(0.0, 0.0), (8.0 / 9.0) * (8.0 / 9.0));
+        // the curve is continuous at 1 and vanishes at 2
+        assert_relative_eq!(
+            Filter::Mitchell.weight_1d(1.0 - 1e-9),
+            Filter::Mitchell.weight_1d(1.0 + 1e-9),
+            epsilon = 1e-6
+        );
+        assert_relative_eq!(Filter::Mitchell.weight_1d(2.0), 0.0, epsilon = 1e-12);
+        assert!(Filter::Mitchell.weight_1d(1.5) < 0.0);
+    }
+}
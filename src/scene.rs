@@ -0,0 +1,601 @@
+use crate::camera::Camera;
+use crate::color::Color;
+use crate::cone::Cone;
+use crate::cube::Cube;
+use crate::cylinder::Cylinder;
+use crate::group::Group;
+use crate::light::Light;
+use crate::material::Material;
+use crate::matrix::*;
+use crate::pattern::*;
+use crate::plane::Plane;
+use crate::point;
+use crate::shape::Shape;
+use crate::sphere::Sphere;
+use crate::tuple::*;
+use crate::vector;
+use crate::world::World;
+use crate::yaml::*;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+// Loads scenes written in the YAML format of the book:
+//
+//   - add: camera
+//     width: 100
+//     height: 100
+//     field-of-view: 0.785
+//     from: [ -6, 6, -10 ]
+//     to: [ 6, 0, 6 ]
+//     up: [ -0.45, 1, 0 ]
+//
+//   - define: white-material
+//     value:
+//       color: [ 1, 1, 1 ]
+//       diffuse: 0.7
+//
+//   - define: blue-material
+//     extend: white-material
+//     value:
+//       color: [ 0.537, 0.831, 0.914 ]
+//
+//   - add: cube
+//     material: blue-material
+//     transform:
+//       - [ scale, 0.5, 0.5, 0.5 ]
+//       - [ translate, 4, 0, 0 ]
+//
+// Transforms are listed in the order they are applied.
+
+pub struct Scene {
+    pub world: World,
+    pub camera: Camera,
+}
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io(io::Error),
+    Invalid { line: usize, message: String },
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SceneError::Io(error) => write!(f, "{}", error),
+            SceneError::Invalid { line, message } => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+impl From<io::Error> for SceneError {
+    fn from(error: io::Error) -> Self {
+        SceneError::Io(error)
+    }
+}
+
+impl From<YamlError> for SceneError {
+    fn from(error: YamlError) -> Self {
+        SceneError::Invalid {
+            line: error.line,
+            message: error.message,
+        }
+    }
+}
+
+fn invalid<T>(line: usize, message: impl Into<String>) -> Result<T, SceneError> {
+    Err(SceneError::Invalid {
+        line,
+        message: message.into(),
+    })
+}
+
+// fails on the first key of the mapping that is not in allowed
+fn check_keys(node: &Node, allowed: &[&str]) -> Result<(), SceneError> {
+    for (key, value) in node.as_map()? {
+        if !allowed.contains(&key.as_str()) {
+            return invalid(value.line, format!("unknown key `{}`", key));
+        }
+    }
+    Ok(())
+}
+
+fn required<'a>(node: &'a Node, key: &str) -> Result<&'a Node, SceneError> {
+    match node.get(key) {
+        Some(value) => Ok(value),
+        None => invalid(node.line, format!("missing `{}`", key)),
+    }
+}
+
+// as_usize lets 0 through, which makes an image with no pixels
+fn positive(node: &Node) -> Result<usize, SceneError> {
+    match node.as_usize()? {
+        0 => invalid(node.line, "expected a positive integer, found `0`"),
+        value => Ok(value),
+    }
+}
+
+fn numbers<const N: usize>(node: &Node) -> Result<[f64; N], SceneError> {
+    let items = node.as_list()?;
+    if items.len() != N {
+        return invalid(node.line, format!("expected {} numbers", N));
+    }
+    let mut result = [0.0; N];
+    for (value, item) in result.iter_mut().zip(items) {
+        *value = item.as_f64()?;
+    }
+    Ok(result)
+}
+
+fn parse_point(node: &Node) -> Result<Tuple, SceneError> {
+    let [x, y, z] = numbers(node)?;
+    Ok(point!(x, y, z))
+}
+
+fn parse_vector(node: &Node) -> Result<Tuple, SceneError> {
+    let [x, y, z] = numbers(node)?;
+    Ok(vector!(x, y, z))
+}
+
+fn parse_color(node: &Node) -> Result<Color, SceneError> {
+    let [red, green, blue] = numbers(node)?;
+    Ok(Color::new(red, green, blue))
+}
+
+struct SceneBuilder {
+    // values of `define`, with `extend` and references to other
+    // definitions already resolved
+    defines: HashMap<String, Node>,
+    world: World,
+    camera: Option<Camera>,
+}
+
+impl SceneBuilder {
+    fn new() -> Self {
+        Self {
+            defines: HashMap::new(),
+            // not World::default, which comes with the objects of the book
+            world: World::new(),
+            camera: None,
+        }
+    }
+
+    fn lookup(&self, name: &Node) -> Result<&Node, SceneError> {
+        let key = name.as_str()?;
+        match self.defines.get(key) {
+            Some(value) => Ok(value),
+            None => invalid(name.line, format!("unknown definition `{}`", key)),
+        }
+    }
+
+    fn define(&mut self, item: &Node) -> Result<(), SceneError> {
+        check_keys(item, &["define", "extend", "value"])?;
+        let name = required(item, "define")?.as_str()?.to_string();
+        let value = required(item, "value")?;
+
+        let value = match &value.value {
+            Value::Map(entries) => {
+                let mut merged = match item.get("extend") {
+                    Some(base) => self.lookup(base)?.as_map()?.to_vec(),
+                    None => vec![],
+                };
+                for (key, entry) in entries {
+                    merged.retain(|(k, _)| k != key);
+                    merged.push((key.clone(), entry.clone()));
+                }
+                Node {
+                    value: Value::Map(merged),
+                    line: value.line,
+                }
+            }
+            Value::List(items) => {
+                if let Some(base) = item.get("extend") {
+                    return invalid(base.line, "only mappings can be extended");
+                }
+                // references to other transforms are replaced by their steps
+                let mut expanded = vec![];
+                for step in items {
+                    match step.value {
+                        Value::Scalar(_) => {
+                            expanded.extend_from_slice(self.lookup(step)?.as_list()?)
+                        }
+                        _ => expanded.push(step.clone()),
+                    }
+                }
+                Node {
+                    value: Value::List(expanded),
+                    line: value.line,
+                }
+            }
+            _ => value.clone(),
+        };
+
+        self.defines.insert(name, value);
+        Ok(())
+    }
+
+    fn add(&mut self, item: &Node) -> Result<(), SceneError> {
+        let kind = required(item, "add")?;
+        match kind.as_str()? {
+            "camera" => {
+                check_keys(
+                    item,
+                    &[
+                        "add",
+                        "width",
+                        "height",
+                        "field-of-view",
+                        "from",
+                        "to",
+                        "up",
+                    ],
+                )?;
+                let mut camera = Camera::new(
+                    positive(required(item, "width")?)?,
+                    positive(required(item, "height")?)?,
+                    required(item, "field-of-view")?.as_f64()?,
+                );
+                camera.set_transform(view_transform(
+                    parse_point(required(item, "from")?)?,
+                    parse_point(required(item, "to")?)?,
+                    parse_vector(required(item, "up")?)?,
+                ));
+                self.camera = Some(camera);
+            }
+            "light" => {
+                check_keys(item, &["add", "at", "intensity"])?;
+                self.world.lights.push(Light {
+                    position: parse_point(required(item, "at")?)?,
+                    intensity: parse_color(required(item, "intensity")?)?,
+                });
+            }
+            _ => {
+                let shape = self.shape(item)?;
+                self.world.add_object(shape);
+            }
+        }
+        Ok(())
+    }
+
+    fn shape(&self, item: &Node) -> Result<Box<dyn Shape>, SceneError> {
+        let kind = required(item, "add")?;
+        let mut shape: Box<dyn Shape> = match kind.as_str()? {
+            "sphere" => {
+                check_keys(item, &["add", "material", "transform"])?;
+                Box::new(Sphere::init())
+            }
+            "plane" => {
+                check_keys(item, &["add", "material", "transform"])?;
+                Box::new(Plane::init())
+            }
+            "cube" => {
+                check_keys(item, &["add", "material", "transform"])?;
+                Box::new(Cube::init())
+            }
+            "cylinder" | "cone" => {
+                check_keys(
+                    item,
+                    &["add", "material", "transform", "min", "max", "closed"],
+                )?;
+                let minimum = match item.get("min") {
+                    Some(min) => min.as_f64()?,
+                    None => f64::NEG_INFINITY,
+                };
+                let maximum = match item.get("max") {
+                    Some(max) => max.as_f64()?,
+                    None => f64::INFINITY,
+                };
+                let closed = match item.get("closed") {
+                    Some(closed) => closed.as_bool()?,
+                    None => false,
+                };
+                if kind.as_str()? == "cylinder" {
+                    let mut cylinder = Cylinder::init();
+                    cylinder.minimum = minimum;
+                    cylinder.maximum = maximum;
+                    cylinder.closed = closed;
+                    Box::new(cylinder)
+                } else {
+                    let mut cone = Cone::init();
+                    cone.minimum = minimum;
+                    cone.maximum = maximum;
+                    cone.closed = closed;
+                    Box::new(cone)
+                }
+            }
+            "group" => {
+                check_keys(item, &["add", "transform", "children"])?;
+                let mut group = Group::init();
+                for child in required(item, "children")?.as_list()? {
+                    group.add_child(self.shape(child)?);
+                }
+                Box::new(group)
+            }
+            other => return invalid(kind.line, format!("unknown object `{}`", other)),
+        };
+
+        if let Some(material) = item.get("material") {
+            *shape.material_mut() = self.material(material)?;
+        }
+        if let Some(transform) = item.get("transform") {
+            shape.set_transform(self.transform(transform)?);
+        }
+        Ok(shape)
+    }
+
+    fn material(&self, node: &Node) -> Result<Material, SceneError> {
+        let node = match node.value {
+            Value::Scalar(_) => self.lookup(node)?,
+            _ => node,
+        };
+
+        let mut material = Material::default();
+        for (key, value) in node.as_map()? {
+            match key.as_str() {
+                "color" => material.color = parse_color(value)?,
+                "pattern" => material.pattern = Some(self.pattern(value)?),
+                "ambient" => material.ambient = value.as_f64()?,
+                "diffuse" => material.diffuse = value.as_f64()?,
+                "specular" => material.specular = value.as_f64()?,
+                "shininess" => material.shininess = value.as_f64()?,
+                "reflective" => material.reflective = value.as_f64()?,
+                "transparency" => material.transparency = value.as_f64()?,
+                "refractive-index" => material.refractive_index = value.as_f64()?,
+                _ => return invalid(value.line, format!("unknown material key `{}`", key)),
+            }
+        }
+        Ok(material)
+    }
+
+    fn pattern(&self, node: &Node) -> Result<Arc<dyn Pattern>, SceneError> {
+        check_keys(node, &["type", "colors", "transform"])?;
+        let colors = required(node, "colors")?;
+        let (a, b) = match colors.as_list()? {
+            [a, b] => (parse_color(a)?, parse_color(b)?),
+            _ => return invalid(colors.line, "a pattern needs two colors"),
+        };
+
+        let kind = required(node, "type")?;
+        let mut pattern: Box<dyn Pattern> = match kind.as_str()? {
+            "stripes" => Box::new(Stripe::new(a, b)),
+            "gradient" => Box::new(Gradient::new(a, b)),
+            "rings" => Box::new(Ring::new(a, b)),
+            "checkers" => Box::new(Checker::new(a, b)),
+            other => return invalid(kind.line, format!("unknown pattern `{}`", other)),
+        };
+        if let Some(transform) = node.get("transform") {
+            pattern.set_transform(self.transform(transform)?);
+        }
+        Ok(Arc::from(pattern))
+    }
+
+    fn transform(&self, node: &Node) -> Result<Matrix4, SceneError> {
+        let mut matrix = Matrix4::identity();
+        for step in node.as_list()? {
+            if let Value::Scalar(_) = step.value {
+                matrix = self.transform(self.lookup(step)?)? * matrix;
+                continue;
+            }
+
+            let (operation, args) = match step.as_list()? {
+                [operation, args @ ..] => (operation, args),
+                [] => return invalid(step.line, "empty transform"),
+            };
+            let args = args
+                .iter()
+                .map(|arg| arg.as_f64())
+                .collect::<Result<Vec<f64>, YamlError>>()?;
+
+            matrix = match (operation.as_str()?, args.as_slice()) {
+                ("translate", [x, y, z]) => matrix.translation(*x, *y, *z),
+                ("scale", [x, y, z]) => matrix.scaling(*x, *y, *z),
+                ("rotate-x", [angle]) => matrix.rotation_x(*angle),
+                ("rotate-y", [angle]) => matrix.rotation_y(*angle),
+                ("rotate-z", [angle]) => matrix.rotation_z(*angle),
+                ("shear", [xy, xz, yx, yz, zx, zy]) => matrix.skew(*xy, *xz, *yx, *yz, *zx, *zy),
+                (other, _) => {
+                    return invalid(
+                        step.line,
+                        format!("unknown transform `{}` or wrong number of arguments", other),
+                    )
+                }
+            };
+        }
+        Ok(matrix)
+    }
+}
+
+pub fn parse_scene(input: &str) -> Result<Scene, SceneError> {
+    let root = parse_yaml(input)?;
+    let items = match root.value {
+        Value::Null => &[][..],
+        _ => root.as_list()?,
+    };
+
+    let mut builder = SceneBuilder::new();
+    for item in items {
+        if item.get("define").is_some() {
+            builder.define(item)?;
+        } else if item.get("add").is_some() {
+            builder.add(item)?;
+        } else {
+            return invalid(item.line, "expected `add` or `define`");
+        }
+    }
+
+    let camera = match builder.camera {
+        Some(camera) => camera,
+        None => return invalid(input.lines().count().max(1), "the scene has no camera"),
+    };
+    let mut world = builder.world;
+    world.build_bvh();
+
+    Ok(Scene { world, camera })
+}
+
+pub fn parse_scene_file(path: &Path) -> Result<Scene, SceneError> {
+    let input = fs::read_to_string(path)?;
+    parse_scene(&input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::FRAC_PI_2;
+
+    const SCENE: &str = "
+- add: camera
+  width: 100
+  height: 50
+  field-of-view: 0.785
+  from: [ 0, 1.5, -5 ]
+  to: [ 0, 1, 0 ]
+  up: [ 0, 1, 0 ]
+
+- add: light
+  at: [ -10, 10, -10 ]
+  intensity: [ 1, 1, 1 ]
+
+- define: white-material
+  value:
+    color: [ 1, 1, 1 ]
+    diffuse: 0.7
+    reflective: 0.1
+
+- define: blue-material
+  extend: white-material
+  value:
+    color: [ 0.5, 0.8, 0.9 ]
+
+- define: standard-transform
+  value:
+    - [ translate, 1, -1, 1 ]
+    - [ scale, 0.5, 0.5, 0.5 ]
+
+- define: large-object
+  value:
+    - standard-transform
+    - [ scale, 4, 4, 4 ]
+
+- add: plane
+  material:
+    pattern:
+      type: checkers
+      colors:
+        - [ 0.35, 0.35, 0.35 ]
+        - [ 0.65, 0.65, 0.65 ]
+    specular: 0
+  transform:
+    - [ rotate-x, 1.5707963267948966 ]
+
+- add: cube
+  material: blue-material
+  transform:
+    - large-object
+    - [ translate, 4, 0, 0 ]
+
+- add: cylinder
+  min: 0
+  max: 1
+  closed: true
+";
+
+    #[test]
+    fn test_loading_scene() {
+        let scene = parse_scene(SCENE).unwrap();
+
+        assert_eq!(scene.camera.hsize, 100);
+        assert_eq!(scene.camera.vsize, 50);
+        assert_eq!(scene.camera.field_of_view, 0.785);
+        assert_eq!(scene.world.lights.len(), 1);
+        assert_eq!(scene.world.lights[0].position, point!(-10, 10, -10));
+        assert_eq!(scene.world.objects().len(), 3);
+    }
+
+    #[test]
+    fn test_materials_are_extended() {
+        let scene = parse_scene(SCENE).unwrap();
+        let cube = scene.world.objects()[1].material();
+
+        assert_eq!(cube.color, Color::new(0.5, 0.8, 0.9));
+        assert_eq!(cube.diffuse, 0.7);
+        assert_eq!(cube.reflective, 0.1);
+        assert_eq!(cube.ambient, Material::default().ambient);
+
+        let plane = scene.world.objects()[0].material();
+        assert!(plane.pattern.is_some());
+        assert_eq!(plane.specular, 0.0);
+    }
+
+    #[test]
+    fn test_transforms_are_applied_in_order() {
+        let scene = parse_scene(SCENE).unwrap();
+
+        let expected = Matrix4::identity()
+            .translation(1.0, -1.0, 1.0)
+            .scaling(0.5, 0.5, 0.5)
+            .scaling(4.0, 4.0, 4.0)
+            .translation(4.0, 0.0, 0.0);
+        assert_eq!(scene.world.objects()[1].transform(), &expected);
+        assert_eq!(
+            scene.world.objects()[0].transform(),
+            &Matrix4::identity().rotation_x(FRAC_PI_2)
+        );
+    }
+
+    fn error_line(input: &str) -> usize {
+        match parse_scene(input) {
+            Err(SceneError::Invalid { line, .. }) => line,
+            _ => panic!("expected an error"),
+        }
+    }
+
+    #[test]
+    fn test_errors_have_line_numbers() {
+        let camera = "- add: camera\n  width: 10\n  height: 10\n  field-of-view: 1\n  from: [0, 0, -5]\n  to: [0, 0, 0]\n  up: [0, 1, 0]\n";
+
+        assert!(parse_scene(camera).is_ok());
+        assert_eq!(error_line(&format!("{}- add: teapot\n", camera)), 8);
+        assert_eq!(
+            error_line(&format!("{}- add: sphere\n  colour: 1\n", camera)),
+            9
+        );
+        assert_eq!(
+            error_line(&format!("{}- add: sphere\n  material: nope\n", camera)),
+            9
+        );
+        assert_eq!(
+            error_line(&format!(
+                "{}- add: sphere\n  transform:\n    - [ spin, 1 ]\n",
+                camera
+            )),
+            10
+        );
+        assert_eq!(error_line("- add: camera\n  width: wide\n"), 2);
+        assert_eq!(error_line(&camera.replace("height: 10", "height: 0")), 3);
+        assert_eq!(
+            error_line("- add: light\n  at: [0, 0, 0]\n  intensity: [1, 1, 1]\n"),
+            3
+        );
+    }
+
+    #[test]
+    fn test_scene_error_message() {
+        let error = parse_scene("- add: camera\n  width: 10\n").err().unwrap();
+
+        assert_eq!(error.to_string(), "line 1: missing `height`");
+
+        let error = parse_scene("- add: camera\n  width: 0\n  height: 10\n")
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "line 2: expected a positive integer, found `0`"
+        );
+    }
+}
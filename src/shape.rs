@@ -0,0 +1,203 @@
+use crate::bounds::BoundingBox;
+use crate::material::Material;
+use crate::matrix::{Matrix4, Transform};
+use crate::ray::{Intersection, Ray};
+use crate::tuple::Tuple;
+use std::fmt::Debug;
+
+// Shape is implemented by every primitive that can be added to a World.
+// Implementations only work in object space: `Ray::intersect` and `normal_at`
+// take care of converting from and to world space using `transform`.
+// Shapes are shared between render threads, hence Send and Sync.
+pub trait Shape: Debug + Send + Sync {
+    fn transform(&self) -> &Transform;
+    // Recomputes the cached inverses, but a Group or World holding the shape
+    // only sees the new bounds if the change goes through them: add shapes to
+    // groups once they are placed, and use World::object_mut for world objects.
+    fn set_transform(&mut self, transform: Matrix4);
+    // the transforms of every group above this shape, composed from the
+    // outermost one down. Identity for shapes that are not in a group.
+    fn parent_transform(&self) -> &Transform;
+    fn set_parent_transform(&mut self, transform: Matrix4);
+    fn material(&self) -> &Material;
+    fn material_mut(&mut self) -> &mut Material;
+
+    fn local_intersect(&self, ray: &Ray) -> Vec<Intersection>;
+    fn local_normal_at(&self, point: Tuple) -> Tuple;
+    // the box around the shape in object space
+    fn bounds(&self) -> BoundingBox;
+
+    // the box around the shape once its own transform is applied, which is
+    // the space of the group or world holding it
+    fn parent_space_bounds(&self) -> BoundingBox {
+        self.bounds().transform(&self.transform().matrix())
+    }
+
+    // shapes that interpolate normals across their surface, like smooth
+    // triangles, need to know where exactly they were hit
+    fn local_normal_at_hit(&self, object_point: Tuple, _hit: &Intersection) -> Tuple {
+        self.local_normal_at(object_point)
+    }
+
+    fn normal_at(&self, world_point: Tuple) -> Tuple {
+        let object_point = self.world_to_object(world_point);
+        let object_normal = self.local_normal_at(object_point);
+        self.normal_to_world(object_normal)
+    }
+
+    fn normal_at_hit(&self, world_point: Tuple, hit: &Intersection) -> Tuple {
+        let object_point = self.world_to_object(world_point);
+        let object_normal = self.local_normal_at_hit(object_point, hit);
+        self.normal_to_world(object_normal)
+    }
+
+    // converts a point from world space to object space, going through the
+    // transforms of every parent group first
+    fn world_to_object(&self, world_point: Tuple) -> Tuple {
+        let parent_point = self.parent_transform().inverse() * world_point;
+        self.transform().inverse() * parent_point
+    }
+
+    // converts a normal from object space back to world space, going out
+    // through this shape's transform and then every parent group
+    fn normal_to_world(&self, object_normal: Tuple) -> Tuple {
+        let mut normal = self.transform().inverse_transpose() * object_normal;
+        // the transpose of the inverse messes up w when there is a translation
+        normal.w = 0.0;
+        normal = normal.norm();
+
+        normal = self.parent_transform().inverse_transpose() * normal;
+        normal.w = 0.0;
+        normal.norm()
+    }
+}
+
+// Shapes are compared by identity, so an intersection can be checked against
+// the object it was computed from.
+impl PartialEq for dyn Shape + '_ {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::addr_eq(self, other)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::point;
+    use crate::vector;
+    use core::f64::consts::{FRAC_1_SQRT_2, PI};
+
+    // a shape with no geometry of its own, used to test the default methods
+    #[derive(Debug)]
+    struct TestShape {
+        transform: Transform,
+        material: Material,
+        parent_transform: Transform,
+    }
+
+    impl TestShape {
+        fn new(transform: Matrix4) -> Self {
+            Self {
+                transform: Transform::new(transform),
+                material: Material::default(),
+                parent_transform: Transform::default(),
+            }
+        }
+    }
+
+    impl Shape for TestShape {
+        fn transform(&self) -> &Transform {
+            &self.transform
+        }
+
+        fn set_transform(&mut self, transform: Matrix4) {
+            self.transform = Transform::new(transform);
+        }
+
+        fn parent_transform(&self) -> &Transform {
+            &self.parent_transform
+        }
+
+        fn set_parent_transform(&mut self, transform: Matrix4) {
+            self.parent_transform = Transform::new(transform);
+        }
+
+        fn material(&self) -> &Material {
+            &self.material
+        }
+
+        fn material_mut(&mut self) -> &mut Material {
+            &mut self.material
+        }
+
+        fn local_intersect(&self, _ray: &Ray) -> Vec<Intersection> {
+            vec![]
+        }
+
+        fn local_normal_at(&self, point: Tuple) -> Tuple {
+            vector!(point.x, point.y, point.z)
+        }
+
+        fn bounds(&self) -> BoundingBox {
+            BoundingBox::new(point!(-1.0, -1.0, -1.0), point!(1.0, 1.0, 1.0))
+        }
+    }
+
+    #[test]
+    fn test_normal_on_translated_shape() {
+        let shape = TestShape::new(Matrix4::identity().translation(0.0, 1.0, 0.0));
+
+        let n = shape.normal_at(point!(0.0, 1.0 + FRAC_1_SQRT_2, -FRAC_1_SQRT_2));
+
+        assert_eq!(n, vector!(0.0, FRAC_1_SQRT_2, -FRAC_1_SQRT_2));
+    }
+
+    #[test]
+    fn test_normal_on_transformed_shape() {
+        let shape = TestShape::new(
+            Matrix4::identity()
+                .rotation_z(PI / 5.0)
+                .scaling(1.0, 0.5, 1.0),
+        );
+
+        let n = shape.normal_at(point!(0.0, FRAC_1_SQRT_2, -FRAC_1_SQRT_2));
+
+        assert_eq!(n, vector!(0.0, 0.97014, -0.24254));
+    }
+
+    #[test]
+    fn test_bounds_in_parent_space() {
+        let shape = TestShape::new(
+            Matrix4::identity()
+                .scaling(0.5, 2.0, 4.0)
+                .translation(1.0, -3.0, 5.0),
+        );
+
+        let bounds = shape.parent_space_bounds();
+
+        assert_eq!(bounds.min, point!(0.5, -5.0, 1.0));
+        assert_eq!(bounds.max, point!(1.5, -1.0, 9.0));
+    }
+
+    #[test]
+    fn test_set_transform_updates_inverse_and_bounds() {
+        let mut shape = TestShape::new(Matrix4::identity());
+        shape.set_transform(Matrix4::identity().translation(0.0, 10.0, 0.0));
+
+        assert_eq!(
+            shape.world_to_object(point!(0.0, 10.0, 0.0)),
+            point!(0.0, 0.0, 0.0)
+        );
+        assert_eq!(shape.parent_space_bounds().min, point!(-1.0, 9.0, -1.0));
+        assert_eq!(shape.parent_space_bounds().max, point!(1.0, 11.0, 1.0));
+    }
+
+    #[test]
+    fn test_shapes_compare_by_identity() {
+        let a = TestShape::new(Matrix4::identity());
+        let b = TestShape::new(Matrix4::identity());
+
+        assert!(&a as &dyn Shape == &a as &dyn Shape);
+        assert!(&a as &dyn Shape != &b as &dyn Shape);
+    }
+}
@@ -0,0 +1,183 @@
+use crate::bounds::BoundingBox;
+use crate::material::*;
+use crate::matrix::*;
+use crate::point;
+use crate::ray::*;
+use crate::shape::Shape;
+use crate::tuple::*;
+
+#[derive(Debug, PartialEq)]
+pub struct Sphere {
+    transform: Transform,
+    pub material: Material,
+    parent_transform: Transform,
+}
+
+impl Sphere {
+    pub fn init() -> Sphere {
+        Sphere {
+            transform: Transform::default(),
+            material: Material::default(),
+            parent_transform: Transform::default(),
+        }
+    }
+
+    // a fully transparent sphere with the refractive index of glass
+    pub fn glass() -> Sphere {
+        let mut sphere = Sphere::init();
+        sphere.material.transparency = 1.0;
+        sphere.material.refractive_index = 1.5;
+        sphere
+    }
+}
+
+impl Shape for Sphere {
+    fn transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    fn set_transform(&mut self, transform: Matrix4) {
+        self.transform = Transform::new(transform);
+    }
+
+    fn parent_transform(&self) -> &Transform {
+        &self.parent_transform
+    }
+
+    fn set_parent_transform(&mut self, transform: Matrix4) {
+        self.parent_transform = Transform::new(transform);
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn material_mut(&mut self) -> &mut Material {
+        &mut self.material
+    }
+
+    fn local_intersect(&self, ray: &Ray) -> Vec<Intersection> {
+        let sphere_to_ray = ray.origin - point!(0.0, 0.0, 0.0);
+
+        let a = ray.direction * ray.direction;
+        let b = 2.0 * (ray.direction * sphere_to_ray);
+        let c = (sphere_to_ray * sphere_to_ray) - 1.0;
+        let discriminant = (b * b) - (4.0 * a * c);
+
+        if discriminant < 0.0 {
+            vec![]
+        } else {
+            let t1 = (-b - discriminant.sqrt()) / (2.0 * a);
+            let t2 = (-b + discriminant.sqrt()) / (2.0 * a);
+
+            vec![Intersection::new(t1, self), Intersection::new(t2, self)]
+        }
+    }
+
+    fn bounds(&self) -> BoundingBox {
+        BoundingBox::new(point!(-1.0, -1.0, -1.0), point!(1.0, 1.0, 1.0))
+    }
+
+    fn local_normal_at(&self, object_point: Tuple) -> Tuple {
+        object_point - point!(0.0, 0.0, 0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vector;
+    use core::f64::consts::{FRAC_1_SQRT_2, PI};
+
+    #[test]
+    fn test_sphere_normal() {
+        let sphere = Sphere::init();
+
+        assert_eq!(
+            sphere.normal_at(point!(1.0, 0.0, 0.0)),
+            vector!(1.0, 0.0, 0.0)
+        );
+        assert_eq!(
+            sphere.normal_at(point!(0.0, 1.0, 0.0)),
+            vector!(0.0, 1.0, 0.0)
+        );
+        assert_eq!(
+            sphere.normal_at(point!(0.0, 0.0, 1.0)),
+            vector!(0.0, 0.0, 1.0)
+        );
+
+        let nonaxial = point!(
+            (3.0_f64.sqrt() / 3.0),
+            (3.0_f64.sqrt() / 3.0),
+            (3.0_f64.sqrt() / 3.0)
+        );
+        let vector_result = vector!(
+            (3.0_f64.sqrt() / 3.0),
+            (3.0_f64.sqrt() / 3.0),
+            (3.0_f64.sqrt() / 3.0)
+        );
+
+        assert_eq!(sphere.normal_at(nonaxial), vector_result);
+
+        // assert a normalized vector is returned
+        let normal = sphere.normal_at(nonaxial);
+        let normalized = normal.norm();
+        assert_eq!(normal, normalized);
+    }
+
+    #[test]
+    fn test_sphere_transformation() {
+        let mut sphere = Sphere::init();
+        sphere.set_transform(Matrix4::identity().translation(0.0, 1.0, 0.0));
+
+        let n = sphere.normal_at(point!(0.0, 1.0 + FRAC_1_SQRT_2, -FRAC_1_SQRT_2));
+
+        assert_eq!(n, vector!(0.0, FRAC_1_SQRT_2, -FRAC_1_SQRT_2));
+    }
+
+    #[test]
+    fn test_sphere_transformation_two() {
+        let mut sphere = Sphere::init();
+        // order matters ?
+
+        sphere.set_transform(
+            Matrix4::identity()
+                .rotation_z(PI / 5.0)
+                .scaling(1.0, 0.5, 1.0),
+        );
+
+        let n = sphere.normal_at(point!(0.0, 2.0_f64.sqrt() / 2.0, -(2.0_f64.sqrt()) / 2.0));
+
+        assert_eq!(n, vector!(0.0, 0.97014, -0.24254));
+    }
+
+    #[test]
+    fn test_sphere_default_material() {
+        let sphere = Sphere::init();
+        assert_eq!(sphere.material, Material::default());
+    }
+
+    #[test]
+    fn test_intersect_scaled_sphere() {
+        let ray = Ray::new(point!(0.0, 0.0, -5.0), vector!(0.0, 0.0, 1.0));
+        let mut sphere = Sphere::init();
+        sphere.set_transform(Matrix4::identity().scaling(2.0, 2.0, 2.0));
+
+        let intersections = ray.intersect(&sphere);
+
+        assert_eq!(intersections.len(), 2);
+        assert_eq!(intersections[0].t, 3.0);
+        assert_eq!(intersections[1].t, 7.0);
+    }
+
+    #[test]
+    fn test_intersect_translated_sphere() {
+        let ray = Ray::new(point!(0.0, 0.0, -5.0), vector!(0.0, 0.0, 1.0));
+        let mut sphere = Sphere::init();
+        sphere.set_transform(Matrix4::identity().translation(5.0, 0.0, 0.0));
+
+        let intersections = ray.intersect(&sphere);
+
+        assert_eq!(intersections.len(), 0);
+    }
+}
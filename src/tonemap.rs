@@ -0,0 +1,262 @@
+use crate::canvas::Canvas;
+use crate::color::Color;
+use std::fmt::Debug;
+
+// ToneMap squeezes linear radiance, which has no upper bound, into 0.0..=1.0
+// before it is quantized by the 8 and 16 bit image formats.
+pub trait ToneMap: Debug + Send + Sync {
+    fn tone_map(&self, color: Color) -> Color;
+}
+
+fn clamp(color: Color) -> Color {
+    Color::new(
+        color.red.clamp(0.0, 1.0),
+        color.green.clamp(0.0, 1.0),
+        color.blue.clamp(0.0, 1.0),
+    )
+}
+
+fn luminance(color: &Color) -> f64 {
+    0.2126 * color.red + 0.7152 * color.green + 0.0722 * color.blue
+}
+
+// Cuts everything brighter than 1.0, which is what the exporters always did
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Clamp;
+
+impl ToneMap for Clamp {
+    fn tone_map(&self, color: Color) -> Color {
+        clamp(color)
+    }
+}
+
+// Reinhard's operator on luminance, so hues are kept. Luminance at white
+// maps to 1.0; with an infinite white nothing ever reaches it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Reinhard {
+    pub white: f64,
+}
+
+impl Reinhard {
+    pub fn new() -> Self {
+        Self {
+            white: f64::INFINITY,
+        }
+    }
+
+    pub fn with_white(white: f64) -> Self {
+        Self { white }
+    }
+}
+
+impl Default for Reinhard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ToneMap for Reinhard {
+    fn tone_map(&self, color: Color) -> Color {
+        let luminance = luminance(&color);
+        if luminance <= 0.0 {
+            return clamp(color);
+        }
+
+        let mapped = luminance * (1.0 + luminance / (self.white * self.white)) / (1.0 + luminance);
+        clamp(color * (mapped / luminance))
+    }
+}
+
+// Krzysztof Narkowicz's fit of the ACES filmic curve, per channel
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Aces;
+
+impl ToneMap for Aces {
+    fn tone_map(&self, color: Color) -> Color {
+        let curve = |x: f64| {
+            let x = x.max(0.0);
+            (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14)
+        };
+        clamp(Color::new(
+            curve(color.red),
+            curve(color.green),
+            curve(color.blue),
+        ))
+    }
+}
+
+pub fn linear_to_srgb(value: f64) -> f64 {
+    if value <= 0.003_130_8 {
+        12.92 * value
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+pub fn srgb_to_linear(value: f64) -> f64 {
+    if value <= 0.040_45 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+// The output stage: exposure in stops, then the tone map, then optionally the sRGB curve.
+// The default leaves colors as the exporters always wrote them.
+#[derive(Debug)]
+pub struct ToneMapping {
+    pub exposure: f64,
+    pub operator: Box<dyn ToneMap>,
+    pub srgb: bool,
+}
+
+impl Default for ToneMapping {
+    fn default() -> Self {
+        Self {
+            exposure: 0.0,
+            operator: Box::new(Clamp),
+            srgb: false,
+        }
+    }
+}
+
+impl ToneMapping {
+    pub fn apply(&self, color: Color) -> Color {
+        let mapped = self.operator.tone_map(color * 2f64.powf(self.exposure));
+        if self.srgb {
+            Color::new(
+                linear_to_srgb(mapped.red),
+                linear_to_srgb(mapped.green),
+                linear_to_srgb(mapped.blue),
+            )
+        } else {
+            mapped
+        }
+    }
+
+    pub fn apply_to_canvas(&self, canvas: &Canvas) -> Canvas {
+        let mut mapped = Canvas::init(canvas.width, canvas.height);
+        for y in 0..canvas.height {
+            for x in 0..canvas.width {
+                mapped.write_pixel(x, y, self.apply(*canvas.pixel_at(x, y)));
+            }
+        }
+        mapped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    fn gray(value: f64) -> Color {
+        Color::new(value, value, value)
+    }
+
+    #[test]
+    fn test_clamp() {
+        assert_eq!(
+            Clamp.tone_map(Color::new(1.9, 0.5, -0.2)),
+            Color::new(1.0, 0.5, 0.0)
+        );
+    }
+
+    #[test]
+    fn test_reinhard() {
+        let reinhard = Reinhard::new();
+
+        assert_eq!(reinhard.tone_map(gray(0.0)), gray(0.0));
+        assert_eq!(reinhard.tone_map(gray(1.0)), gray(0.5));
+        assert_eq!(reinhard.tone_map(gray(3.0)), gray(0.75));
+        assert!(reinhard.tone_map(gray(1000.0)).red < 1.0);
+    }
+
+    #[test]
+    fn test_reinhard_keeps_hue() {
+        let mapped = Reinhard::new().tone_map(Color::new(2.0, 1.0, 0.0));
+
+        assert_relative_eq!(mapped.red, 2.0 * mapped.green);
+        assert_eq!(mapped.blue, 0.0);
+    }
+
+    #[test]
+    fn test_reinhard_white_point() {
+        let reinhard = Reinhard::with_white(4.0);
+
+        assert_eq!(reinhard.tone_map(gray(4.0)), gray(1.0));
+        assert_eq!(reinhard.tone_map(gray(10.0)), gray(1.0));
+    }
+
+    #[test]
+    fn test_aces() {
+        assert_eq!(Aces.tone_map(gray(0.0)), gray(0.0));
+        assert_eq!(Aces.tone_map(gray(-1.0)), gray(0.0));
+        assert_eq!(Aces.tone_map(gray(1.0)), gray(0.804));
+        assert_eq!(Aces.tone_map(gray(100.0)), gray(1.0));
+    }
+
+    #[test]
+    fn test_srgb() {
+        assert_eq!(linear_to_srgb(0.0), 0.0);
+        assert_relative_eq!(linear_to_srgb(1.0), 1.0);
+        assert_relative_eq!(linear_to_srgb(0.002), 0.02584);
+        assert_relative_eq!(linear_to_srgb(0.5), 0.735_356_983, epsilon = 1e-9);
+
+        for &value in [0.0, 0.001, 0.02, 0.2, 0.5, 0.9, 1.0].iter() {
+            assert_relative_eq!(
+                srgb_to_linear(linear_to_srgb(value)),
+                value,
+                epsilon = 1e-12
+            );
+        }
+    }
+
+    #[test]
+    fn test_default_tone_mapping_only_clamps() {
+        let mapping = ToneMapping::default();
+
+        assert_eq!(
+            mapping.apply(Color::new(1.9, 0.5, -1.0)),
+            Color::new(1.0, 0.5, 0.0)
+        );
+    }
+
+    #[test]
+    fn test_exposure_in_stops() {
+        let mapping = ToneMapping {
+            exposure: -1.0,
+            ..ToneMapping::default()
+        };
+
+        assert_eq!(mapping.apply(gray(1.9)), gray(0.95));
+    }
+
+    #[test]
+    fn test_tone_mapping_stage() {
+        let mapping = ToneMapping {
+            exposure: 1.0,
+            operator: Box::new(Reinhard::new()),
+            srgb: true,
+        };
+
+        // 0.5 doubles to 1.0, Reinhard halves it, sRGB brightens it
+        assert_eq!(mapping.apply(gray(0.5)), gray(0.735));
+    }
+
+    #[test]
+    fn test_apply_to_canvas() {
+        let mut canvas = Canvas::init(2, 1);
+        canvas.write_pixel(1, 0, gray(3.0));
+        let mapping = ToneMapping {
+            operator: Box::new(Reinhard::new()),
+            ..ToneMapping::default()
+        };
+
+        let mapped = mapping.apply_to_canvas(&canvas);
+
+        assert_eq!(*mapped.pixel_at(0, 0), gray(0.0));
+        assert_eq!(*mapped.pixel_at(1, 0), gray(0.75));
+        assert_eq!(*canvas.pixel_at(1, 0), gray(3.0));
+    }
+}
@@ -0,0 +1,379 @@
+use crate::bounds::BoundingBox;
+use crate::material::*;
+use crate::matrix::*;
+use crate::ray::*;
+use crate::shape::Shape;
+use crate::tuple::*;
+
+// Intersects a ray with the triangle p1, p1 + e1, p1 + e2, returning the
+// distance and the barycentric coordinates of the hit
+fn intersect_triangle(ray: &Ray, p1: Tuple, e1: Tuple, e2: Tuple) -> Option<(f64, f64, f64)> {
+    let dir_cross_e2 = ray.direction.cross_product(e2);
+    let det = e1 * dir_cross_e2;
+    if det.abs() < PARALLEL_EPSILON {
+        return None;
+    }
+
+    let f = 1.0 / det;
+    let p1_to_origin = ray.origin - p1;
+    let u = f * (p1_to_origin * dir_cross_e2);
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+
+    let origin_cross_e1 = p1_to_origin.cross_product(e1);
+    let v = f * (ray.direction * origin_cross_e1);
+    if v < 0.0 || (u + v) > 1.0 {
+        return None;
+    }
+
+    let t = f * (e2 * origin_cross_e1);
+    Some((t, u, v))
+}
+
+// A flat triangle. Edges and normal are computed once in `new`, so the
+// vertices are only readable afterwards.
+#[derive(Debug, PartialEq)]
+pub struct Triangle {
+    p1: Tuple,
+    p2: Tuple,
+    p3: Tuple,
+    transform: Transform,
+    pub material: Material,
+    parent_transform: Transform,
+    e1: Tuple,
+    e2: Tuple,
+    normal: Tuple,
+}
+
+impl Triangle {
+    pub fn new(p1: Tuple, p2: Tuple, p3: Tuple) -> Triangle {
+        let e1 = p2 - p1;
+        let e2 = p3 - p1;
+        let normal = e2.cross_product(e1).norm();
+
+        Triangle {
+            p1,
+            p2,
+            p3,
+            transform: Transform::default(),
+            material: Material::default(),
+            parent_transform: Transform::default(),
+            e1,
+            e2,
+            normal,
+        }
+    }
+
+    pub fn p1(&self) -> Tuple {
+        self.p1
+    }
+
+    pub fn p2(&self) -> Tuple {
+        self.p2
+    }
+
+    pub fn p3(&self) -> Tuple {
+        self.p3
+    }
+
+    pub fn e1(&self) -> Tuple {
+        self.e1
+    }
+
+    pub fn e2(&self) -> Tuple {
+        self.e2
+    }
+
+    pub fn normal(&self) -> Tuple {
+        self.normal
+    }
+}
+
+impl Shape for Triangle {
+    fn transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    fn set_transform(&mut self, transform: Matrix4) {
+        self.transform = Transform::new(transform);
+    }
+
+    fn parent_transform(&self) -> &Transform {
+        &self.parent_transform
+    }
+
+    fn set_parent_transform(&mut self, transform: Matrix4) {
+        self.parent_transform = Transform::new(transform);
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn material_mut(&mut self) -> &mut Material {
+        &mut self.material
+    }
+
+    fn local_intersect(&self, ray: &Ray) -> Vec<Intersection> {
+        match intersect_triangle(ray, self.p1, self.e1, self.e2) {
+            Some((t, _, _)) => vec![Intersection::new(t, self)],
+            None => vec![],
+        }
+    }
+
+    fn bounds(&self) -> BoundingBox {
+        let mut bounds = BoundingBox::empty();
+        bounds.add_point(self.p1);
+        bounds.add_point(self.p2);
+        bounds.add_point(self.p3);
+        bounds
+    }
+
+    fn local_normal_at(&self, _object_point: Tuple) -> Tuple {
+        self.normal
+    }
+}
+
+// A triangle with a normal for each vertex, interpolated across the surface
+// using the u and v of the intersection, so meshes look smooth
+#[derive(Debug, PartialEq)]
+pub struct SmoothTriangle {
+    p1: Tuple,
+    p2: Tuple,
+    p3: Tuple,
+    n1: Tuple,
+    n2: Tuple,
+    n3: Tuple,
+    transform: Transform,
+    pub material: Material,
+    parent_transform: Transform,
+    e1: Tuple,
+    e2: Tuple,
+    normal: Tuple,
+}
+
+impl SmoothTriangle {
+    pub fn new(p1: Tuple, p2: Tuple, p3: Tuple, n1: Tuple, n2: Tuple, n3: Tuple) -> SmoothTriangle {
+        let e1 = p2 - p1;
+        let e2 = p3 - p1;
+        let normal = e2.cross_product(e1).norm();
+
+        SmoothTriangle {
+            p1,
+            p2,
+            p3,
+            n1,
+            n2,
+            n3,
+            transform: Transform::default(),
+            material: Material::default(),
+            parent_transform: Transform::default(),
+            e1,
+            e2,
+            normal,
+        }
+    }
+
+    pub fn p1(&self) -> Tuple {
+        self.p1
+    }
+
+    pub fn p2(&self) -> Tuple {
+        self.p2
+    }
+
+    pub fn p3(&self) -> Tuple {
+        self.p3
+    }
+
+    pub fn n1(&self) -> Tuple {
+        self.n1
+    }
+
+    pub fn n2(&self) -> Tuple {
+        self.n2
+    }
+
+    pub fn n3(&self) -> Tuple {
+        self.n3
+    }
+}
+
+impl Shape for SmoothTriangle {
+    fn transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    fn set_transform(&mut self, transform: Matrix4) {
+        self.transform = Transform::new(transform);
+    }
+
+    fn parent_transform(&self) -> &Transform {
+        &self.parent_transform
+    }
+
+    fn set_parent_transform(&mut self, transform: Matrix4) {
+        self.parent_transform = Transform::new(transform);
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn material_mut(&mut self) -> &mut Material {
+        &mut self.material
+    }
+
+    fn local_intersect(&self, ray: &Ray) -> Vec<Intersection> {
+        match intersect_triangle(ray, self.p1, self.e1, self.e2) {
+            Some((t, u, v)) => vec![Intersection::with_uv(t, self, u, v)],
+            None => vec![],
+        }
+    }
+
+    // without an intersection there is nothing to interpolate with
+    fn bounds(&self) -> BoundingBox {
+        let mut bounds = BoundingBox::empty();
+        bounds.add_point(self.p1);
+        bounds.add_point(self.p2);
+        bounds.add_point(self.p3);
+        bounds
+    }
+
+    fn local_normal_at(&self, _object_point: Tuple) -> Tuple {
+        self.normal
+    }
+
+    fn local_normal_at_hit(&self, _object_point: Tuple, hit: &Intersection) -> Tuple {
+        self.n2 * hit.u + self.n3 * hit.v + self.n1 * (1.0 - hit.u - hit.v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::point;
+    use crate::tuple::EPSILON;
+    use crate::vector;
+    use approx::assert_relative_eq;
+
+    fn triangle() -> Triangle {
+        Triangle::new(
+            point!(0.0, 1.0, 0.0),
+            point!(-1.0, 0.0, 0.0),
+            point!(1.0, 0.0, 0.0),
+        )
+    }
+
+    fn smooth_triangle() -> SmoothTriangle {
+        SmoothTriangle::new(
+            point!(0.0, 1.0, 0.0),
+            point!(-1.0, 0.0, 0.0),
+            point!(1.0, 0.0, 0.0),
+            vector!(0.0, 1.0, 0.0),
+            vector!(-1.0, 0.0, 0.0),
+            vector!(1.0, 0.0, 0.0),
+        )
+    }
+
+    #[test]
+    fn test_constructing_a_triangle() {
+        let t = triangle();
+
+        assert_eq!(t.p1(), point!(0.0, 1.0, 0.0));
+        assert_eq!(t.p2(), point!(-1.0, 0.0, 0.0));
+        assert_eq!(t.p3(), point!(1.0, 0.0, 0.0));
+        assert_eq!(t.e1(), vector!(-1.0, -1.0, 0.0));
+        assert_eq!(t.e2(), vector!(1.0, -1.0, 0.0));
+        assert_eq!(t.normal(), vector!(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn test_triangle_normal_is_constant() {
+        let t = triangle();
+
+        assert_eq!(t.local_normal_at(point!(0.0, 0.5, 0.0)), t.normal());
+        assert_eq!(t.local_normal_at(point!(-0.5, 0.75, 0.0)), t.normal());
+        assert_eq!(t.local_normal_at(point!(0.5, 0.25, 0.0)), t.normal());
+    }
+
+    #[test]
+    fn test_ray_parallel_to_triangle() {
+        let t = triangle();
+        let ray = Ray::new(point!(0.0, -1.0, -2.0), vector!(0.0, 1.0, 0.0));
+
+        assert_eq!(t.local_intersect(&ray).len(), 0);
+    }
+
+    #[test]
+    fn test_ray_misses_triangle_edges() {
+        let t = triangle();
+        // p1-p3, p1-p2 and p2-p3 edges
+        let origins = [
+            point!(1.0, 1.0, -2.0),
+            point!(-1.0, 1.0, -2.0),
+            point!(0.0, -1.0, -2.0),
+        ];
+
+        for origin in origins {
+            let ray = Ray::new(origin, vector!(0.0, 0.0, 1.0));
+            assert_eq!(t.local_intersect(&ray).len(), 0);
+        }
+    }
+
+    #[test]
+    fn test_ray_strikes_triangle() {
+        let t = triangle();
+        let ray = Ray::new(point!(0.0, 0.5, -2.0), vector!(0.0, 0.0, 1.0));
+
+        let intersections = t.local_intersect(&ray);
+
+        assert_eq!(intersections.len(), 1);
+        assert_eq!(intersections[0].t, 2.0);
+    }
+
+    #[test]
+    fn test_constructing_a_smooth_triangle() {
+        let t = smooth_triangle();
+
+        assert_eq!(t.p1(), point!(0.0, 1.0, 0.0));
+        assert_eq!(t.p2(), point!(-1.0, 0.0, 0.0));
+        assert_eq!(t.p3(), point!(1.0, 0.0, 0.0));
+        assert_eq!(t.n1(), vector!(0.0, 1.0, 0.0));
+        assert_eq!(t.n2(), vector!(-1.0, 0.0, 0.0));
+        assert_eq!(t.n3(), vector!(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_intersection_with_smooth_triangle_stores_uv() {
+        let t = smooth_triangle();
+        let ray = Ray::new(point!(-0.2, 0.3, -2.0), vector!(0.0, 0.0, 1.0));
+
+        let intersections = t.local_intersect(&ray);
+
+        assert_relative_eq!(intersections[0].u, 0.45, epsilon = EPSILON);
+        assert_relative_eq!(intersections[0].v, 0.25, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn test_smooth_triangle_interpolates_normal() {
+        let t = smooth_triangle();
+        let intersection = Intersection::with_uv(1.0, &t, 0.45, 0.25);
+
+        let normal = t.normal_at_hit(point!(0.0, 0.0, 0.0), &intersection);
+
+        assert_eq!(normal, vector!(-0.5547, 0.83205, 0.0));
+    }
+
+    #[test]
+    fn test_prepare_normal_on_smooth_triangle() {
+        let t = smooth_triangle();
+        let intersection = Intersection::with_uv(1.0, &t, 0.45, 0.25);
+        let ray = Ray::new(point!(-0.2, 0.3, -2.0), vector!(0.0, 0.0, 1.0));
+
+        let comps = intersection.prepare_computations(&ray, &[intersection.clone()]);
+
+        assert_eq!(comps.normalv, vector!(-0.5547, 0.83205, 0.0));
+    }
+}
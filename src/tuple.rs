@@ -1,5 +1,5 @@
-use crate::matrix;
 use crate::matrix::*;
+use crate::matrix4;
 use std::ops::{Add, Mul, Sub};
 
 #[derive(Debug, Clone, Copy)]
@@ -89,7 +89,7 @@ impl Tuple {
     }
 
     pub fn translation(&self, x: f64, y: f64, z: f64) -> Self {
-        let transform = matrix![ 1.0, 0.0, 0.0, x;
+        let transform = matrix4![ 1.0, 0.0, 0.0, x;
                                  0.0, 1.0, 0.0, y;
                                  0.0, 0.0, 1.0, z;
                                  0.0, 0.0, 0.0, 1.0];
@@ -97,7 +97,7 @@ impl Tuple {
     }
 
     pub fn scaling(&self, x: f64, y: f64, z: f64) -> Self {
-        let transform = matrix![   x, 0.0, 0.0, 0.0;
+        let transform = matrix4![   x, 0.0, 0.0, 0.0;
                                  0.0,   y, 0.0, 0.0;
                                  0.0, 0.0,   z, 0.0;
                                  0.0, 0.0, 0.0, 1.0];
@@ -105,7 +105,7 @@ impl Tuple {
     }
 
     pub fn rotation_x(&self, x: f64) -> Self {
-        let transform = matrix![ 1.0, 0.0, 0.0, 0.0;
+        let transform = matrix4![ 1.0, 0.0, 0.0, 0.0;
                                  0.0, x.cos(), -x.sin(), 0.0;
                                  0.0, x.sin(),  x.cos(), 0.0;
                                  0.0, 0.0, 0.0, 1.0];
@@ -113,7 +113,7 @@ impl Tuple {
     }
 
     pub fn rotation_y(&self, x: f64) -> Self {
-        let transform = matrix![ x.cos(), 0.0, x.sin(), 0.0;
+        let transform = matrix4![ x.cos(), 0.0, x.sin(), 0.0;
                                  0.0, 1.0, 0.0, 0.0;
                                  -x.sin(), 0.0, x.cos(), 0.0;
                                  0.0, 0.0, 0.0, 1.0];
@@ -121,7 +121,7 @@ impl Tuple {
     }
 
     pub fn rotation_z(&self, x: f64) -> Self {
-        let transform = matrix![ x.cos(), -x.sin(), 0.0, 0.0;
+        let transform = matrix4![ x.cos(), -x.sin(), 0.0, 0.0;
                                  x.sin(), x.cos(), 0.0, 0.0;
                                  0.0, 0.0, 1.0, 0.0;
                                  0.0, 0.0, 0.0, 1.0];
@@ -129,7 +129,7 @@ impl Tuple {
     }
 
     pub fn skew(&self, x_y: f64, x_z: f64, y_x: f64, y_z: f64, z_x: f64, z_y: f64) -> Self {
-        let transform = matrix![ 1.0, x_y, x_z, 0.0;
+        let transform = matrix4![ 1.0, x_y, x_z, 0.0;
                                   y_x, 1.0, y_z, 0.0;
                                   z_x, z_y, 1.0, 0.0;
                                   0.0, 0.0, 0.0, 1.0];
@@ -140,6 +140,12 @@ impl Tuple {
 
 pub const EPSILON: f64 = 0.001;
 
+// For deciding that a ray is parallel to a surface. The quantities tested
+// shrink with the object space direction as shapes are scaled up, and with
+// the size of small shapes like mesh triangles, so EPSILON would take rays
+// that do cross them for parallel ones.
+pub const PARALLEL_EPSILON: f64 = 1e-12;
+
 impl PartialEq for Tuple {
     fn eq(&self, other: &Self) -> bool {
         let x_diff = (self.x - other.x).abs() <= EPSILON;
@@ -1,3 +1,4 @@
+use crate::bvh::Bvh;
 use crate::color::*;
 use crate::light;
 use crate::light::*;
@@ -5,12 +6,20 @@ use crate::material::*;
 use crate::matrix::*;
 use crate::point;
 use crate::ray::*;
+use crate::shape::Shape;
+use crate::sphere::Sphere;
 use crate::tuple::*;
 
+// how many times a ray may bounce between reflective surfaces
+pub const MAX_DEPTH: usize = 5;
+
 // World struct contains a list of all objects in the scene and a light source
 pub struct World {
-    pub objects: Vec<Sphere>,
+    objects: Vec<Box<dyn Shape>>,
     pub lights: Vec<Light>,
+    pub max_depth: usize,
+    // see build_bvh
+    bvh: Option<Bvh>,
 }
 
 impl World {
@@ -18,97 +27,207 @@ impl World {
         Self {
             objects: vec![],
             lights: vec![],
+            max_depth: MAX_DEPTH,
+            bvh: None,
         }
     }
 
-    pub fn default() -> Self {
-        let mut world = Self::new();
-
-        let light = Light {
-            position: point!(-10.0, 10.0, -10.0),
-            intensity: Color::new(1.0, 1.0, 1.0),
-        };
-
-        world.lights.push(light);
-
-        let material = Material {
-            ambient: 0.1,
-            diffuse: 0.7,
-            specular: 0.2,
-            shininess: 200.0,
-            color: Color::new(0.8, 1.0, 0.6),
-        };
-
-        let mut s1 = Sphere::init();
-        s1.material = material;
+    pub fn add_object(&mut self, object: Box<dyn Shape>) {
+        self.objects.push(object);
+        self.bvh = None;
+    }
 
-        let mut s2 = Sphere::init();
-        s2.transform = Matrix::identity(4).scaling(0.5, 0.5, 0.5);
+    pub fn objects(&self) -> &[Box<dyn Shape>] {
+        &self.objects
+    }
 
-        world.objects.push(s1);
-        world.objects.push(s2);
+    // The object can be moved or replaced through the returned box, so this
+    // drops the hierarchy built from the old bounds.
+    pub fn object_mut(&mut self, index: usize) -> &mut Box<dyn Shape> {
+        self.bvh = None;
+        &mut self.objects[index]
+    }
 
-        world
+    // Builds a bounding volume hierarchy over the objects, so intersect only
+    // tests the objects near a ray. Adding or changing an object drops it, and
+    // intersect goes back to testing every object until this is called again.
+    pub fn build_bvh(&mut self) {
+        self.bvh = Some(Bvh::build(&self.objects));
     }
 
-    pub fn intersect<'a>(&'a self, ray: &'a Ray) -> Vec<Intersection> {
-        // iterate over all objects in the world and collect all intersections
-        let mut intersections = vec![];
-        for object in &self.objects {
-            let mut isects = ray.intersect(object);
-            intersections.append(&mut isects);
-        }
+    pub fn intersect<'a>(&'a self, ray: &Ray) -> Vec<Intersection<'a>> {
+        let mut intersections = match &self.bvh {
+            Some(bvh) => bvh.intersect(&self.objects, ray),
+            // iterate over all objects in the world and collect all intersections
+            _ => {
+                let mut intersections = vec![];
+                for object in &self.objects {
+                    let mut isects = ray.intersect(object.as_ref());
+                    intersections.append(&mut isects);
+                }
+                intersections
+            }
+        };
         // sort intersections by t value
         intersections.sort_by(|a, b| a.t.partial_cmp(&b.t).unwrap());
 
         intersections
     }
 
-    pub fn shade_hit(&self, comps: &Computation) -> Color {
+    pub fn shade_hit(&self, comps: &Computation, remaining: usize) -> Color {
         let mut current_color = Color::new(0.0, 0.0, 0.0);
         for light in &self.lights {
+            let in_shadow = self.is_shadowed(comps.over_point, light);
             let color = light::lightning(
-                &comps.object.material,
+                comps.object.material(),
+                comps.object,
                 light,
-                comps.point,
+                comps.over_point,
                 comps.eyev,
                 comps.normalv,
+                in_shadow,
             );
             current_color = current_color + color;
         }
-        return current_color;
+
+        let reflected = self.reflected_color(comps, remaining);
+        let refracted = self.refracted_color(comps, remaining);
+
+        let material = comps.object.material();
+        if material.reflective > 0.0 && material.transparency > 0.0 {
+            let reflectance = comps.schlick();
+            current_color + reflected * reflectance + refracted * (1.0 - reflectance)
+        } else {
+            current_color + reflected + refracted
+        }
+    }
+
+    // color seen along the reflection vector, bounded by the remaining depth
+    // so that two facing mirrors do not recurse forever
+    pub fn reflected_color(&self, comps: &Computation, remaining: usize) -> Color {
+        let reflective = comps.object.material().reflective;
+        if remaining == 0 || reflective == 0.0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+
+        let reflect_ray = Ray::new(comps.over_point, comps.reflectv);
+        let color = self.color_at_depth(&reflect_ray, remaining - 1);
+
+        color * reflective
+    }
+
+    // a point is in shadow when something sits between it and the light
+    pub fn is_shadowed(&self, point: Tuple, light: &Light) -> bool {
+        let v = light.position - point;
+        let distance = v.magnitude();
+        let ray = Ray::new(point, v.norm());
+
+        let intersections = self.intersect(&ray);
+        match hit(&intersections) {
+            Some(intersection) => intersection.t < distance,
+            None => false,
+        }
+    }
+
+    // color seen through a transparent surface, bending the ray with Snell's law
+    pub fn refracted_color(&self, comps: &Computation, remaining: usize) -> Color {
+        let transparency = comps.object.material().transparency;
+        if remaining == 0 || transparency == 0.0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+
+        let n_ratio = comps.n1 / comps.n2;
+        let cos_i = comps.eyev * comps.normalv;
+        let sin2_t = n_ratio * n_ratio * (1.0 - cos_i * cos_i);
+        // total internal reflection, no light gets through
+        if sin2_t > 1.0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+
+        let cos_t = (1.0 - sin2_t).sqrt();
+        let direction = comps.normalv * (n_ratio * cos_i - cos_t) - comps.eyev * n_ratio;
+        let refract_ray = Ray::new(comps.under_point, direction);
+
+        self.color_at_depth(&refract_ray, remaining - 1) * transparency
     }
 
     pub fn color_at(&self, ray: &Ray) -> Color {
+        self.color_at_depth(ray, self.max_depth)
+    }
+
+    pub fn color_at_depth(&self, ray: &Ray, remaining: usize) -> Color {
         let intersections = self.intersect(ray);
-        if intersections.len() == 0 {
-            return Color::new(0.0, 0.0, 0.0);
+        match hit(&intersections) {
+            Some(intersection) => {
+                let comps = intersection.prepare_computations(ray, &intersections);
+                self.shade_hit(&comps, remaining)
+            }
+            None => Color::new(0.0, 0.0, 0.0),
         }
-        let intersection = intersections.into_iter().find(|i| i.t >= 0.0).unwrap();
-        let comps = intersection.prepare_computations(ray);
-        return self.shade_hit(&comps);
+    }
+}
+
+impl Default for World {
+    // the default world from the book: two concentric spheres and a single light
+    fn default() -> Self {
+        let mut world = Self::new();
+
+        let light = Light {
+            position: point!(-10.0, 10.0, -10.0),
+            intensity: Color::new(1.0, 1.0, 1.0),
+        };
+
+        world.lights.push(light);
+
+        let material = Material {
+            ambient: 0.1,
+            diffuse: 0.7,
+            specular: 0.2,
+            shininess: 200.0,
+            color: Color::new(0.8, 1.0, 0.6),
+            ..Material::default()
+        };
+
+        let mut s1 = Sphere::init();
+        s1.material = material;
+
+        let mut s2 = Sphere::init();
+        s2.set_transform(Matrix4::identity().scaling(0.5, 0.5, 0.5));
+
+        world.add_object(Box::new(s1));
+        world.add_object(Box::new(s2));
+
+        world
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::cube::Cube;
+    use crate::pattern::test_pattern;
+    use crate::plane::Plane;
     use crate::ray::Ray;
     use crate::vector;
+    use core::f64::consts::FRAC_1_SQRT_2;
+    use std::sync::Arc;
     // test default world contains default objects and light source
     #[test]
     fn test_default_world() {
         let world = World::default();
 
-        assert_eq!(world.objects.len(), 2);
-        let sphere = &world.objects[0];
-        assert_eq!(sphere.material.color, Color::new(0.8, 1.0, 0.6));
-        assert_eq!(sphere.material.ambient, 0.1);
-        assert_eq!(sphere.material.diffuse, 0.7);
-        assert_eq!(sphere.material.specular, 0.2);
+        assert_eq!(world.objects().len(), 2);
+        let sphere = &world.objects()[0];
+        assert_eq!(sphere.material().color, Color::new(0.8, 1.0, 0.6));
+        assert_eq!(sphere.material().ambient, 0.1);
+        assert_eq!(sphere.material().diffuse, 0.7);
+        assert_eq!(sphere.material().specular, 0.2);
 
-        let sphere = &world.objects[1];
-        assert_eq!(sphere.transform, Matrix::identity(4).scaling(0.5, 0.5, 0.5));
+        let sphere = &world.objects()[1];
+        assert_eq!(
+            sphere.transform(),
+            &Matrix4::identity().scaling(0.5, 0.5, 0.5)
+        );
 
         let light = &world.lights[0];
         assert_eq!(light.position, point!(-10.0, 10.0, -10.0));
@@ -157,15 +276,403 @@ mod tests {
     #[test]
     fn test_color_ray_behind() {
         let mut world = World::default();
-        let outer = &mut world.objects[0];
-        outer.material.ambient = 1.0;
-        let inner = &mut world.objects[1];
-        inner.material.ambient = 1.0;
+        let outer = world.object_mut(0);
+        outer.material_mut().ambient = 1.0;
+        let inner = world.object_mut(1);
+        inner.material_mut().ambient = 1.0;
 
         let ray = Ray::new(point!(0.0, 0.0, 0.75), vector!(0.0, 0.0, -1.0));
 
         let color = world.color_at(&ray);
 
-        assert_eq!(color, world.objects[1].material.color);
+        assert_eq!(color, world.objects()[1].material().color);
+    }
+
+    // test a world can mix planes and spheres
+    #[test]
+    fn test_intersect_world_with_plane() {
+        let mut world = World::default();
+        let mut floor = Plane::init();
+        floor.set_transform(Matrix4::identity().translation(0.0, -1.0, 0.0));
+        world.add_object(Box::new(floor));
+
+        let ray = Ray::new(point!(0.0, 1.0, -5.0), vector!(0.0, -1.0, 0.0));
+        let intersections = world.intersect(&ray);
+
+        assert_eq!(intersections.len(), 1);
+        assert_eq!(intersections[0].t, 2.0);
+        assert_eq!(intersections[0].object, world.objects()[2].as_ref());
+    }
+
+    // bvh tests
+    #[test]
+    fn test_intersect_world_with_bvh() {
+        let mut world = World::default();
+        let ray = Ray::new(point!(0.0, 0.0, -5.0), vector!(0.0, 0.0, 1.0));
+        let expected: Vec<f64> = world.intersect(&ray).iter().map(|i| i.t).collect();
+
+        world.build_bvh();
+        let intersections = world.intersect(&ray);
+
+        assert_eq!(
+            intersections.iter().map(|i| i.t).collect::<Vec<f64>>(),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_adding_an_object_drops_the_bvh() {
+        let mut world = World::default();
+        world.build_bvh();
+        let mut floor = Plane::init();
+        floor.set_transform(Matrix4::identity().translation(0.0, -1.0, 0.0));
+        world.add_object(Box::new(floor));
+
+        let ray = Ray::new(point!(0.0, 1.0, -5.0), vector!(0.0, -1.0, 0.0));
+
+        assert_eq!(world.intersect(&ray).len(), 1);
+    }
+
+    #[test]
+    fn test_moving_an_object_drops_the_bvh() {
+        let mut world = World::default();
+        world.build_bvh();
+        world
+            .object_mut(1)
+            .set_transform(Matrix4::identity().translation(0.0, 10.0, 0.0));
+
+        let ray = Ray::new(point!(0.0, 10.0, -5.0), vector!(0.0, 0.0, 1.0));
+
+        assert_eq!(world.intersect(&ray).len(), 2);
+    }
+
+    #[test]
+    fn test_replacing_an_object_drops_the_bvh() {
+        let mut world = World::default();
+        world.build_bvh();
+        let mut sphere = Sphere::init();
+        sphere.set_transform(Matrix4::identity().translation(0.0, 10.0, 0.0));
+        *world.object_mut(1) = Box::new(sphere);
+
+        let ray = Ray::new(point!(0.0, 10.0, -5.0), vector!(0.0, 0.0, 1.0));
+        let intersections = world.intersect(&ray);
+
+        assert_eq!(intersections.len(), 2);
+        assert_eq!(intersections[0].object, world.objects()[1].as_ref());
+    }
+
+    // shadow tests
+    #[test]
+    fn test_no_shadow_when_nothing_is_collinear() {
+        let world = World::default();
+        let point = point!(0.0, 10.0, 0.0);
+
+        assert!(!world.is_shadowed(point, &world.lights[0]));
+    }
+
+    #[test]
+    fn test_shadow_when_object_between_point_and_light() {
+        let world = World::default();
+        let point = point!(10.0, -10.0, 10.0);
+
+        assert!(world.is_shadowed(point, &world.lights[0]));
+    }
+
+    #[test]
+    fn test_no_shadow_when_object_behind_light() {
+        let world = World::default();
+        let point = point!(-20.0, 20.0, -20.0);
+
+        assert!(!world.is_shadowed(point, &world.lights[0]));
+    }
+
+    #[test]
+    fn test_no_shadow_when_object_behind_point() {
+        let world = World::default();
+        let point = point!(-2.0, 2.0, -2.0);
+
+        assert!(!world.is_shadowed(point, &world.lights[0]));
+    }
+
+    #[test]
+    fn test_shade_hit_in_shadow() {
+        let mut world = World::new();
+        world.lights.push(Light {
+            position: point!(0.0, 0.0, -10.0),
+            intensity: Color::new(1.0, 1.0, 1.0),
+        });
+        world.add_object(Box::new(Sphere::init()));
+        let mut s2 = Sphere::init();
+        s2.set_transform(Matrix4::identity().translation(0.0, 0.0, 10.0));
+        world.add_object(Box::new(s2));
+
+        let ray = Ray::new(point!(0.0, 0.0, 5.0), vector!(0.0, 0.0, 1.0));
+        let intersection = Intersection::new(4.0, world.objects()[1].as_ref());
+        let comps = intersection.prepare_computations(&ray, &[intersection.clone()]);
+
+        assert_eq!(
+            world.shade_hit(&comps, world.max_depth),
+            Color::new(0.1, 0.1, 0.1)
+        );
+    }
+
+    #[test]
+    fn test_thin_object_on_a_floor_casts_a_shadow() {
+        let mut world = World::new();
+        world.lights.push(Light {
+            position: point!(0.0, 10.0, 0.0),
+            intensity: Color::new(1.0, 1.0, 1.0),
+        });
+        world.add_object(Box::new(Plane::init()));
+        // a sheet from 0.0002 to 0.0006 above the floor
+        let mut sheet = Cube::init();
+        sheet.set_transform(
+            Matrix4::identity()
+                .scaling(1.0, 0.0002, 1.0)
+                .translation(0.0, 0.0004, 0.0),
+        );
+        world.add_object(Box::new(sheet));
+
+        let ray = Ray::new(point!(0.0, 1.0, -1.0), vector!(0.0, -1.0, 1.0).norm());
+        let intersection = Intersection::new(2.0_f64.sqrt(), world.objects()[0].as_ref());
+        let comps = intersection.prepare_computations(&ray, &[intersection.clone()]);
+
+        // the shadow ray has to start under the sheet
+        assert!(comps.over_point.y < 0.0002);
+        assert!(world.is_shadowed(comps.over_point, &world.lights[0]));
+    }
+
+    // reflection tests
+    #[test]
+    fn test_reflected_color_for_nonreflective_material() {
+        let mut world = World::default();
+        world.object_mut(1).material_mut().ambient = 1.0;
+
+        let ray = Ray::new(point!(0.0, 0.0, 0.0), vector!(0.0, 0.0, 1.0));
+        let intersection = Intersection::new(1.0, world.objects()[1].as_ref());
+        let comps = intersection.prepare_computations(&ray, &[intersection.clone()]);
+
+        assert_eq!(
+            world.reflected_color(&comps, world.max_depth),
+            Color::new(0.0, 0.0, 0.0)
+        );
+    }
+
+    fn world_with_reflective_plane() -> World {
+        let mut world = World::default();
+        let mut plane = Plane::init();
+        plane.material.reflective = 0.5;
+        plane.set_transform(Matrix4::identity().translation(0.0, -1.0, 0.0));
+        world.add_object(Box::new(plane));
+        world
+    }
+
+    #[test]
+    fn test_reflected_color_for_reflective_material() {
+        let world = world_with_reflective_plane();
+
+        let ray = Ray::new(
+            point!(0.0, 0.0, -3.0),
+            vector!(0.0, -FRAC_1_SQRT_2, FRAC_1_SQRT_2),
+        );
+        let intersection = Intersection::new(2.0_f64.sqrt(), world.objects()[2].as_ref());
+        let comps = intersection.prepare_computations(&ray, &[intersection.clone()]);
+
+        assert_eq!(
+            world.reflected_color(&comps, world.max_depth),
+            Color::new(0.19032, 0.2379, 0.14274)
+        );
+    }
+
+    #[test]
+    fn test_shade_hit_with_reflective_material() {
+        let world = world_with_reflective_plane();
+
+        let ray = Ray::new(
+            point!(0.0, 0.0, -3.0),
+            vector!(0.0, -FRAC_1_SQRT_2, FRAC_1_SQRT_2),
+        );
+        let intersection = Intersection::new(2.0_f64.sqrt(), world.objects()[2].as_ref());
+        let comps = intersection.prepare_computations(&ray, &[intersection.clone()]);
+
+        assert_eq!(
+            world.shade_hit(&comps, world.max_depth),
+            Color::new(0.87677, 0.92436, 0.82918)
+        );
+    }
+
+    #[test]
+    fn test_reflected_color_at_maximum_depth() {
+        let world = world_with_reflective_plane();
+
+        let ray = Ray::new(
+            point!(0.0, 0.0, -3.0),
+            vector!(0.0, -FRAC_1_SQRT_2, FRAC_1_SQRT_2),
+        );
+        let intersection = Intersection::new(2.0_f64.sqrt(), world.objects()[2].as_ref());
+        let comps = intersection.prepare_computations(&ray, &[intersection.clone()]);
+
+        assert_eq!(world.reflected_color(&comps, 0), Color::new(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_mutually_reflective_surfaces_terminate() {
+        let mut world = World::new();
+        world.lights.push(Light {
+            position: point!(0.0, 0.0, 0.0),
+            intensity: Color::new(1.0, 1.0, 1.0),
+        });
+
+        let mut lower = Plane::init();
+        lower.material.reflective = 1.0;
+        lower.set_transform(Matrix4::identity().translation(0.0, -1.0, 0.0));
+        world.add_object(Box::new(lower));
+
+        let mut upper = Plane::init();
+        upper.material.reflective = 1.0;
+        upper.set_transform(
+            Matrix4::identity()
+                .rotation_x(std::f64::consts::PI)
+                .translation(0.0, 1.0, 0.0),
+        );
+        world.add_object(Box::new(upper));
+
+        let ray = Ray::new(point!(0.0, 0.0, 0.0), vector!(0.0, 1.0, 0.0));
+
+        // stops after max_depth bounces instead of overflowing the stack
+        let color = world.color_at(&ray);
+        assert!(color.red > 1.0);
+    }
+
+    // refraction tests
+    #[test]
+    fn test_refracted_color_with_opaque_surface() {
+        let world = World::default();
+        let ray = Ray::new(point!(0.0, 0.0, -5.0), vector!(0.0, 0.0, 1.0));
+        let shape = world.objects()[0].as_ref();
+        let intersections = vec![Intersection::new(4.0, shape), Intersection::new(6.0, shape)];
+
+        let comps = intersections[0].prepare_computations(&ray, &intersections);
+
+        assert_eq!(world.refracted_color(&comps, 5), Color::new(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_refracted_color_at_maximum_depth() {
+        let mut world = World::default();
+        world.object_mut(0).material_mut().transparency = 1.0;
+        world.object_mut(0).material_mut().refractive_index = 1.5;
+        let ray = Ray::new(point!(0.0, 0.0, -5.0), vector!(0.0, 0.0, 1.0));
+        let shape = world.objects()[0].as_ref();
+        let intersections = vec![Intersection::new(4.0, shape), Intersection::new(6.0, shape)];
+
+        let comps = intersections[0].prepare_computations(&ray, &intersections);
+
+        assert_eq!(world.refracted_color(&comps, 0), Color::new(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_refracted_color_under_total_internal_reflection() {
+        let mut world = World::default();
+        world.object_mut(0).material_mut().transparency = 1.0;
+        world.object_mut(0).material_mut().refractive_index = 1.5;
+        let ray = Ray::new(point!(0.0, 0.0, FRAC_1_SQRT_2), vector!(0.0, 1.0, 0.0));
+        let shape = world.objects()[0].as_ref();
+        let intersections = vec![
+            Intersection::new(-FRAC_1_SQRT_2, shape),
+            Intersection::new(FRAC_1_SQRT_2, shape),
+        ];
+
+        // inside the sphere, so look at the second intersection
+        let comps = intersections[1].prepare_computations(&ray, &intersections);
+
+        assert_eq!(world.refracted_color(&comps, 5), Color::new(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_refracted_color_with_refracted_ray() {
+        let mut world = World::default();
+        let a = world.object_mut(0).material_mut();
+        a.ambient = 1.0;
+        a.pattern = Some(Arc::new(test_pattern()));
+        let b = world.object_mut(1).material_mut();
+        b.transparency = 1.0;
+        b.refractive_index = 1.5;
+
+        let ray = Ray::new(point!(0.0, 0.0, 0.1), vector!(0.0, 1.0, 0.0));
+        let a = world.objects()[0].as_ref();
+        let b = world.objects()[1].as_ref();
+        let intersections = vec![
+            Intersection::new(-0.9899, a),
+            Intersection::new(-0.4899, b),
+            Intersection::new(0.4899, b),
+            Intersection::new(0.9899, a),
+        ];
+
+        let comps = intersections[2].prepare_computations(&ray, &intersections);
+
+        assert_eq!(
+            world.refracted_color(&comps, 5),
+            Color::new(0.0, 0.99888, 0.04725)
+        );
+    }
+
+    fn world_with_glass_floor(reflective: f64) -> World {
+        let mut world = World::default();
+
+        let mut floor = Plane::init();
+        floor.set_transform(Matrix4::identity().translation(0.0, -1.0, 0.0));
+        floor.material.transparency = 0.5;
+        floor.material.reflective = reflective;
+        floor.material.refractive_index = 1.5;
+        world.add_object(Box::new(floor));
+
+        let mut ball = Sphere::init();
+        ball.material.color = Color::new(1.0, 0.0, 0.0);
+        ball.material.ambient = 0.5;
+        ball.set_transform(Matrix4::identity().translation(0.0, -3.5, -0.5));
+        world.add_object(Box::new(ball));
+
+        world
+    }
+
+    #[test]
+    fn test_shade_hit_with_transparent_material() {
+        let world = world_with_glass_floor(0.0);
+        let ray = Ray::new(
+            point!(0.0, 0.0, -3.0),
+            vector!(0.0, -FRAC_1_SQRT_2, FRAC_1_SQRT_2),
+        );
+        let intersections = vec![Intersection::new(
+            2.0_f64.sqrt(),
+            world.objects()[2].as_ref(),
+        )];
+
+        let comps = intersections[0].prepare_computations(&ray, &intersections);
+
+        assert_eq!(
+            world.shade_hit(&comps, 5),
+            Color::new(0.93642, 0.68642, 0.68642)
+        );
+    }
+
+    #[test]
+    fn test_shade_hit_with_reflective_transparent_material() {
+        let world = world_with_glass_floor(0.5);
+        let ray = Ray::new(
+            point!(0.0, 0.0, -3.0),
+            vector!(0.0, -FRAC_1_SQRT_2, FRAC_1_SQRT_2),
+        );
+        let intersections = vec![Intersection::new(
+            2.0_f64.sqrt(),
+            world.objects()[2].as_ref(),
+        )];
+
+        let comps = intersections[0].prepare_computations(&ray, &intersections);
+
+        assert_eq!(
+            world.shade_hit(&comps, 5),
+            Color::new(0.93391, 0.69643, 0.69243)
+        );
     }
 }
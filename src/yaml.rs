@@ -0,0 +1,530 @@
+use std::fmt;
+
+// The subset of YAML used by the scene files of the book: block lists and
+// mappings nested by indentation, flow lists like `[ 1, 2, 3 ]`, flow
+// mappings, quoted and plain scalars, and `#` comments. Anchors, tags,
+// multi-line strings and multiple documents are not supported.
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Value {
+    Null,
+    Scalar(String),
+    List(Vec<Node>),
+    Map(Vec<(String, Node)>),
+}
+
+// A value along with the line it starts on, so errors can point to it
+#[derive(Debug, PartialEq, Clone)]
+pub struct Node {
+    pub value: Value,
+    pub line: usize,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct YamlError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for YamlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for YamlError {}
+
+fn error<T>(line: usize, message: impl Into<String>) -> Result<T, YamlError> {
+    Err(YamlError {
+        line,
+        message: message.into(),
+    })
+}
+
+impl Node {
+    pub fn as_str(&self) -> Result<&str, YamlError> {
+        match &self.value {
+            Value::Scalar(text) => Ok(text),
+            _ => error(self.line, "expected a text value"),
+        }
+    }
+
+    pub fn as_f64(&self) -> Result<f64, YamlError> {
+        let text = self.as_str()?;
+        match text.parse() {
+            Ok(number) => Ok(number),
+            Err(_) => error(self.line, format!("expected a number, found `{}`", text)),
+        }
+    }
+
+    pub fn as_usize(&self) -> Result<usize, YamlError> {
+        let text = self.as_str()?;
+        match text.parse() {
+            Ok(number) => Ok(number),
+            Err(_) => error(
+                self.line,
+                format!("expected a positive integer, found `{}`", text),
+            ),
+        }
+    }
+
+    pub fn as_bool(&self) -> Result<bool, YamlError> {
+        match self.as_str()? {
+            "true" => Ok(true),
+            "false" => Ok(false),
+            text => error(
+                self.line,
+                format!("expected true or false, found `{}`", text),
+            ),
+        }
+    }
+
+    pub fn as_list(&self) -> Result<&[Node], YamlError> {
+        match &self.value {
+            Value::List(items) => Ok(items),
+            _ => error(self.line, "expected a list"),
+        }
+    }
+
+    pub fn as_map(&self) -> Result<&[(String, Node)], YamlError> {
+        match &self.value {
+            Value::Map(entries) => Ok(entries),
+            _ => error(self.line, "expected a mapping"),
+        }
+    }
+
+    // the value of key when this is a mapping that has it
+    pub fn get(&self, key: &str) -> Option<&Node> {
+        match &self.value {
+            Value::Map(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Line {
+    number: usize,
+    indent: usize,
+    text: String,
+}
+
+// Removes a trailing comment. A `#` only starts a comment at the start of
+// the line or after whitespace, and never inside quotes.
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut previous = ' ';
+    for (index, c) in line.char_indices() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == '#' && previous.is_whitespace() => return &line[..index],
+            None => {}
+        }
+        previous = c;
+    }
+    line
+}
+
+fn is_item(text: &str) -> bool {
+    text == "-" || text.starts_with("- ")
+}
+
+// Splits `key: value` at the first colon followed by a space or the end of
+// the line, outside of quotes and brackets
+fn split_entry(text: &str) -> Option<(String, &str)> {
+    let mut quote = None;
+    let mut depth = 0;
+    let bytes = text.as_bytes();
+    for (index, c) in text.char_indices() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None => match c {
+                '"' | '\'' => quote = Some(c),
+                '[' | '{' => depth += 1,
+                ']' | '}' => depth -= 1,
+                ':' if depth == 0 && (index + 1 == text.len() || bytes[index + 1] == b' ') => {
+                    let key = unquote(text[..index].trim());
+                    return Some((key, text[index + 1..].trim()));
+                }
+                _ => {}
+            },
+        }
+    }
+    None
+}
+
+fn unquote(text: &str) -> String {
+    let quoted = text.len() >= 2
+        && ((text.starts_with('"') && text.ends_with('"'))
+            || (text.starts_with('\'') && text.ends_with('\'')));
+    if quoted {
+        text[1..text.len() - 1].to_string()
+    } else {
+        text.to_string()
+    }
+}
+
+struct Parser {
+    lines: Vec<Line>,
+    pos: usize,
+}
+
+impl Parser {
+    fn current(&self) -> Option<&Line> {
+        self.lines.get(self.pos)
+    }
+
+    fn parse_block(&mut self, indent: usize) -> Result<Node, YamlError> {
+        let text = &self.lines[self.pos].text;
+        if is_item(text) {
+            self.parse_list(indent)
+        } else {
+            self.parse_map(indent)
+        }
+    }
+
+    // the block nested under the line just read, or null when there is none
+    fn parse_nested(&mut self, indent: usize, line: usize) -> Result<Node, YamlError> {
+        match self.current() {
+            Some(next) if next.indent > indent => {
+                let indent = next.indent;
+                self.parse_block(indent)
+            }
+            _ => Ok(Node {
+                value: Value::Null,
+                line,
+            }),
+        }
+    }
+
+    fn parse_list(&mut self, indent: usize) -> Result<Node, YamlError> {
+        let first = self.lines[self.pos].number;
+        let mut items = vec![];
+
+        while let Some(line) = self.current() {
+            if line.indent != indent || !is_item(&line.text) {
+                break;
+            }
+            let line = line.clone();
+            let rest = line.text[1..].trim_start();
+
+            let item = if rest.is_empty() {
+                self.pos += 1;
+                self.parse_nested(indent, line.number)?
+            } else if is_item(rest) || split_entry(rest).is_some() {
+                // the rest of the line is the first line of a nested block,
+                // the lines lined up with it below are part of it too
+                let nested_indent = indent + line.text.len() - rest.len();
+                self.lines[self.pos] = Line {
+                    number: line.number,
+                    indent: nested_indent,
+                    text: rest.to_string(),
+                };
+                self.parse_block(nested_indent)?
+            } else {
+                self.pos += 1;
+                parse_flow(rest, line.number)?
+            };
+            items.push(item);
+        }
+
+        Ok(Node {
+            value: Value::List(items),
+            line: first,
+        })
+    }
+
+    fn parse_map(&mut self, indent: usize) -> Result<Node, YamlError> {
+        let first = self.lines[self.pos].number;
+        let mut entries: Vec<(String, Node)> = vec![];
+
+        while let Some(line) = self.current() {
+            if line.indent != indent || is_item(&line.text) {
+                break;
+            }
+            let line = line.clone();
+            let (key, rest) = match split_entry(&line.text) {
+                Some(entry) => entry,
+                None => {
+                    return error(
+                        line.number,
+                        format!("expected `key: value`, found `{}`", line.text),
+                    )
+                }
+            };
+            if entries.iter().any(|(k, _)| *k == key) {
+                return error(line.number, format!("duplicate key `{}`", key));
+            }
+            self.pos += 1;
+
+            let value = if !rest.is_empty() {
+                parse_flow(rest, line.number)?
+            } else {
+                match self.current() {
+                    // a list may sit at the same indentation as its key
+                    Some(next) if next.indent == indent && is_item(&next.text) => {
+                        self.parse_list(indent)?
+                    }
+                    _ => self.parse_nested(indent, line.number)?,
+                }
+            };
+            entries.push((key, value));
+        }
+
+        Ok(Node {
+            value: Value::Map(entries),
+            line: first,
+        })
+    }
+}
+
+// Parses a value written on a single line: a flow list, a flow mapping or a
+// scalar
+fn parse_flow(text: &str, line: usize) -> Result<Node, YamlError> {
+    if !text.starts_with('[') && !text.starts_with('{') {
+        return Ok(scalar(text, line));
+    }
+
+    let chars: Vec<char> = text.chars().collect();
+    let mut pos = 0;
+    let node = parse_flow_value(&chars, &mut pos, line)?;
+    skip_spaces(&chars, &mut pos);
+    if pos < chars.len() {
+        let rest: String = chars[pos..].iter().collect();
+        return error(line, format!("unexpected `{}`", rest));
+    }
+    Ok(node)
+}
+
+fn scalar(text: &str, line: usize) -> Node {
+    let text = text.trim();
+    let value = if text.is_empty() || text == "~" || text == "null" {
+        Value::Null
+    } else {
+        Value::Scalar(unquote(text))
+    };
+    Node { value, line }
+}
+
+fn skip_spaces(chars: &[char], pos: &mut usize) {
+    while *pos < chars.len() && chars[*pos].is_whitespace() {
+        *pos += 1;
+    }
+}
+
+fn parse_flow_value(chars: &[char], pos: &mut usize, line: usize) -> Result<Node, YamlError> {
+    skip_spaces(chars, pos);
+    match chars.get(*pos) {
+        Some('[') => {
+            *pos += 1;
+            let mut items = vec![];
+            loop {
+                skip_spaces(chars, pos);
+                if chars.get(*pos) == Some(&']') {
+                    *pos += 1;
+                    break;
+                }
+                items.push(parse_flow_value(chars, pos, line)?);
+                skip_spaces(chars, pos);
+                match chars.get(*pos) {
+                    Some(',') => *pos += 1,
+                    Some(']') => {}
+                    _ => return error(line, "expected `,` or `]` in list"),
+                }
+            }
+            Ok(Node {
+                value: Value::List(items),
+                line,
+            })
+        }
+        Some('{') => {
+            *pos += 1;
+            let mut entries = vec![];
+            loop {
+                skip_spaces(chars, pos);
+                if chars.get(*pos) == Some(&'}') {
+                    *pos += 1;
+                    break;
+                }
+                let key = read_plain(chars, pos, true);
+                skip_spaces(chars, pos);
+                if chars.get(*pos) != Some(&':') {
+                    return error(line, format!("expected `:` after `{}`", key));
+                }
+                *pos += 1;
+                let value = parse_flow_value(chars, pos, line)?;
+                entries.push((unquote(key.trim()), value));
+                skip_spaces(chars, pos);
+                match chars.get(*pos) {
+                    Some(',') => *pos += 1,
+                    Some('}') => {}
+                    _ => return error(line, "expected `,` or `}` in mapping"),
+                }
+            }
+            Ok(Node {
+                value: Value::Map(entries),
+                line,
+            })
+        }
+        Some(_) => {
+            let text = read_plain(chars, pos, false);
+            Ok(scalar(&text, line))
+        }
+        None => error(line, "unexpected end of line"),
+    }
+}
+
+// reads a scalar inside a flow collection, up to the next delimiter
+fn read_plain(chars: &[char], pos: &mut usize, is_key: bool) -> String {
+    let mut text = String::new();
+    let mut quote = None;
+    while let Some(&c) = chars.get(*pos) {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == ',' || c == ']' || c == '}' || (is_key && c == ':') => break,
+            None => {}
+        }
+        text.push(c);
+        *pos += 1;
+    }
+    text
+}
+
+pub fn parse_yaml(input: &str) -> Result<Node, YamlError> {
+    let mut lines = vec![];
+    for (index, raw) in input.lines().enumerate() {
+        let text = strip_comment(raw).trim_end();
+        if text.trim().is_empty() || text == "---" {
+            continue;
+        }
+        if text.starts_with('\t') {
+            return error(index + 1, "tabs can't be used for indentation");
+        }
+        let content = text.trim_start();
+        lines.push(Line {
+            number: index + 1,
+            indent: text.len() - content.len(),
+            text: content.to_string(),
+        });
+    }
+
+    if lines.is_empty() {
+        return Ok(Node {
+            value: Value::Null,
+            line: 1,
+        });
+    }
+
+    let mut parser = Parser { lines, pos: 0 };
+    let indent = parser.lines[0].indent;
+    let root = parser.parse_block(indent)?;
+    if let Some(line) = parser.current() {
+        return error(line.number, "unexpected indentation");
+    }
+
+    Ok(root)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(value: &str, line: usize) -> Node {
+        Node {
+            value: Value::Scalar(value.to_string()),
+            line,
+        }
+    }
+
+    #[test]
+    fn test_parsing_mapping() {
+        let node = parse_yaml("width: 100\nname: \"a: b\" # comment\nempty:\n").unwrap();
+
+        assert_eq!(
+            node.value,
+            Value::Map(vec![
+                ("width".to_string(), text("100", 1)),
+                ("name".to_string(), text("a: b", 2)),
+                (
+                    "empty".to_string(),
+                    Node {
+                        value: Value::Null,
+                        line: 3
+                    }
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parsing_flow_collections() {
+        let node =
+            parse_yaml("from: [ -6, 6, [1, 2] ]\nmaterial: { color: [1, 0, 0], ambient: 0.1 }")
+                .unwrap();
+
+        let from = node.get("from").unwrap().as_list().unwrap();
+        assert_eq!(from.len(), 3);
+        assert_eq!(from[0].as_f64(), Ok(-6.0));
+        assert_eq!(from[2].as_list().unwrap()[1].as_f64(), Ok(2.0));
+
+        let material = node.get("material").unwrap();
+        assert_eq!(material.get("ambient").unwrap().as_f64(), Ok(0.1));
+        assert_eq!(material.get("color").unwrap().as_list().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_parsing_list_of_mappings() {
+        let input = "
+# a comment
+- add: camera
+  width: 100
+
+- define: standard-transform
+  value:
+    - [ translate, 1, -1, 1 ]
+    - [ scale, 0.5, 0.5, 0.5 ]
+- add: sphere
+  transform:
+  - standard-transform
+";
+        let node = parse_yaml(input).unwrap();
+        let items = node.as_list().unwrap();
+
+        assert_eq!(items.len(), 3);
+        assert_eq!(items[0].line, 3);
+        assert_eq!(items[0].get("add").unwrap().as_str(), Ok("camera"));
+        assert_eq!(items[0].get("width").unwrap().as_usize(), Ok(100));
+        let value = items[1].get("value").unwrap().as_list().unwrap();
+        assert_eq!(value.len(), 2);
+        assert_eq!(value[1].line, 9);
+        let transform = items[2].get("transform").unwrap().as_list().unwrap();
+        assert_eq!(transform[0].as_str(), Ok("standard-transform"));
+    }
+
+    #[test]
+    fn test_errors_point_to_the_line() {
+        assert_eq!(
+            parse_yaml("- add: camera\n     width: 100")
+                .unwrap_err()
+                .line,
+            2
+        );
+        assert_eq!(parse_yaml("a: 1\nnot a mapping").unwrap_err().line, 2);
+        assert_eq!(parse_yaml("a: 1\na: 2").unwrap_err().line, 2);
+        assert_eq!(parse_yaml("\n\na: [1, 2").unwrap_err().line, 3);
+    }
+
+    #[test]
+    fn test_conversion_errors() {
+        let node = parse_yaml("a: abc\nb: [1]").unwrap();
+
+        assert_eq!(node.get("a").unwrap().as_f64().unwrap_err().line, 1);
+        assert!(node.get("b").unwrap().as_str().is_err());
+        assert!(node.get("missing").is_none());
+    }
+}